@@ -1,7 +1,9 @@
 
+use crate::memory::map::Handler;
+
 use sdl2::event::Event;
 use sdl2::keyboard::Keycode;
-#[derive(Debug, Copy, Clone)]
+#[derive(Debug, Copy, Clone, Hash)]
 pub enum KeyStatus {
     Idle = 0,
     A = 1 << 0,
@@ -14,6 +16,7 @@ pub enum KeyStatus {
     Right = 1 << 7,
 }
 
+#[derive(Hash)]
 pub struct Controller {
     addr: u8,
     strobe: bool,
@@ -68,10 +71,36 @@ impl Controller {
     }
 }
 
+// $4000-$40FF: only the first controller is plugged, the APU registers are write only
+impl Handler for Controller {
+    fn read(&mut self, addr: u16, open_bus: u8) -> u8 {
+        match addr {
+            // Only the lower bits are driven by the controllers
+            0x4016 => Controller::read(self) | (open_bus & 0xE0),
+            0x4017 => open_bus & 0xE0,
+            _ => open_bus,
+        }
+    }
+    fn write(&mut self, addr: u16, v: u8) {
+        if addr == 0x4016 {
+            Controller::write(self, v);
+        }
+    }
+    fn inspect(&self, addr: u16, open_bus: u8) -> u8 {
+        let bits = if self.strobe { self.key as u8 } else { self.addr };
+        match addr {
+            0x4016 => (bits & 1) | (open_bus & 0xE0),
+            0x4017 => open_bus & 0xE0,
+            _ => open_bus,
+        }
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use super::Controller;
     use super::KeyStatus;
+    use crate::memory::map::Handler;
 
     #[test]
     fn read_without_strobe_should_shift_right() {
@@ -105,4 +134,13 @@ mod tests {
         controller.write(0xc0);
         assert!(!controller.strobe);
     }
-}
\ No newline at end of file
+    #[test]
+    fn inspect_should_not_shift() {
+        let mut controller = Controller::new();
+        controller.addr = KeyStatus::A as u8;
+        assert_eq!(controller.inspect(0x4016, 0x40), 0x41);
+        assert_eq!(controller.addr, KeyStatus::A as u8);
+        assert_eq!(Handler::read(&mut controller, 0x4016, 0x40), 0x41);
+        assert_eq!(controller.inspect(0x4016, 0x40), 0x40);
+    }
+}
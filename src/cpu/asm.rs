@@ -0,0 +1,463 @@
+// Two pass assembler for the programs of the CPU tests.
+// Mnemonics and encodings come from the opcode table of the CPU variant.
+//
+//         .org $0200
+// start:  LDX #<table      ; labels end with ':'
+//         LDA table+1,X
+//         BNE start
+// table:  .byte $01, %10, 3
+//         .word start, *   ; '*' is the current address
+//
+// Operands of unknown value on the first pass (forward references) are assembled
+// with the absolute addressing, 'a:' forces it when the value is known.
+use crate::cpu::disasm::Instr;
+use crate::cpu::opcode::Addressing;
+use crate::cpu::Variant;
+
+use std::collections::HashMap;
+
+#[derive(Debug, PartialEq)]
+pub struct Program {
+    pub origin: u16,
+    pub bytes: Vec<u8>,
+}
+
+#[derive(Debug)]
+enum Index {
+    None,
+    X,
+    Y,
+}
+
+#[derive(Debug)]
+enum Operand {
+    None,
+    Accumulator,
+    Immediate(String),
+    Direct(String, Index),
+    Indirect(String),
+    IndexedIndirect(String),
+    IndirectIndexed(String),
+    BitBranch(String, String),
+}
+
+#[derive(Debug)]
+enum Statement {
+    Empty,
+    Org(String),
+    Define(String, String),
+    Byte(Vec<String>),
+    Word(Vec<String>),
+    Instruction(String, Operand),
+}
+
+struct Line {
+    number: usize,
+    labels: Vec<String>,
+    statement: Statement,
+    // Chosen on the first pass so that the size cannot change on the second one
+    opcode: Option<u8>,
+}
+
+struct Assembler {
+    variant: Variant,
+    mnemonics: Vec<String>,
+    symbols: HashMap<String, i32>,
+}
+
+pub fn assemble(variant: Variant, source: &str) -> Result<Program, String> {
+    let mut lines = Vec::new();
+    for (i, text) in source.lines().enumerate() {
+        lines.push(parse_line(i + 1, text).map_err(|e| format!("line {}: {}", i + 1, e))?);
+    }
+    let mut assembler = Assembler {
+        variant,
+        mnemonics: (0..=0xFFu8).map(|code| Instr::decode(variant, &[code], 0).mnemonic()).collect(),
+        symbols: HashMap::new(),
+    };
+    assembler.first_pass(&mut lines)?;
+    assembler.second_pass(&lines)
+}
+
+fn is_symbol(name: &str) -> bool {
+    let mut chars = name.chars();
+    matches!(chars.next(), Some(c) if c.is_ascii_alphabetic() || c == '_')
+        && chars.all(|c| c.is_ascii_alphanumeric() || c == '_')
+}
+
+fn split_list(text: &str) -> Vec<String> {
+    text.split(',').map(|e| e.trim().to_string()).collect()
+}
+
+fn parse_line(number: usize, text: &str) -> Result<Line, String> {
+    let mut rest = text.split(';').next().unwrap_or("").trim();
+    let mut labels = Vec::new();
+    while let Some(colon) = rest.find(':') {
+        let name = rest[..colon].trim();
+        // 'a:' is the absolute prefix of an operand, not a label
+        if !is_symbol(name) || name.eq_ignore_ascii_case("a") {
+            break;
+        }
+        labels.push(name.to_string());
+        rest = rest[colon + 1..].trim();
+    }
+    let statement = if rest.is_empty() {
+        Statement::Empty
+    } else if let Some(eq) = rest.find('=') {
+        let name = rest[..eq].trim();
+        if !is_symbol(name) {
+            return Err(format!("invalid symbol name '{}'", name));
+        }
+        Statement::Define(name.to_string(), rest[eq + 1..].trim().to_string())
+    } else {
+        let (word, args) = match rest.find(char::is_whitespace) {
+            Some(i) => (&rest[..i], rest[i..].trim()),
+            None => (rest, ""),
+        };
+        match word.to_lowercase().as_str() {
+            ".org" => Statement::Org(args.to_string()),
+            ".byte" | ".db" => Statement::Byte(split_list(args)),
+            ".word" | ".dw" => Statement::Word(split_list(args)),
+            d if d.starts_with('.') => return Err(format!("unknown directive '{}'", word)),
+            _ => Statement::Instruction(word.to_uppercase(), parse_operand(args)),
+        }
+    };
+    Ok(Line { number, labels, statement, opcode: None })
+}
+
+fn parse_operand(text: &str) -> Operand {
+    let text: String = text.chars().filter(|c| !c.is_whitespace()).collect();
+    let upper = text.to_uppercase();
+    if text.is_empty() {
+        Operand::None
+    } else if upper == "A" {
+        Operand::Accumulator
+    } else if let Some(value) = text.strip_prefix('#') {
+        Operand::Immediate(value.to_string())
+    } else if text.starts_with('(') && upper.ends_with("),Y") {
+        Operand::IndirectIndexed(text[1..text.len() - 3].to_string())
+    } else if text.starts_with('(') && upper.ends_with(",X)") {
+        Operand::IndexedIndirect(text[1..text.len() - 3].to_string())
+    } else if text.starts_with('(') && text.ends_with(')') {
+        Operand::Indirect(text[1..text.len() - 1].to_string())
+    } else if upper.ends_with(",X") {
+        Operand::Direct(text[..text.len() - 2].to_string(), Index::X)
+    } else if upper.ends_with(",Y") {
+        Operand::Direct(text[..text.len() - 2].to_string(), Index::Y)
+    } else if let Some(comma) = text.find(',') {
+        Operand::BitBranch(text[..comma].to_string(), text[comma + 1..].to_string())
+    } else {
+        Operand::Direct(text, Index::None)
+    }
+}
+
+impl Assembler {
+    // Value of an expression, None when it uses a symbol not defined yet.
+    // Terms are numbers ($hex, %binary, decimal), symbols and '*', with unary
+    // '<' (low byte), '>' (high byte), '-' and binary '+', '-' from left to right.
+    fn eval(&self, expr: &str, pc: u16) -> Result<Option<i32>, String> {
+        let expr = expr.trim();
+        if expr.is_empty() {
+            return Err(String::from("missing value"));
+        }
+        let mut total = Some(0);
+        let mut sign = 1;
+        let mut start = 0;
+        let bytes = expr.as_bytes();
+        for i in 0..=bytes.len() {
+            // A sign right after the start or another operator is unary
+            let is_operator = i < bytes.len() && (bytes[i] == b'+' || bytes[i] == b'-') && i > start
+                && !matches!(bytes[i - 1], b'+' | b'-' | b'<' | b'>');
+            if i == bytes.len() || is_operator {
+                let term = self.term(&expr[start..i], pc)?;
+                total = match (total, term) {
+                    (Some(total), Some(term)) => Some(total + sign * term),
+                    _ => None,
+                };
+                if i < bytes.len() {
+                    sign = if bytes[i] == b'+' { 1 } else { -1 };
+                    start = i + 1;
+                }
+            }
+        }
+        Ok(total)
+    }
+    fn term(&self, term: &str, pc: u16) -> Result<Option<i32>, String> {
+        let term = term.trim();
+        let number = |digits: &str, radix: u32| {
+            i32::from_str_radix(digits, radix).map(Some).map_err(|_| format!("invalid number '{}'", term))
+        };
+        if let Some(inner) = term.strip_prefix('<') {
+            Ok(self.term(inner, pc)?.map(|v| v & 0xFF))
+        } else if let Some(inner) = term.strip_prefix('>') {
+            Ok(self.term(inner, pc)?.map(|v| (v >> 8) & 0xFF))
+        } else if let Some(inner) = term.strip_prefix('-') {
+            Ok(self.term(inner, pc)?.map(|v| -v))
+        } else if let Some(digits) = term.strip_prefix('$') {
+            number(digits, 16)
+        } else if let Some(digits) = term.strip_prefix('%') {
+            number(digits, 2)
+        } else if term == "*" {
+            Ok(Some(pc as i32))
+        } else if term.starts_with(|c: char| c.is_ascii_digit()) {
+            number(term, 10)
+        } else if is_symbol(term) {
+            Ok(self.symbols.get(term).copied())
+        } else {
+            Err(format!("invalid expression '{}'", term))
+        }
+    }
+    fn resolve(&self, expr: &str, pc: u16) -> Result<i32, String> {
+        self.eval(expr, pc)?.ok_or(format!("undefined symbol in '{}'", expr))
+    }
+    fn define(&mut self, name: &str, value: i32) -> Result<(), String> {
+        if self.symbols.insert(name.to_string(), value).is_some() {
+            return Err(format!("'{}' is already defined", name));
+        }
+        Ok(())
+    }
+    fn find(&self, mnemonic: &str, mode: Addressing) -> Option<u8> {
+        let opcodes = self.variant.opcodes();
+        (0..=0xFFu8)
+            .filter(|code| self.mnemonics[*code as usize] == mnemonic && opcodes[*code as usize].mode == mode)
+            .min_by_key(|code| opcodes[*code as usize].is_unofficial(*code))
+    }
+    // The zero page form is used when the value is known to fit and no 'a:' prefix is given
+    fn select(&self, mnemonic: &str, expr: &str, short: Addressing, long: Option<Addressing>, pc: u16) -> Result<Option<u8>, String> {
+        let (expr, forced) = match expr.strip_prefix("a:").or_else(|| expr.strip_prefix("A:")) {
+            Some(expr) => (expr, true),
+            None => (expr, false),
+        };
+        let fits = matches!(self.eval(expr, pc)?, Some(v) if (0..0x100).contains(&v));
+        let short = self.find(mnemonic, short);
+        let long = long.and_then(|mode| self.find(mnemonic, mode));
+        Ok(match (short, long) {
+            (Some(short), Some(_)) if fits && !forced => Some(short),
+            (_, Some(long)) => Some(long),
+            (short, None) => short,
+        })
+    }
+    fn opcode(&self, mnemonic: &str, operand: &Operand, pc: u16) -> Result<u8, String> {
+        let code = match operand {
+            Operand::None => self.find(mnemonic, Addressing::Implied).or_else(|| self.find(mnemonic, Addressing::Accumulator)),
+            Operand::Accumulator => self.find(mnemonic, Addressing::Accumulator),
+            Operand::Immediate(_) => self.find(mnemonic, Addressing::Immediate),
+            Operand::Direct(expr, Index::None) => match self.find(mnemonic, Addressing::Relative) {
+                Some(code) => Some(code),
+                None => self.select(mnemonic, expr, Addressing::ZeroPage, Some(Addressing::Absolute), pc)?,
+            },
+            Operand::Direct(expr, Index::X) => self.select(mnemonic, expr, Addressing::ZeroPageX, Some(Addressing::AbsoluteX), pc)?,
+            Operand::Direct(expr, Index::Y) => self.select(mnemonic, expr, Addressing::ZeroPageY, Some(Addressing::AbsoluteY), pc)?,
+            Operand::Indirect(expr) => {
+                self.select(mnemonic, expr, Addressing::ZeroPageIndirect, Some(Addressing::IndirectAbsolute), pc)?
+            }
+            Operand::IndexedIndirect(expr) => {
+                self.select(mnemonic, expr, Addressing::IndexedIndirect, Some(Addressing::IndexedAbsoluteIndirect), pc)?
+            }
+            Operand::IndirectIndexed(_) => self.find(mnemonic, Addressing::IndirectIndexed),
+            Operand::BitBranch(_, _) => self.find(mnemonic, Addressing::ZeroPageRelative),
+        };
+        match code {
+            Some(code) => Ok(code),
+            None if self.mnemonics.iter().any(|m| *m == mnemonic) => Err(format!("invalid addressing mode for {}", mnemonic)),
+            None => Err(format!("unknown instruction '{}'", mnemonic)),
+        }
+    }
+    fn first_pass(&mut self, lines: &mut [Line]) -> Result<(), String> {
+        let mut pc: u16 = 0;
+        for line in lines.iter_mut() {
+            let number = line.number;
+            let error = |e: String| format!("line {}: {}", number, e);
+            if let Statement::Org(expr) = &line.statement {
+                pc = self.resolve(expr, pc).map_err(error)? as u16;
+            }
+            for label in &line.labels {
+                self.define(label, pc as i32).map_err(error)?;
+            }
+            pc = pc.wrapping_add(match &line.statement {
+                Statement::Define(name, expr) => {
+                    let value = self.resolve(expr, pc).map_err(error)?;
+                    self.define(name, value).map_err(error)?;
+                    0
+                }
+                Statement::Byte(values) => values.len() as u16,
+                Statement::Word(values) => values.len() as u16 * 2,
+                Statement::Instruction(mnemonic, operand) => {
+                    let code = self.opcode(mnemonic, operand, pc).map_err(error)?;
+                    line.opcode = Some(code);
+                    1 + self.variant.opcodes()[code as usize].mode.operand_len()
+                }
+                Statement::Empty | Statement::Org(_) => 0,
+            });
+        }
+        Ok(())
+    }
+    fn second_pass(&self, lines: &[Line]) -> Result<Program, String> {
+        let mut program = Program { origin: 0, bytes: Vec::new() };
+        let mut pc: u16 = 0;
+        for line in lines {
+            let error = |e: String| format!("line {}: {}", line.number, e);
+            let mut out = Vec::new();
+            match &line.statement {
+                Statement::Org(expr) => {
+                    let org = self.resolve(expr, pc).map_err(error)? as u16;
+                    if program.bytes.is_empty() {
+                        program.origin = org;
+                    } else if org < pc {
+                        return Err(error(format!(".org ${:04X} is below the current address ${:04X}", org, pc)));
+                    } else {
+                        program.bytes.resize((org - program.origin) as usize, 0);
+                    }
+                    pc = org;
+                }
+                Statement::Byte(values) => {
+                    for value in values {
+                        out.push(byte(self.resolve(value, pc).map_err(error)?).map_err(error)?);
+                    }
+                }
+                Statement::Word(values) => {
+                    for value in values {
+                        out.extend_from_slice(&(self.resolve(value, pc).map_err(error)? as u16).to_le_bytes());
+                    }
+                }
+                Statement::Instruction(_, operand) => {
+                    let code = line.opcode.unwrap_or(0);
+                    out = self.encode(code, operand, pc).map_err(error)?;
+                }
+                Statement::Empty | Statement::Define(_, _) => {}
+            }
+            pc = pc.wrapping_add(out.len() as u16);
+            program.bytes.extend(out);
+        }
+        Ok(program)
+    }
+    fn encode(&self, code: u8, operand: &Operand, pc: u16) -> Result<Vec<u8>, String> {
+        let mode = &self.variant.opcodes()[code as usize].mode;
+        let mut out = vec![code];
+        let expr = match operand {
+            Operand::None | Operand::Accumulator => return Ok(out),
+            Operand::BitBranch(zp, target) => {
+                out.push(byte(self.resolve(zp, pc)?)?);
+                out.push(offset(self.resolve(target, pc)?, pc.wrapping_add(3))?);
+                return Ok(out);
+            }
+            Operand::Immediate(expr) | Operand::Direct(expr, _) | Operand::Indirect(expr)
+            | Operand::IndexedIndirect(expr) | Operand::IndirectIndexed(expr) => expr,
+        };
+        let expr = expr.strip_prefix("a:").or_else(|| expr.strip_prefix("A:")).unwrap_or(expr);
+        let value = self.resolve(expr, pc)?;
+        match mode {
+            Addressing::Relative => out.push(offset(value, pc.wrapping_add(2))?),
+            _ if mode.operand_len() == 2 => out.extend_from_slice(&(value as u16).to_le_bytes()),
+            _ => out.push(byte(value)?),
+        }
+        Ok(out)
+    }
+}
+
+fn byte(value: i32) -> Result<u8, String> {
+    if (-0x80..0x100).contains(&value) {
+        Ok(value as u8)
+    } else {
+        Err(format!("value ${:X} does not fit in a byte", value))
+    }
+}
+
+fn offset(target: i32, next: u16) -> Result<u8, String> {
+    let offset = (target as u16).wrapping_sub(next) as i16;
+    if (-128..=127).contains(&offset) {
+        Ok(offset as u8)
+    } else {
+        Err(format!("branch to ${:04X} is out of range", target))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{assemble, Program};
+    use crate::cpu::disasm::Instr;
+    use crate::cpu::Variant;
+
+    fn bytes(source: &str) -> Vec<u8> {
+        assemble(Variant::Ricoh2A03, source).unwrap().bytes
+    }
+
+    #[test]
+    fn every_official_opcode_should_assemble_back() {
+        for variant in [Variant::Ricoh2A03, Variant::Wdc65C02].iter() {
+            for code in 0..=0xFFu8 {
+                let instr = Instr::decode(*variant, &[code, 0x12, 0x34], 0x8000);
+                if instr.is_unofficial() || instr.text() == "JAM" {
+                    continue;
+                }
+                let program = assemble(*variant, &format!(".org $8000\n{}", instr.text())).unwrap();
+                assert_eq!(program.bytes, instr.bytes(), "{:?} {}", variant, instr.text());
+            }
+        }
+    }
+    #[test]
+    fn zero_page_should_be_used_when_value_fits() {
+        assert_eq!(bytes("LDA $10"), vec![0xA5, 0x10]);
+        assert_eq!(bytes("LDA $0010"), vec![0xA5, 0x10]);
+        assert_eq!(bytes("LDA a:$10"), vec![0xAD, 0x10, 0x00]);
+        assert_eq!(bytes("LDA $10,Y"), vec![0xB9, 0x10, 0x00]);
+        assert_eq!(bytes("LDX $10,Y"), vec![0xB6, 0x10]);
+        assert_eq!(bytes("ASL\nASL A"), vec![0x0A, 0x0A]);
+        assert_eq!(bytes("NOP\nSBC #1"), vec![0xEA, 0xE9, 0x01]);
+    }
+    #[test]
+    fn labels_and_expressions() {
+        let source = "
+            .org $C000
+            ptr = $20
+    start:  LDX #<table      ; forward reference
+            LDA table+1,X
+            STA (ptr),Y
+            BNE start
+    table:  .byte $01, %10, 3, -1
+            .word start, *, >table
+    ";
+        let program = assemble(Variant::Ricoh2A03, source).unwrap();
+        let expected = vec![
+            0xA2, 0x09, 0xBD, 0x0A, 0xC0, 0x91, 0x20, 0xD0, 0xF7,
+            0x01, 0x02, 0x03, 0xFF, 0x00, 0xC0, 0x0D, 0xC0, 0xC0, 0x00,
+        ];
+        assert_eq!(program, Program { origin: 0xC000, bytes: expected });
+    }
+    #[test]
+    fn forward_zero_page_reference_should_stay_absolute() {
+        assert_eq!(bytes("LDA value\nvalue = $10"), vec![0xAD, 0x10, 0x00]);
+    }
+    #[test]
+    fn org_should_fill_gaps() {
+        let program = assemble(Variant::Ricoh2A03, ".org $8000\nNOP\n.org $8003\nRTS").unwrap();
+        assert_eq!(program, Program { origin: 0x8000, bytes: vec![0xEA, 0x00, 0x00, 0x60] });
+    }
+    #[test]
+    fn cmos_instructions() {
+        let source = "
+            .org $0200
+    loop:   BBR3 $10,loop
+            SMB3 $10
+            STZ $10
+            BRA loop
+            JMP ($0300,X)
+            LDA ($10)
+    ";
+        let program = assemble(Variant::Wdc65C02, source).unwrap();
+        let expected =
+            vec![0x3F, 0x10, 0xFD, 0xB7, 0x10, 0x64, 0x10, 0x80, 0xF7, 0x7C, 0x00, 0x03, 0xB2, 0x10];
+        assert_eq!(program.bytes, expected);
+        assert!(assemble(Variant::Ricoh2A03, "STZ $10").is_err());
+    }
+    #[test]
+    fn errors_should_report_the_line() {
+        let error = |source| assemble(Variant::Ricoh2A03, source).unwrap_err();
+        assert_eq!(error("NOP\nFOO"), "line 2: unknown instruction 'FOO'");
+        assert_eq!(error("LDA missing"), "line 1: undefined symbol in 'missing'");
+        assert_eq!(error("STA #$10"), "line 1: invalid addressing mode for STA");
+        assert_eq!(error("LDA #$100"), "line 1: value $100 does not fit in a byte");
+        assert_eq!(error("x:\nx:"), "line 2: 'x' is already defined");
+        assert_eq!(error(".org $8000\nBNE $8100"), "line 2: branch to $8100 is out of range");
+    }
+}
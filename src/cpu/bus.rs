@@ -1,82 +1,381 @@
 use crate::controller::Controller;
+use crate::cpu::interrupt::IrqLine;
 use crate::cpu::memory::Ram;
-use crate::memory::Memory;
+use crate::memory::map::{Handler, HandlerId, MemoryMap};
 use crate::rom::Cartbridge;
 use crate::ppu::Ppu;
+use crate::ppu::PpuStatus;
 
 use std::fmt;
 
 pub struct CpuBus<'a> {
-    ram: &'a mut Ram,
-    rom: &'a mut Cartbridge,
     ppu: &'a mut Ppu,
-    controller: &'a mut Controller,
+    irq: &'a mut IrqLine,
+    map: MemoryMap<'a>,
+    // The PPU is also clocked by the bus, its registers are served outside of the map
+    ppu_id: HandlerId,
+    // Page written to $4014, handed to the CPU right after the write
+    oam_dma: Option<u8>,
+    frame_complete: bool,
+    // Last value driven on the data bus, read back from unmapped addresses
+    open_bus: u8,
 }
 
 pub trait Bus {
     fn peek(&mut self, i: u16) -> u8;
     fn write(&mut self, i: u16, v: u8) -> u8;
+    // Side effect free read, used by the tracer
+    fn inspect(&mut self, i: u16) -> u8 {
+        self.peek(i)
+    }
+
+    // Called once per CPU cycle, right before the read or write of that cycle
+    fn tick(&mut self) {}
+    fn poll_nmi(&mut self) -> bool {
+        false
+    }
+    fn irq_line(&mut self) -> bool {
+        false
+    }
+
+    // DMA requests, the OAM one is polled after every write and served on the next read cycle.
+    // A request is returned once.
+    fn poll_oam_dma(&mut self) -> Option<u8> {
+        None
+    }
+    // Address of the next DMC sample byte, delivered back through dmc_dma_done
+    fn poll_dmc_dma(&mut self) -> Option<u16> {
+        None
+    }
+    fn dmc_dma_done(&mut self, _v: u8) {}
 }
 
 impl<'a> CpuBus<'a> {
-    pub fn new(ram: &'a mut Ram, rom: &'a mut Cartbridge, ppu: &'a mut Ppu, controller: &'a mut Controller) -> CpuBus<'a> {
+    pub fn new(ram: &'a mut Ram, rom: &'a mut Cartbridge, ppu: &'a mut Ppu, controller: &'a mut Controller, irq: &'a mut IrqLine) -> CpuBus<'a> {
+        let mut map = MemoryMap::new();
+        // 2KB mirrored 4 times
+        map.register(0x0000, 0x1FFF, ram);
+        // 8 registers mirrored every 8 bytes
+        let ppu_id = map.reserve(0x2000, 0x3FFF);
+        map.register(0x4000, 0x40FF, controller);
+        // $4100-$5FFF is the expansion area, unused by NROM. PRG-RAM and PRG-ROM go through the mapper.
+        map.register(0x6000, 0xFFFF, rom);
         Self {
-            ram,
-            rom,
             ppu,
-            controller,
+            irq,
+            map,
+            ppu_id,
+            oam_dma: None,
+            frame_complete: false,
+            open_bus: 0,
+        }
+    }
+    pub fn is_frame_complete(&self) -> bool {
+        self.frame_complete
+    }
+    pub fn ppu_position(&self) -> (i16, i16) {
+        self.ppu.position()
+    }
+    // Plugs a device on the pages from start to end included, such as mapper registers or an
+    // expansion port device
+    #[allow(dead_code)]
+    pub fn register(&mut self, start: u16, end: u16, handler: &'a mut dyn Handler) -> HandlerId {
+        self.map.register(start, end, handler)
+    }
+    fn handler(&mut self, addr: u16) -> Option<&mut (dyn Handler + 'a)> {
+        if self.map.id(addr) == Some(self.ppu_id) {
+            return Some(self.ppu);
         }
+        self.map.handler(addr)
     }
 }
 
 impl<'a> Bus for CpuBus<'a> {
     fn peek(&mut self, i: u16) -> u8 {
-        match i & 0xFFFF {
-            0..=0x1FFF => self.ram.peek(i),
-            0x2000..=0x3FFF => self.ppu.peek(i),
-            0x4000..=0x4014 => panic!("Not implemented yet !"),
-            0x4015 => 0,
-            0x4016 => self.controller.read(),
-            0x4017 => self.controller.read(),
-            0x6000..=0x7FFF => self.ram.peek(i - 0x6000),
-            0x8000..=0xBFFF if self.rom.get_size() <= 0x4010 => self.rom.peek(i-0x8000),
-            0x8000..=0xBFFF => self.rom.peek(i - 0x8000),
-            0xC000..=0xFFFF => self.rom.peek(i - 0xC000),
-            _ => panic!("Wrong index => {:x?}", i),
-        }
+        let open_bus = self.open_bus;
+        let v = match self.handler(i) {
+            Some(handler) => handler.read(i, open_bus),
+            None => open_bus,
+        };
+        self.open_bus = v;
+        v
     }
     fn write(&mut self, i: u16, v: u8) -> u8 {
-        match i {
-            0..=0x1FFF => self.ram.write(i, v),
-            0x2000..=0x3FFF => self.ppu.write(i, v),
-            0x4000..=0x4013 => 0,
-            0x4014 => self.ppu.write_dma(v, &mut self.ram),
-            0x4015 => 0,
-            0x4016 => self.controller.write(v),
-            0x4017 => self.controller.write(v),
-            0x6000..=0x7FFF => self.ram.write(i - 0x6000, v),
-            _ => panic!("Wrong index => {:x?}", i),
+        self.open_bus = v;
+        // The DMA unit is part of the CPU, not of the devices on the bus
+        if i == 0x4014 {
+            self.oam_dma = Some(v);
         }
+        if let Some(handler) = self.handler(i) {
+            handler.write(i, v);
+        }
+        v
     }
-}
-
-impl<'a> fmt::Display for CpuBus<'a> {
-    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
-        writeln!(f, "=======RAM=======\nSize: {}", self.ram.get_size())?;
-        for (i, b) in self.ram.get_mem().iter().enumerate() {
-            if *b != 0 {
-                writeln!(f, "{:04x?} => {:x?} ", i, b)?;
+    fn inspect(&mut self, i: u16) -> u8 {
+        let open_bus = self.open_bus;
+        match self.handler(i) {
+            Some(handler) => handler.inspect(i, open_bus),
+            None => open_bus,
+        }
+    }
+    fn tick(&mut self) {
+        for _ in 0..3 {
+            match self.ppu.run() {
+                PpuStatus::RENDERING => self.frame_complete = true,
+                PpuStatus::PROCESSING => {}
             }
         }
-        writeln!(f, "=======ROM=======\nSize: {}", self.rom.get_size())?;
-        for (i, b) in self.rom.get_mem().iter().enumerate() {
-            if *b != 0 {
+    }
+    fn poll_nmi(&mut self) -> bool {
+        self.ppu.poll_nmi()
+    }
+    fn irq_line(&mut self) -> bool {
+        self.irq.is_asserted()
+    }
+    fn poll_oam_dma(&mut self) -> Option<u8> {
+        self.oam_dma.take()
+    }
+    // There is no APU yet: the DMC never fetches samples, poll_dmc_dma keeps its default
+}
+
+impl<'a> CpuBus<'a> {
+    // Non zero bytes of the devices in the map, from start to end included
+    fn dump(&self, f: &mut fmt::Formatter, start: u16, end: u16) -> fmt::Result {
+        for i in start..=end {
+            let b = match self.map.get(i) {
+                Some(handler) => handler.inspect(i, self.open_bus),
+                None => continue,
+            };
+            if b != 0 {
                 writeln!(f, "{:04x?} => {:x?} ", i, b)?;
             }
         }
+        Ok(())
+    }
+}
+
+impl<'a> fmt::Display for CpuBus<'a> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        writeln!(f, "=======RAM=======")?;
+        self.dump(f, 0x0000, 0x07FF)?;
+        writeln!(f, "=======ROM=======")?;
+        self.dump(f, 0x6000, 0xFFFF)?;
         writeln!(f, "=======PPU=======")?;
         writeln!(f, "{}", self.ppu)?;
         writeln!(f, "=================")?;
         Ok(())
     }
-}
\ No newline at end of file
+}
+#[cfg(test)]
+mod tests {
+    use super::{Bus, CpuBus};
+    use crate::controller::Controller;
+    use crate::memory::map::Handler;
+    use crate::cpu::interrupt::IrqLine;
+    use crate::cpu::memory::Ram;
+    use crate::ppu::register::Register;
+    use crate::ppu::Ppu;
+    use crate::rom::Cartbridge;
+
+    struct TestContext {
+        ram: Ram,
+        rom: Cartbridge,
+        ppu: Ppu,
+        controller: Controller,
+        irq: IrqLine,
+    }
+
+    fn create_test_context(program: &Vec<u8>) -> TestContext {
+        let mut rom = Cartbridge::new();
+        rom.load_from_vec(program);
+        TestContext {
+            ram: Ram::new(),
+            rom,
+            ppu: Ppu::new(),
+            controller: Controller::new(),
+            irq: IrqLine::new(),
+        }
+    }
+
+    #[test]
+    fn unmapped_read_should_return_last_bus_value() {
+        let mut ctx = create_test_context(&vec![0x5A, 0x40]);
+        let mut bus = CpuBus::new(&mut ctx.ram, &mut ctx.rom, &mut ctx.ppu, &mut ctx.controller, &mut ctx.irq);
+        bus.peek(0x8000);
+        assert_eq!(bus.peek(0x5000), 0x5A);
+        assert_eq!(bus.peek(0x4000), 0x5A);
+        bus.write(0x0010, 0x33);
+        assert_eq!(bus.peek(0x4018), 0x33);
+    }
+    #[test]
+    fn registered_device_should_serve_its_pages() {
+        // Latch on the expansion port, reading back what was written
+        struct Expansion(u8);
+        impl Handler for Expansion {
+            fn read(&mut self, _addr: u16, _open_bus: u8) -> u8 {
+                self.0
+            }
+            fn write(&mut self, _addr: u16, v: u8) {
+                self.0 = v;
+            }
+            fn inspect(&self, _addr: u16, _open_bus: u8) -> u8 {
+                self.0
+            }
+        }
+        let mut ctx = create_test_context(&vec![0x5A]);
+        let mut expansion = Expansion(0);
+        {
+            let mut bus = CpuBus::new(&mut ctx.ram, &mut ctx.rom, &mut ctx.ppu, &mut ctx.controller, &mut ctx.irq);
+            bus.register(0x5000, 0x5FFF, &mut expansion);
+            bus.write(0x5123, 0x3C);
+            bus.peek(0x8000);
+            assert_eq!(bus.peek(0x5FFF), 0x3C);
+            assert_eq!(bus.peek(0x4100), 0x3C);
+            assert_eq!(bus.peek(0x8000), 0x5A);
+        }
+        assert_eq!(expansion.0, 0x3C);
+    }
+    #[test]
+    fn controller_read_should_keep_open_bus_upper_bits() {
+        let mut ctx = create_test_context(&vec![0x5A, 0x40]);
+        let mut bus = CpuBus::new(&mut ctx.ram, &mut ctx.rom, &mut ctx.ppu, &mut ctx.controller, &mut ctx.irq);
+        bus.peek(0x8001);
+        assert_eq!(bus.peek(0x4016) & 0xFE, 0x40);
+    }
+    #[test]
+    fn write_only_ppu_registers_should_read_the_ppu_latch() {
+        let mut ctx = create_test_context(&vec![]);
+        let mut bus = CpuBus::new(&mut ctx.ram, &mut ctx.rom, &mut ctx.ppu, &mut ctx.controller, &mut ctx.irq);
+        bus.write(0x2003, 0x1B);
+        assert_eq!(bus.peek(0x2005), 0x1B);
+        // Mirrors of $2002, the status only drives bits 5-7
+        assert_eq!(bus.peek(0x3FFA) & 0x1F, 0x1B);
+    }
+    #[test]
+    fn writes_to_rom_should_be_ignored() {
+        let mut ctx = create_test_context(&vec![0x5A]);
+        let mut bus = CpuBus::new(&mut ctx.ram, &mut ctx.rom, &mut ctx.ppu, &mut ctx.controller, &mut ctx.irq);
+        bus.write(0x8000, 0x00);
+        bus.write(0xFFFF, 0x00);
+        bus.write(0x5000, 0x00);
+        assert_eq!(bus.peek(0x8000), 0x5A);
+    }
+    #[test]
+    fn every_address_should_be_readable_and_writable() {
+        // Zero is written everywhere, OAM DMA included
+        let mut ctx = create_test_context(&vec![]);
+        let mut bus = CpuBus::new(&mut ctx.ram, &mut ctx.rom, &mut ctx.ppu, &mut ctx.controller, &mut ctx.irq);
+        for i in 0..=0xFFFFu16 {
+            bus.peek(i);
+            bus.write(i, 0x00);
+        }
+    }
+    #[test]
+    fn ram_should_be_mirrored_every_2k() {
+        let mut ctx = create_test_context(&vec![]);
+        let mut bus = CpuBus::new(&mut ctx.ram, &mut ctx.rom, &mut ctx.ppu, &mut ctx.controller, &mut ctx.irq);
+        bus.write(0x1803, 0x77);
+        assert_eq!(bus.peek(0x0003), 0x77);
+        assert_eq!(bus.peek(0x0803), 0x77);
+        assert_eq!(bus.peek(0x1003), 0x77);
+    }
+    #[test]
+    fn prg_ram_should_not_alias_internal_ram() {
+        let mut ctx = create_test_context(&vec![]);
+        let mut bus = CpuBus::new(&mut ctx.ram, &mut ctx.rom, &mut ctx.ppu, &mut ctx.controller, &mut ctx.irq);
+        bus.write(0x6000, 0x12);
+        bus.write(0x7FFF, 0x34);
+        assert_eq!(bus.peek(0x6000), 0x12);
+        assert_eq!(bus.peek(0x7FFF), 0x34);
+        assert_eq!(bus.peek(0x0000), 0x00);
+    }
+    #[test]
+    fn prg_rom_bank_should_be_mirrored_at_c000() {
+        let mut ctx = create_test_context(&vec![0x5A]);
+        let mut bus = CpuBus::new(&mut ctx.ram, &mut ctx.rom, &mut ctx.ppu, &mut ctx.controller, &mut ctx.irq);
+        assert_eq!(bus.peek(0xC000), 0x5A);
+    }
+    #[test]
+    fn inspect_should_not_change_device_state() {
+        let mut ctx = create_test_context(&vec![]);
+        ctx.ppu.register.set_vblank();
+        let mut bus = CpuBus::new(&mut ctx.ram, &mut ctx.rom, &mut ctx.ppu, &mut ctx.controller, &mut ctx.irq);
+        assert_eq!(bus.inspect(0x2002) & 0x80, 0x80);
+        assert_eq!(bus.inspect(0x3FFA) & 0x80, 0x80);
+        assert_eq!(bus.peek(0x2002) & 0x80, 0x80);
+        assert_eq!(bus.inspect(0x2002) & 0x80, 0x00);
+    }
+    #[test]
+    fn ppu_should_ignore_writes_until_the_end_of_the_first_vblank() {
+        let mut ctx = create_test_context(&vec![]);
+        ctx.ppu.power_on(0);
+        let mut bus = CpuBus::new(&mut ctx.ram, &mut ctx.rom, &mut ctx.ppu, &mut ctx.controller, &mut ctx.irq);
+        bus.write(0x2000, 0x80);
+        bus.write(0x2003, 0x10);
+        while !bus.is_frame_complete() {
+            bus.tick();
+        }
+        bus.write(0x2001, 0x1E);
+        assert_eq!(ctx.ppu.register.get_ctrl_zero(), 0x00);
+        assert_eq!(ctx.ppu.register.get_oam_addr(), 0x10);
+        assert_eq!(ctx.ppu.register.get_ctrl_one(), 0x1E);
+    }
+    #[test]
+    fn nmi_should_fire_once_when_vblank_starts() {
+        let mut ctx = create_test_context(&vec![]);
+        let mut bus = CpuBus::new(&mut ctx.ram, &mut ctx.rom, &mut ctx.ppu, &mut ctx.controller, &mut ctx.irq);
+        bus.write(0x2000, 0x80);
+        let mut edges = 0;
+        while !bus.is_frame_complete() {
+            bus.tick();
+            if bus.poll_nmi() {
+                edges += 1;
+            }
+        }
+        assert_eq!(edges, 1);
+    }
+    #[test]
+    fn enabling_nmi_during_vblank_should_fire_an_nmi() {
+        let mut ctx = create_test_context(&vec![]);
+        ctx.ppu.register.set_vblank();
+        let mut bus = CpuBus::new(&mut ctx.ram, &mut ctx.rom, &mut ctx.ppu, &mut ctx.controller, &mut ctx.irq);
+        assert!(!bus.poll_nmi());
+        bus.write(0x2000, 0x80);
+        assert!(bus.poll_nmi());
+        assert!(!bus.poll_nmi());
+        bus.write(0x2000, 0x00);
+        assert!(!bus.poll_nmi());
+        bus.write(0x2000, 0x80);
+        assert!(bus.poll_nmi());
+        // A bus is created for every instruction, the edge detector state is kept in the PPU
+        let mut bus = CpuBus::new(&mut ctx.ram, &mut ctx.rom, &mut ctx.ppu, &mut ctx.controller, &mut ctx.irq);
+        assert!(!bus.poll_nmi());
+    }
+    #[test]
+    fn status_read_one_dot_before_vblank_should_suppress_it() {
+        let mut ctx = create_test_context(&vec![]);
+        ctx.ppu.line = 240;
+        ctx.ppu.dot = 338;
+        let mut bus = CpuBus::new(&mut ctx.ram, &mut ctx.rom, &mut ctx.ppu, &mut ctx.controller, &mut ctx.irq);
+        bus.write(0x2000, 0x80);
+        bus.tick();
+        assert_eq!(bus.ppu_position(), (241, 1));
+        assert_eq!(bus.peek(0x2002) & 0x80, 0x00);
+        for _ in 0..100 {
+            bus.tick();
+            assert!(!bus.poll_nmi());
+        }
+        assert_eq!(bus.peek(0x2002) & 0x80, 0x00);
+    }
+    #[test]
+    fn status_read_on_the_vblank_dot_should_clear_it_before_the_nmi() {
+        let mut ctx = create_test_context(&vec![]);
+        ctx.ppu.line = 240;
+        ctx.ppu.dot = 339;
+        let mut bus = CpuBus::new(&mut ctx.ram, &mut ctx.rom, &mut ctx.ppu, &mut ctx.controller, &mut ctx.irq);
+        bus.write(0x2000, 0x80);
+        bus.tick();
+        assert_eq!(bus.peek(0x2002) & 0x80, 0x80);
+        assert!(!bus.poll_nmi());
+    }
+}
@@ -0,0 +1,446 @@
+use crate::cpu::bus::Bus;
+use crate::cpu::opcode::{Addressing, Instruction, Opcode};
+use crate::cpu::Variant;
+
+use std::collections::BTreeMap;
+
+const VECTORS: [(u16, &str); 3] = [(0xFFFA, "nmi"), (0xFFFC, "reset"), (0xFFFE, "irq")];
+const BYTES_PER_LINE: usize = 16;
+
+// One decoded instruction, the operand bytes are stored little endian
+#[derive(Debug)]
+pub struct Instr {
+    pub addr: u16,
+    pub code: u8,
+    pub operand: u16,
+    pub opcode: &'static Opcode,
+}
+
+impl Instr {
+    // Missing operand bytes are read as zero
+    pub fn decode(variant: Variant, bytes: &[u8], addr: u16) -> Instr {
+        let byte = |i: usize| bytes.get(i).copied().unwrap_or(0) as u16;
+        let code = byte(0) as u8;
+        let opcode = &variant.opcodes()[code as usize];
+        let operand = match opcode.mode.operand_len() {
+            0 => 0,
+            1 => byte(1),
+            _ => byte(1) | byte(2) << 8,
+        };
+        Instr { addr, code, operand, opcode }
+    }
+    // Uses Bus::inspect, the bus is left untouched
+    pub fn read<B: Bus>(variant: Variant, bus: &mut B, addr: u16) -> Instr {
+        let bytes: Vec<u8> = (0..3).map(|i| bus.inspect(addr.wrapping_add(i))).collect();
+        Instr::decode(variant, &bytes, addr)
+    }
+    pub fn len(&self) -> u16 {
+        1 + self.opcode.mode.operand_len()
+    }
+    pub fn bytes(&self) -> Vec<u8> {
+        let operand = self.operand.to_le_bytes();
+        let mut bytes = vec![self.code];
+        bytes.extend_from_slice(&operand[..self.opcode.mode.operand_len() as usize]);
+        bytes
+    }
+    pub fn is_unofficial(&self) -> bool {
+        self.opcode.is_unofficial(self.code)
+    }
+    // The bit tested or modified by BBR/BBS/RMB/SMB is part of the mnemonic
+    pub fn mnemonic(&self) -> String {
+        match self.opcode.name {
+            Instruction::BBR | Instruction::BBS | Instruction::RMB | Instruction::SMB => {
+                format!("{:?}{}", self.opcode.name, (self.code >> 4) & 0x07)
+            }
+            _ => format!("{:?}", self.opcode.name),
+        }
+    }
+    // Destination of a branch, JMP or JSR when it is known without executing anything
+    pub fn target(&self) -> Option<u16> {
+        let next = self.addr.wrapping_add(self.len());
+        match self.opcode.mode {
+            Addressing::Relative => Some(next.wrapping_add(self.operand as u8 as i8 as u16)),
+            Addressing::ZeroPageRelative => Some(next.wrapping_add((self.operand >> 8) as u8 as i8 as u16)),
+            Addressing::Absolute => match self.opcode.name {
+                Instruction::JMP | Instruction::JSR => Some(self.operand),
+                _ => None,
+            },
+            _ => None,
+        }
+    }
+    // Operand as written in the source, without any resolved address
+    pub fn operand(&self) -> String {
+        self.format_operand(None)
+    }
+    // "LDA $10,X"
+    pub fn text(&self) -> String {
+        format!("{} {}", self.mnemonic(), self.operand()).trim_end().to_string()
+    }
+    // With labels, the operand is written for ca65: known addresses are replaced by
+    // their label and absolute operands below $0100 are forced to stay absolute
+    fn format_operand(&self, labels: Option<&BTreeMap<u16, String>>) -> String {
+        let byte = self.operand as u8;
+        let word = self.operand;
+        let address = |addr: u16| match labels.and_then(|l| l.get(&addr)) {
+            Some(label) => label.clone(),
+            None if labels.is_some() && addr < 0x100 => format!("a:${:04X}", addr),
+            None => format!("${:04X}", addr),
+        };
+        let target = || address(self.target().unwrap_or(0));
+        match self.opcode.mode {
+            Addressing::Implied => String::new(),
+            Addressing::Accumulator => String::from("A"),
+            Addressing::Immediate => format!("#${:02X}", byte),
+            Addressing::Relative => target(),
+            Addressing::ZeroPage => format!("${:02X}", byte),
+            Addressing::ZeroPageX => format!("${:02X},X", byte),
+            Addressing::ZeroPageY => format!("${:02X},Y", byte),
+            Addressing::Absolute => address(word),
+            Addressing::AbsoluteX => format!("{},X", address(word)),
+            Addressing::AbsoluteY => format!("{},Y", address(word)),
+            Addressing::IndexedIndirect => format!("(${:02X},X)", byte),
+            Addressing::IndirectIndexed => format!("(${:02X}),Y", byte),
+            Addressing::IndirectAbsolute => format!("({})", address(word)),
+            Addressing::ZeroPageIndirect => format!("(${:02X})", byte),
+            Addressing::IndexedAbsoluteIndirect => format!("({},X)", address(word)),
+            Addressing::ZeroPageRelative => format!("${:02X},{}", byte, target()),
+        }
+    }
+    // Execution never continues with the next instruction
+    fn ends_flow(&self) -> bool {
+        matches!(
+            self.opcode.name,
+            Instruction::BRK | Instruction::JAM | Instruction::STP | Instruction::RTS | Instruction::RTI
+                | Instruction::BRA | Instruction::JMP
+        )
+    }
+}
+
+// Linear sweep from addr, one line per instruction, for the debugger:
+// C000  4C F5 C5  JMP $C5F5
+pub fn listing<B: Bus>(variant: Variant, bus: &mut B, addr: u16, count: usize) -> Vec<String> {
+    let mut addr = addr;
+    let mut lines = Vec::with_capacity(count);
+    for _ in 0..count {
+        let instr = Instr::read(variant, bus, addr);
+        let bytes: Vec<String> = instr.bytes().iter().map(|b| format!("{:02X}", b)).collect();
+        let unofficial = if instr.is_unofficial() { '*' } else { ' ' };
+        lines.push(format!("{:04X}  {:<8} {}{}", addr, bytes.join(" "), unofficial, instr.text()));
+        addr = addr.wrapping_add(instr.len());
+    }
+    lines
+}
+
+#[derive(Debug, Copy, Clone, PartialEq)]
+enum Kind {
+    Data,
+    Opcode,
+    Operand,
+}
+
+// Code/data separation of a memory image, found by following every path from the
+// entry points. Jump tables and indirect jumps are not followed.
+pub struct Disassembly<'a> {
+    variant: Variant,
+    image: &'a [u8],
+    origin: u16,
+    kinds: Vec<Kind>,
+    labels: BTreeMap<u16, String>,
+}
+
+impl<'a> Disassembly<'a> {
+    // Entry points are the NMI, RESET and IRQ vectors, when the image contains them
+    pub fn from_vectors(variant: Variant, image: &'a [u8], origin: u16) -> Disassembly<'a> {
+        let mut disasm = Disassembly::new(variant, image, origin);
+        let mut entries = Vec::new();
+        for (vector, name) in VECTORS.iter() {
+            if let (Some(lo), Some(hi)) = (disasm.byte(*vector), disasm.byte(vector.wrapping_add(1))) {
+                let entry = lo as u16 | (hi as u16) << 8;
+                disasm.labels.entry(entry).or_insert_with(|| name.to_string());
+                entries.push(entry);
+            }
+        }
+        for entry in entries {
+            disasm.trace(entry);
+        }
+        disasm.finish();
+        disasm
+    }
+    // Entry points given by the caller, for code the vectors do not lead to
+    pub fn from_entries(variant: Variant, image: &'a [u8], origin: u16, entries: &[u16]) -> Disassembly<'a> {
+        let mut disasm = Disassembly::new(variant, image, origin);
+        for entry in entries {
+            disasm.trace(*entry);
+        }
+        disasm.finish();
+        disasm
+    }
+    fn new(variant: Variant, image: &'a [u8], origin: u16) -> Disassembly<'a> {
+        Disassembly {
+            variant,
+            image,
+            origin,
+            kinds: vec![Kind::Data; image.len()],
+            labels: BTreeMap::new(),
+        }
+    }
+    fn index(&self, addr: u16) -> Option<usize> {
+        let i = addr.wrapping_sub(self.origin) as usize;
+        if addr >= self.origin && i < self.image.len() {
+            Some(i)
+        } else {
+            None
+        }
+    }
+    fn byte(&self, addr: u16) -> Option<u8> {
+        self.index(addr).map(|i| self.image[i])
+    }
+    #[cfg(test)]
+    pub fn is_code(&self, addr: u16) -> bool {
+        matches!(self.index(addr), Some(i) if self.kinds[i] != Kind::Data)
+    }
+    #[cfg(test)]
+    pub fn labels(&self) -> &BTreeMap<u16, String> {
+        &self.labels
+    }
+    fn decode(&self, i: usize) -> Instr {
+        Instr::decode(self.variant, &self.image[i..], self.origin.wrapping_add(i as u16))
+    }
+    fn trace(&mut self, entry: u16) {
+        let mut pending = vec![entry];
+        while let Some(mut pc) = pending.pop() {
+            while let Some(i) = self.index(pc) {
+                let instr = self.decode(i);
+                let end = i + instr.len() as usize;
+                // Stop on code already seen, or an instruction overlapping it or the end of the image
+                if end > self.image.len() || self.kinds[i..end].iter().any(|k| *k != Kind::Data) {
+                    break;
+                }
+                self.kinds[i] = Kind::Opcode;
+                for kind in &mut self.kinds[i + 1..end] {
+                    *kind = Kind::Operand;
+                }
+                if let Some(target) = instr.target() {
+                    self.labels.entry(target).or_insert_with(|| format!("L{:04X}", target));
+                    pending.push(target);
+                } else if let Some(addr) = self.data_reference(&instr) {
+                    self.labels.entry(addr).or_insert_with(|| format!("L{:04X}", addr));
+                }
+                if instr.ends_flow() {
+                    break;
+                }
+                pc = pc.wrapping_add(instr.len());
+            }
+        }
+    }
+    fn data_reference(&self, instr: &Instr) -> Option<u16> {
+        match instr.opcode.mode {
+            Addressing::Absolute | Addressing::AbsoluteX | Addressing::AbsoluteY | Addressing::IndirectAbsolute
+            | Addressing::IndexedAbsoluteIndirect => self.index(instr.operand).map(|_| instr.operand),
+            _ => None,
+        }
+    }
+    // Labels can only be defined at the start of a line, and only inside the image
+    fn finish(&mut self) {
+        let labels = std::mem::take(&mut self.labels);
+        self.labels = labels
+            .into_iter()
+            .filter(|(addr, _)| matches!(self.index(*addr), Some(i) if self.kinds[i] != Kind::Operand))
+            .collect();
+    }
+    fn label_line(&self, out: &mut String, addr: u16) {
+        if let Some(label) = self.labels.get(&addr) {
+            out.push_str(&format!("{}:\n", label));
+        }
+    }
+    // Source that ca65 assembles back to the same bytes. Unofficial opcodes have
+    // no mnemonic in ca65 and are written as bytes.
+    pub fn to_ca65(&self) -> String {
+        let cpu = if self.variant.is_cmos() { "65C02" } else { "6502" };
+        let mut out = format!("; {} bytes at ${:04X}\n.setcpu \"{}\"\n.org ${:04X}\n", self.image.len(), self.origin, cpu, self.origin);
+        let mut i = 0;
+        while i < self.image.len() {
+            let addr = self.origin.wrapping_add(i as u16);
+            if self.kinds[i] == Kind::Opcode {
+                let instr = self.decode(i);
+                self.label_line(&mut out, addr);
+                if instr.is_unofficial() {
+                    let bytes: Vec<String> = instr.bytes().iter().map(|b| format!("${:02X}", b)).collect();
+                    out.push_str(&format!("        .byte {:<20}; {}\n", bytes.join(","), instr.text()));
+                } else {
+                    let operand = instr.format_operand(Some(&self.labels));
+                    let line = format!("        {} {}", instr.mnemonic(), operand);
+                    out.push_str(line.trim_end());
+                    out.push('\n');
+                }
+                i += instr.len() as usize;
+            } else if let Some(words) = self.vector_table(addr) {
+                out.push_str(&format!("        .word {}\n", words.join(", ")));
+                i += 6;
+            } else {
+                self.label_line(&mut out, addr);
+                let mut bytes = vec![format!("${:02X}", self.image[i])];
+                i += 1;
+                while i < self.image.len() && bytes.len() < BYTES_PER_LINE && self.kinds[i] == Kind::Data {
+                    let next = self.origin.wrapping_add(i as u16);
+                    if self.labels.contains_key(&next) || self.vector_table(next).is_some() {
+                        break;
+                    }
+                    bytes.push(format!("${:02X}", self.image[i]));
+                    i += 1;
+                }
+                out.push_str(&format!("        .byte {}\n", bytes.join(",")));
+            }
+        }
+        out
+    }
+    // The CPU vectors, written with the labels of the handlers
+    fn vector_table(&self, addr: u16) -> Option<Vec<String>> {
+        if addr != VECTORS[0].0 || self.index(0xFFFF).is_none() {
+            return None;
+        }
+        let start = self.index(addr)?;
+        if self.kinds[start..start + 6].iter().any(|k| *k != Kind::Data)
+            || (1..6).any(|n| self.labels.contains_key(&addr.wrapping_add(n)))
+            || self.labels.contains_key(&addr)
+        {
+            return None;
+        }
+        Some(
+            (0..3)
+                .map(|n| {
+                    let vector = self.image[start + n * 2] as u16 | (self.image[start + n * 2 + 1] as u16) << 8;
+                    self.labels.get(&vector).cloned().unwrap_or(format!("${:04X}", vector))
+                })
+                .collect(),
+        )
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{listing, Disassembly, Instr};
+    use crate::cpu::bus::Bus;
+    use crate::cpu::Variant;
+
+    struct FlatBus {
+        mem: Vec<u8>,
+    }
+
+    impl Bus for FlatBus {
+        fn peek(&mut self, i: u16) -> u8 {
+            self.mem[i as usize]
+        }
+        fn write(&mut self, i: u16, v: u8) -> u8 {
+            self.mem[i as usize] = v;
+            v
+        }
+    }
+
+    fn text(variant: Variant, bytes: &[u8], addr: u16) -> String {
+        Instr::decode(variant, bytes, addr).text()
+    }
+
+    #[test]
+    fn operands_should_use_assembler_syntax() {
+        let nes = Variant::Ricoh2A03;
+        assert_eq!(text(nes, &[0xEA], 0), "NOP");
+        assert_eq!(text(nes, &[0x0A], 0), "ASL A");
+        assert_eq!(text(nes, &[0xA9, 0x10], 0), "LDA #$10");
+        assert_eq!(text(nes, &[0xB5, 0x10], 0), "LDA $10,X");
+        assert_eq!(text(nes, &[0xB6, 0x10], 0), "LDX $10,Y");
+        assert_eq!(text(nes, &[0xBD, 0x34, 0x12], 0), "LDA $1234,X");
+        assert_eq!(text(nes, &[0xA1, 0x10], 0), "LDA ($10,X)");
+        assert_eq!(text(nes, &[0xB1, 0x10], 0), "LDA ($10),Y");
+        assert_eq!(text(nes, &[0x6C, 0xFF, 0x02], 0), "JMP ($02FF)");
+        let cmos = Variant::Wdc65C02;
+        assert_eq!(text(cmos, &[0xB2, 0x10], 0), "LDA ($10)");
+        assert_eq!(text(cmos, &[0x7C, 0x00, 0x03], 0), "JMP ($0300,X)");
+        assert_eq!(text(cmos, &[0xB7, 0x10], 0), "SMB3 $10");
+    }
+    #[test]
+    fn branch_targets_should_be_resolved() {
+        let nes = Variant::Ricoh2A03;
+        assert_eq!(text(nes, &[0xD0, 0xFE], 0xC000), "BNE $C000");
+        assert_eq!(text(nes, &[0x10, 0x7F], 0xC000), "BPL $C081");
+        assert_eq!(text(nes, &[0x90, 0x80], 0x0000), "BCC $FF82");
+        assert_eq!(text(Variant::Wdc65C02, &[0x3F, 0x10, 0xFD], 0x0200), "BBR3 $10,$0200");
+        assert_eq!(Instr::decode(nes, &[0x20, 0x00, 0x80], 0).target(), Some(0x8000));
+        assert_eq!(Instr::decode(nes, &[0xAD, 0x00, 0x80], 0).target(), None);
+    }
+    #[test]
+    fn listing_should_sweep_instructions() {
+        let mut mem = vec![0u8; 0x10000];
+        mem[0xC000..0xC006].copy_from_slice(&[0x4C, 0xF5, 0xC5, 0xA7, 0x10, 0xEA]);
+        let mut bus = FlatBus { mem };
+        let lines = listing(Variant::Ricoh2A03, &mut bus, 0xC000, 3);
+        assert_eq!(lines, vec!["C000  4C F5 C5  JMP $C5F5", "C003  A7 10    *LAX $10", "C005  EA        NOP"]);
+    }
+
+    // NMI/RESET/IRQ at $FFFA of an image mapped at $FFE0
+    fn image() -> Vec<u8> {
+        let mut image = vec![0u8; 0x20];
+        let program = [
+            0xA2, 0x00,       // FFE0 reset: LDX #$00
+            0xBD, 0xF0, 0xFF, // FFE2 LFFE2: LDA LFFF0,X
+            0xF0, 0x04,       // FFE5        BEQ LFFEB
+            0xE8,             // FFE7        INX
+            0x4C, 0xE2, 0xFF, // FFE8        JMP LFFE2
+            0x20, 0xEF, 0xFF, // FFEB LFFEB: JSR nmi
+            0x40,             // FFEE        RTI
+            0x40,             // FFEF nmi:   RTI
+            0x01, 0x02, 0x00, // FFF0 LFFF0: .byte
+        ];
+        image[..program.len()].copy_from_slice(&program);
+        image[0x1A..].copy_from_slice(&[0xEF, 0xFF, 0xE0, 0xFF, 0xEB, 0xFF]);
+        image
+    }
+    #[test]
+    fn descent_should_separate_code_and_data() {
+        let image = image();
+        let disasm = Disassembly::from_vectors(Variant::Ricoh2A03, &image, 0xFFE0);
+        assert!(disasm.is_code(0xFFE0));
+        assert!(disasm.is_code(0xFFEF));
+        assert!(!disasm.is_code(0xFFF0));
+        assert!(!disasm.is_code(0xFFFA));
+        let labels: Vec<(u16, &str)> = disasm.labels().iter().map(|(a, l)| (*a, l.as_str())).collect();
+        assert_eq!(
+            labels,
+            vec![(0xFFE0, "reset"), (0xFFE2, "LFFE2"), (0xFFEB, "irq"), (0xFFEF, "nmi"), (0xFFF0, "LFFF0")]
+        );
+    }
+    #[test]
+    fn ca65_output_should_use_labels() {
+        let image = image();
+        let source = Disassembly::from_vectors(Variant::Ricoh2A03, &image, 0xFFE0).to_ca65();
+        let expected = "; 32 bytes at $FFE0
+.setcpu \"6502\"
+.org $FFE0
+reset:
+        LDX #$00
+LFFE2:
+        LDA LFFF0,X
+        BEQ irq
+        INX
+        JMP LFFE2
+irq:
+        JSR nmi
+        RTI
+nmi:
+        RTI
+LFFF0:
+        .byte $01,$02,$00,$00,$00,$00,$00,$00,$00,$00
+        .word nmi, reset, irq
+";
+        assert_eq!(source, expected);
+    }
+    #[test]
+    fn ca65_output_should_keep_encoding() {
+        // LDA $0010 / *NOP $10 / BRK
+        let image = [0xAD, 0x10, 0x00, 0x04, 0x10, 0x00];
+        let source = Disassembly::from_entries(Variant::Ricoh2A03, &image, 0x8000, &[0x8000]).to_ca65();
+        assert!(source.contains("        LDA a:$0010\n"));
+        assert!(source.contains("        .byte $04,$10             ; NOP $10\n"));
+        assert!(source.ends_with("        BRK\n"));
+    }
+}
@@ -0,0 +1,166 @@
+// Runner for Klaus Dormann's 6502 test suite: https://github.com/Klaus2m5/6502_65C02_functional_tests
+//
+// The binaries are full 64K images, they start at $0400 and end in a self loop: at the
+// success address when every test passed, anywhere else on failure, with the number of
+// the failing test at $0200. The addresses below are the ones of the prebuilt bin_files,
+// rebuilding the tests with another configuration moves them.
+//
+// The binaries are not part of the repository, put them in roms/ and run:
+// cargo test functional -- --ignored --nocapture
+use crate::cpu::bus::Bus;
+use crate::cpu::register::CpuRegister;
+use crate::cpu::{Cpu, EmulationStatus, Variant};
+
+pub const START: u16 = 0x0400;
+const TEST_CASE: u16 = 0x0200;
+// Some tests run for close to 100 million cycles
+const MAX_INSTRUCTIONS: u64 = 200_000_000;
+
+pub const FUNCTIONAL_SUCCESS: u16 = 0x3469;
+pub const INTERRUPT_SUCCESS: u16 = 0x06F5;
+pub const EXTENDED_OPCODES_SUCCESS: u16 = 0x24F1;
+// Feedback register of the interrupt test, bit 0 drives IRQ and bit 1 NMI
+pub const INTERRUPT_PORT: u16 = 0xBFFC;
+const IRQ_BIT: u8 = 0x01;
+const NMI_BIT: u8 = 0x02;
+
+#[derive(Debug, PartialEq)]
+pub enum Outcome {
+    Success { instructions: u64 },
+    Trapped { pc: u16, test: u8 },
+    Jammed { pc: u16 },
+    Timeout { pc: u16 },
+}
+
+pub struct FlatBus {
+    mem: Vec<u8>,
+    port: Option<u16>,
+    nmi: bool,
+}
+
+impl FlatBus {
+    pub fn new(image: &[u8], port: Option<u16>) -> FlatBus {
+        let mut mem = vec![0u8; 0x10000];
+        let len = image.len().min(mem.len());
+        mem[..len].copy_from_slice(&image[..len]);
+        FlatBus { mem, port, nmi: false }
+    }
+}
+
+impl Bus for FlatBus {
+    fn peek(&mut self, i: u16) -> u8 {
+        self.mem[i as usize]
+    }
+    fn write(&mut self, i: u16, v: u8) -> u8 {
+        if Some(i) == self.port && v & NMI_BIT != 0 && self.mem[i as usize] & NMI_BIT == 0 {
+            self.nmi = true;
+        }
+        self.mem[i as usize] = v;
+        v
+    }
+    fn poll_nmi(&mut self) -> bool {
+        std::mem::replace(&mut self.nmi, false)
+    }
+    fn irq_line(&mut self) -> bool {
+        matches!(self.port, Some(port) if self.mem[port as usize] & IRQ_BIT != 0)
+    }
+}
+
+pub fn run(variant: Variant, image: &[u8], success: u16, port: Option<u16>) -> Outcome {
+    let mut bus = FlatBus::new(image, port);
+    let mut cpu = Cpu::with_variant(variant);
+    cpu.register.set_pc(START);
+    for instructions in 0..MAX_INSTRUCTIONS {
+        let pc = cpu.get_pc();
+        if cpu.run(&mut bus).1 == EmulationStatus::JAMMED {
+            return Outcome::Jammed { pc };
+        }
+        // JMP * or a branch to itself
+        if cpu.get_pc() == pc {
+            if pc == success {
+                return Outcome::Success { instructions };
+            }
+            return Outcome::Trapped { pc, test: bus.mem[TEST_CASE as usize] };
+        }
+    }
+    Outcome::Timeout { pc: cpu.get_pc() }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::cpu::asm;
+
+    fn load(name: &str) -> Vec<u8> {
+        let path = format!("roms/{}", name);
+        std::fs::read(&path).unwrap_or_else(|e| panic!("{} is needed by this test: {}", path, e))
+    }
+    fn image(source: &str) -> Vec<u8> {
+        let program = asm::assemble(Variant::Nmos6502, &format!(".org $0400\n{}", source)).unwrap();
+        let mut image = vec![0u8; 0x0400];
+        image.extend(program.bytes);
+        image
+    }
+
+    #[test]
+    fn self_loop_should_end_the_run() {
+        let source = "LDA #$2A\nSTA $0200\nok: JMP ok";
+        assert_eq!(run(Variant::Nmos6502, &image(source), 0x0405, None), Outcome::Success { instructions: 2 });
+        assert_eq!(run(Variant::Nmos6502, &image(source), 0x3469, None), Outcome::Trapped { pc: 0x0405, test: 0x2A });
+    }
+    #[test]
+    fn feedback_port_should_raise_interrupts() {
+        // The IRQ handler stores $01 at $10, the NMI handler $02 at $11
+        let source = "
+            CLI
+            LDA #$01
+            STA $BFFC
+            LDA #$02
+            STA $BFFC
+        done: JMP done
+        irq: LDX #$01
+            STX $10
+            LDX #$00
+            STX $BFFC
+            RTI
+        nmi: LDX #$02
+            STX $11
+            RTI
+            .org $FFFA
+            .word nmi, 0, irq
+        ";
+        let mut bus = FlatBus::new(&image(source), Some(INTERRUPT_PORT));
+        let mut cpu = Cpu::with_variant(Variant::Nmos6502);
+        cpu.register.set_pc(START);
+        for _ in 0..30 {
+            cpu.run(&mut bus);
+        }
+        assert_eq!(cpu.get_pc(), 0x040B);
+        assert_eq!(bus.mem[0x10], 0x01);
+        assert_eq!(bus.mem[0x11], 0x02);
+    }
+
+    #[test]
+    #[ignore]
+    fn functional_test() {
+        let outcome = run(Variant::Nmos6502, &load("6502_functional_test.bin"), FUNCTIONAL_SUCCESS, None);
+        println!("6502_functional_test: {:?}", outcome);
+        assert!(matches!(outcome, Outcome::Success { .. }), "{:?}", outcome);
+    }
+    #[test]
+    #[ignore]
+    fn functional_interrupt_test() {
+        let image = load("6502_interrupt_test.bin");
+        let outcome = run(Variant::Nmos6502, &image, INTERRUPT_SUCCESS, Some(INTERRUPT_PORT));
+        println!("6502_interrupt_test: {:?}", outcome);
+        assert!(matches!(outcome, Outcome::Success { .. }), "{:?}", outcome);
+    }
+    #[test]
+    #[ignore]
+    fn functional_65c02_extended_opcodes_test() {
+        let image = load("65C02_extended_opcodes_test.bin");
+        let outcome = run(Variant::Wdc65C02, &image, EXTENDED_OPCODES_SUCCESS, None);
+        println!("65C02_extended_opcodes_test: {:?}", outcome);
+        assert!(matches!(outcome, Outcome::Success { .. }), "{:?}", outcome);
+    }
+}
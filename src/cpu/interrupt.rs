@@ -0,0 +1,64 @@
+#[allow(dead_code)]
+#[derive(Debug, Copy, Clone)]
+pub enum IrqSource {
+    FrameCounter = 1 << 0,
+    Dmc = 1 << 1,
+    Mapper = 1 << 2,
+}
+
+// Level triggered IRQ line, shared by every device able to pull it low.
+// The line stays asserted as long as one of the sources holds it.
+#[derive(Hash)]
+pub struct IrqLine {
+    sources: u8,
+}
+
+impl IrqLine {
+    pub fn new() -> IrqLine {
+        IrqLine {
+            sources: 0,
+        }
+    }
+    #[allow(dead_code)]
+    pub fn assert(&mut self, source: IrqSource) {
+        self.sources |= source as u8;
+    }
+    #[allow(dead_code)]
+    pub fn release(&mut self, source: IrqSource) {
+        self.sources &= !(source as u8);
+    }
+    #[allow(dead_code)]
+    pub fn is_asserted_by(&self, source: IrqSource) -> bool {
+        self.sources & source as u8 != 0
+    }
+    pub fn is_asserted(&self) -> bool {
+        self.sources != 0
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::IrqLine;
+    use super::IrqSource;
+
+    #[test]
+    fn line_should_stay_asserted_until_every_source_releases() {
+        let mut irq = IrqLine::new();
+        assert!(!irq.is_asserted());
+        irq.assert(IrqSource::FrameCounter);
+        irq.assert(IrqSource::Mapper);
+        irq.release(IrqSource::FrameCounter);
+        assert!(irq.is_asserted());
+        assert!(irq.is_asserted_by(IrqSource::Mapper));
+        assert!(!irq.is_asserted_by(IrqSource::FrameCounter));
+        irq.release(IrqSource::Mapper);
+        assert!(!irq.is_asserted());
+    }
+    #[test]
+    fn release_should_ignore_idle_source() {
+        let mut irq = IrqLine::new();
+        irq.assert(IrqSource::Dmc);
+        irq.release(IrqSource::Mapper);
+        assert!(irq.is_asserted_by(IrqSource::Dmc));
+    }
+}
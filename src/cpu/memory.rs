@@ -1,18 +1,57 @@
+use crate::memory::map::Handler;
 use crate::memory::Memory;
+use rand::Rng;
 use std::io::prelude::*;
 
+// Content of the RAM at power on, the real chips come up with an unreliable pattern
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub enum RamInit {
+    Zeros,
+    Ones,
+    Random,
+    // $00 $00 $00 $00 $FF $FF $FF $FF repeated
+    Fceux,
+}
+
+impl RamInit {
+    pub fn from_name(name: &str) -> Option<RamInit> {
+        match name {
+            "zeros" => Some(RamInit::Zeros),
+            "ff" => Some(RamInit::Ones),
+            "random" => Some(RamInit::Random),
+            "fceux" => Some(RamInit::Fceux),
+            _ => None,
+        }
+    }
+}
+
+// 2KB, mirrored up to $1FFF
+#[derive(Hash)]
 pub struct Ram {
-    mem: [u8; 0x1FFF],
+    mem: [u8; 0x0800],
     size: usize,
 }
 
 impl Ram {
     pub fn new() -> Ram {
         Ram {
-            mem: [0; 0x1FFF],
+            mem: [0; 0x0800],
             size: 0,
         }
     }
+    // Random contents come from the machine RNG, to be reproducible from its seed
+    pub fn power_on<R: Rng>(&mut self, init: RamInit, rng: &mut R) {
+        match init {
+            RamInit::Zeros => self.mem.iter_mut().for_each(|b| *b = 0x00),
+            RamInit::Ones => self.mem.iter_mut().for_each(|b| *b = 0xFF),
+            RamInit::Random => rng.fill(&mut self.mem[..]),
+            RamInit::Fceux => {
+                for (i, b) in self.mem.iter_mut().enumerate() {
+                    *b = if i & 0x04 == 0 { 0x00 } else { 0xFF };
+                }
+            }
+        }
+    }
     #[allow(dead_code)]
     pub fn load_program(&mut self, data: &mut Vec<u8>) -> &mut Self {
         for (i, byte) in data.bytes().enumerate() {
@@ -31,13 +70,54 @@ impl Memory for Ram {
         self.size
     }
     fn peek(&self, i: u16) -> u8 {
-        self.mem[i as usize]
+        self.mem[(i & 0x07FF) as usize]
     }
     fn write(&mut self, i: u16, value: u8) -> u8 {
-        self.mem[i as usize] = value;
+        self.mem[(i & 0x07FF) as usize] = value;
         value
     }
     fn get_mem(&self) -> &[u8] {
         &self.mem[0..self.size]
     }
-}
\ No newline at end of file
+}
+
+impl Handler for Ram {
+    fn read(&mut self, addr: u16, _open_bus: u8) -> u8 {
+        Memory::peek(self, addr)
+    }
+    fn write(&mut self, addr: u16, v: u8) {
+        Memory::write(self, addr, v);
+    }
+    fn inspect(&self, addr: u16, _open_bus: u8) -> u8 {
+        Memory::peek(self, addr)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{Ram, RamInit};
+    use crate::memory::Memory;
+    use rand::rngs::StdRng;
+    use rand::SeedableRng;
+
+    #[test]
+    fn power_on_should_fill_with_pattern() {
+        let mut ram = Ram::new();
+        let mut rng = StdRng::seed_from_u64(0);
+        ram.power_on(RamInit::Fceux, &mut rng);
+        let start: Vec<u8> = (0..9).map(|i| ram.peek(i)).collect();
+        assert_eq!(start, vec![0x00, 0x00, 0x00, 0x00, 0xFF, 0xFF, 0xFF, 0xFF, 0x00]);
+        ram.power_on(RamInit::Ones, &mut rng);
+        assert!((0..0x800).all(|i| ram.peek(i) == 0xFF));
+    }
+    #[test]
+    fn random_fill_should_depend_on_seed_only() {
+        let fill = |seed| {
+            let mut ram = Ram::new();
+            ram.power_on(RamInit::Random, &mut StdRng::seed_from_u64(seed));
+            (0..0x800).map(|i| ram.peek(i)).collect::<Vec<u8>>()
+        };
+        assert_eq!(fill(42), fill(42));
+        assert_ne!(fill(42), fill(43));
+    }
+}
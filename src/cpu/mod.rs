@@ -2,764 +2,1092 @@ pub mod register;
 pub mod opcode;
 pub mod memory;
 pub mod bus;
+pub mod interrupt;
+pub mod trace;
+pub mod disasm;
+#[cfg(test)]
+pub mod asm;
+#[cfg(test)]
+mod single_step;
+#[cfg(test)]
+mod functional;
 
 use crate::cpu::bus::Bus;
 use crate::cpu::register::*;
 use crate::cpu::opcode::*;
+use crate::error::EmuError;
 use crate::Cycle;
-extern crate rand;
 
-use rand::Rng;
 use std::fmt;
 
-#[derive(PartialEq)]
+#[derive(Debug, PartialEq)]
 pub enum EmulationStatus {
     PROCESSING,
-    ERROR,
+    ERROR(EmuError),
     BREAK,
     RESET,
+    JAMMED,
+}
+
+#[derive(Debug, Copy, Clone, PartialEq, Hash)]
+pub enum Variant {
+    // NES CPU: NMOS core with the decimal mode disconnected
+    Ricoh2A03,
+    Nmos6502,
+    Wdc65C02,
+}
+
+impl Variant {
+    pub fn opcodes(&self) -> &'static [Opcode; 256] {
+        match self {
+            Variant::Ricoh2A03 | Variant::Nmos6502 => &opcode::OPCODES,
+            Variant::Wdc65C02 => &opcode::OPCODES_65C02,
+        }
+    }
+    fn has_decimal(&self) -> bool {
+        *self != Variant::Ricoh2A03
+    }
+    fn is_cmos(&self) -> bool {
+        *self == Variant::Wdc65C02
+    }
 }
 
 #[derive(PartialEq)]
 pub enum CPUInterrupts {
     INTERRUPTNMI,
-    // INTERRUPTIRQ,
+    INTERRUPTIRQ,
     INTERRUPTNONE,
 }
 
+#[derive(Hash)]
 pub struct Cpu {
+    variant: Variant,
     register: Register,
-    extra_cycle: Cycle,
-    interrupt: CPUInterrupts,
+    cycles: Cycle,
+    need_nmi: bool,
+    prev_need_nmi: bool,
+    run_irq: bool,
+    prev_run_irq: bool,
+    in_nmi: bool,
     page_crossed: bool,
+    jammed: bool,
+    waiting: bool,
+    // Parity of the cycles run since power on, DMA reads happen on even (get) cycles
+    odd_cycle: bool,
+    // Page written to $4014, copied on the next read. The bus can be dropped in between.
+    oam_dma: Option<u8>,
 }
 
 impl Cpu {
     pub fn new() -> Cpu {
+        Cpu::with_variant(Variant::Ricoh2A03)
+    }
+    pub fn with_variant(variant: Variant) -> Cpu {
         let register = Register::new();
         Cpu {
+            variant,
             register: register,
-            extra_cycle: 0,
-            interrupt: CPUInterrupts::INTERRUPTNONE,
+            cycles: 0,
+            need_nmi: false,
+            prev_need_nmi: false,
+            run_irq: false,
+            prev_run_irq: false,
+            in_nmi: false,
             page_crossed: false,
+            jammed: false,
+            waiting: false,
+            odd_cycle: false,
+            oam_dma: None,
         }
     }
-    pub fn trigger_nmi(&mut self) {
-        self.interrupt = CPUInterrupts::INTERRUPTNMI;
+    // Registers are cleared, the reset sequence then brings SP from $00 to $FD
+    pub fn power_on<B: Bus>(&mut self, bus: &mut B) -> Cycle {
+        self.register = Register::new();
+        self.register.set_sp(0x00);
+        self.soft_reset(bus)
     }
-    pub fn reset<B: Bus>(&mut self, bus: &mut B) -> Cycle {
-        let hi = bus.peek(0xFFFC);
-        let low = bus.peek(0xFFFC + 1);
-        self.register.push_stack(hi, bus);
-        self.register.push_stack(low, bus);
-        let pc = ((low as u16) << 8) | hi as u16;
-        self.register.set_pc(pc);
+    // The reset line runs the interrupt sequence with its writes turned into reads:
+    // SP is decremented by 3, I is set, A, X and Y are kept
+    pub fn soft_reset<B: Bus>(&mut self, bus: &mut B) -> Cycle {
+        self.cycles = 0;
+        self.need_nmi = false;
+        self.prev_need_nmi = false;
+        self.run_irq = false;
+        self.prev_run_irq = false;
+        self.in_nmi = false;
+        self.jammed = false;
+        self.waiting = false;
+        self.oam_dma = None;
+        self.dummy_read_pc(bus);
+        self.dummy_read_pc(bus);
+        for _ in 0..3 {
+            self.dummy_read_stack(bus);
+            self.register.decr_sp();
+        }
+        self.register.set_flag(StatusFlags::INTERRUPT, true);
+        if self.variant.is_cmos() {
+            self.register.set_flag(StatusFlags::DECIMAL, false);
+        }
+        let low = self.read(bus, 0xFFFC) as u16;
+        let hi = self.read(bus, 0xFFFD) as u16;
+        self.register.set_pc((hi << 8) | low);
         println!("CPU: first PC : {:x?}", self.register.get_pc());
-        7
+        self.cycles
+    }
+    // True between the NMI sequence and the RTI of its handler
+    pub fn is_in_nmi(&self) -> bool {
+        self.in_nmi
+    }
+    pub fn get_pc(&self) -> u16 {
+        self.register.get_pc()
     }
-    fn write_random_number<B: Bus>(&mut self, bus: &mut B) {
-        let mut rng = rand::thread_rng();
-        let value: u8 = rng.gen_range(0, 255);
-        bus.write(0x00FE, value);
+    pub fn get_variant(&self) -> Variant {
+        self.variant
     }
     pub fn run<B: Bus>(&mut self, bus: &mut B) -> (Cycle, EmulationStatus) {
-        self.page_crossed = false;
-        self.extra_cycle = 0;
-        self.write_random_number(bus);
-        let pc = self.register.get_pc();
-        let value = bus.peek(pc);
-        self.register.incr_pc();
-        let opcode = opcode::OPCODES.get(&value).unwrap();
-        let operand = self.fetch_operand(bus, opcode);
-        let res = self.execute_op(operand, bus, opcode);
-        match self.interrupt {
-            //CPUInterrupts::INTERRUPTIRQ => {}
-            CPUInterrupts::INTERRUPTNMI => {
-                self.register.set_flag(StatusFlags::BREAK, false);
-                self.register.push_stack((self.register.get_pc() >> 8) as u8, bus);
-                self.register.push_stack((self.register.get_pc() & 0xFF) as u8, bus);
-                self.register.push_stack(self.register.get_sr() as u8, bus);
-                self.register.set_flag(StatusFlags::INTERRUPT, true);
-
-                let hi = bus.peek(0xFFFB) as u16;
-                let low = bus.peek(0xFFFA) as u16;
-                self.register.set_pc((hi << 8) | low);
-                self.extra_cycle = 7;
-            }
+        self.cycles = 0;
+        let res = self.step(bus);
+        if res == EmulationStatus::JAMMED {
+            return (self.cycles, res);
+        }
+        match self.pending_interrupt() {
+            CPUInterrupts::INTERRUPTNMI | CPUInterrupts::INTERRUPTIRQ => self.interrupt_sequence(bus),
             CPUInterrupts::INTERRUPTNONE => {}
         }
-        self.interrupt = CPUInterrupts::INTERRUPTNONE;
-        (opcode.cycle as Cycle + self.extra_cycle, res)
+        (self.cycles, res)
     }
     #[allow(dead_code)]
     fn run_instructions<B: Bus>(&mut self, n: usize, bus: &mut B) -> (Cycle, EmulationStatus) {
-        let mut cycle: Cycle = 0;
+        self.cycles = 0;
         for _i in 0..n {
-            self.extra_cycle = 0;
-            self.write_random_number(bus);
-            let value = bus.peek(self.register.get_pc());
-            self.register.incr_pc();
-            let opcode = opcode::OPCODES.get(&value).unwrap();
-            let value = self.fetch_operand(bus, opcode);
-            self.execute_op(value, bus, opcode);
-            cycle += opcode.cycle as Cycle + self.extra_cycle;
+            if self.step(bus) == EmulationStatus::JAMMED {
+                return (self.cycles, EmulationStatus::JAMMED);
+            }
         }
-        (cycle, EmulationStatus::BREAK)
+        (self.cycles, EmulationStatus::BREAK)
     }
-
-    fn fetch_absolute_x<B: Bus>(&mut self, bus: &mut B) -> u16 {
-        let hi = bus.peek(self.register.get_pc()) as u16;
-        self.register.incr_pc();
-        let low = bus.peek(self.register.get_pc()) as u16;
+    fn step<B: Bus>(&mut self, bus: &mut B) -> EmulationStatus {
+        if self.jammed {
+            bus.tick();
+            self.cycles += 1;
+            return EmulationStatus::JAMMED;
+        }
+        // WAI: nothing runs until an interrupt is requested, even a masked IRQ
+        if self.waiting {
+            let pc = self.register.get_pc();
+            self.read(bus, pc);
+            if self.need_nmi || bus.irq_line() {
+                self.waiting = false;
+            }
+            return EmulationStatus::PROCESSING;
+        }
+        self.page_crossed = false;
+        let pc = self.register.get_pc();
+        let value = self.read(bus, pc);
         self.register.incr_pc();
-        let addr = (low << 8) | hi;
-        let res = addr.wrapping_add(self.register.get_x() as u16);
-        self.page_crossed = addr & 0xFF00 != res & 0xFF00;
-        res
+        let opcode = &self.variant.opcodes()[value as usize];
+        self.execute_op(bus, value, opcode)
     }
 
-    fn fetch_absolute_y<B: Bus>(&mut self, bus: &mut B) -> u16 {
-        let hi = bus.peek(self.register.get_pc()) as u16;
-        self.register.incr_pc();
-        let low = bus.peek(self.register.get_pc()) as u16;
-        self.register.incr_pc();
-        let addr = (low << 8) | hi;
-        let res = addr.wrapping_add(self.register.get_y() as u16);
-        self.page_crossed = addr & 0xFF00 != res & 0xFF00;
-        res
+    // Interrupts are polled at the end of every cycle, the decision to service one
+    // is taken on the state polled at the end of the second-to-last cycle of an instruction
+    fn pending_interrupt(&self) -> CPUInterrupts {
+        if self.prev_need_nmi {
+            CPUInterrupts::INTERRUPTNMI
+        } else if self.prev_run_irq {
+            CPUInterrupts::INTERRUPTIRQ
+        } else {
+            CPUInterrupts::INTERRUPTNONE
+        }
+    }
+    fn poll_interrupts<B: Bus>(&mut self, bus: &mut B) {
+        self.prev_need_nmi = self.need_nmi;
+        if bus.poll_nmi() {
+            self.need_nmi = true;
+        }
+        self.prev_run_irq = self.run_irq;
+        self.run_irq = bus.irq_line() && !self.register.get_flag(StatusFlags::INTERRUPT);
     }
 
-    fn fetch_zeropage_x<B: Bus>(&mut self, bus: &mut B) -> u16 {
-        let value = bus.peek(self.register.get_pc());
-        self.register.incr_pc();
-        value.wrapping_add(self.register.get_x()) as u16
+    // Every bus access takes exactly one CPU cycle
+    fn cycle<B: Bus>(&mut self, bus: &mut B) {
+        bus.tick();
+        self.cycles += 1;
+        self.odd_cycle = !self.odd_cycle;
     }
-    fn fetch_zeropage_y<B: Bus>(&mut self, bus: &mut B) -> u16 {
-        let value = bus.peek(self.register.get_pc());
-        self.register.incr_pc();
-        value.wrapping_add(self.register.get_y()) as u16
+    fn read<B: Bus>(&mut self, bus: &mut B, addr: u16) -> u8 {
+        self.run_dma(bus, addr);
+        self.bus_read(bus, addr)
+    }
+    fn bus_read<B: Bus>(&mut self, bus: &mut B, addr: u16) -> u8 {
+        self.cycle(bus);
+        let v = bus.peek(addr);
+        self.poll_interrupts(bus);
+        v
+    }
+    fn write<B: Bus>(&mut self, bus: &mut B, addr: u16, v: u8) {
+        self.cycle(bus);
+        bus.write(addr, v);
+        if let Some(page) = bus.poll_oam_dma() {
+            self.oam_dma = Some(page);
+        }
+        self.poll_interrupts(bus);
     }
 
-    fn fetch_relative_address<B: Bus>(&mut self, bus: &mut B) -> u16 {
-        let value = bus.peek(self.register.get_pc()) as u16;
-        self.register.incr_pc();
-        if value < 0x80 {
-            return value + self.register.get_pc();
+    // DMA halts the CPU on a read cycle, the halt and alignment cycles repeat that read.
+    // OAM DMA alternates reads on get cycles and writes to $2004 on put cycles: 513 or 514
+    // cycles depending on the parity of the halt. A DMC fetch needs a halt and a dummy cycle
+    // before its get cycle, the cycles of a running OAM DMA count as such.
+    fn run_dma<B: Bus>(&mut self, bus: &mut B, addr: u16) {
+        let mut oam = self.oam_dma.take().map(|page| (page as u16) << 8);
+        let mut dmc = bus.poll_dmc_dma();
+        if oam.is_none() && dmc.is_none() {
+            return;
+        }
+        // Cycles left before the DMC fetch can happen, the halt is the first one
+        let mut dmc_wait: u8 = 1;
+        self.bus_read(bus, addr);
+        let mut count: u16 = 0;
+        let mut value = 0;
+        while oam.is_some() || dmc.is_some() {
+            if dmc.is_none() {
+                dmc = bus.poll_dmc_dma();
+                if dmc.is_some() {
+                    dmc_wait = 2;
+                }
+            }
+            let dmc_ready = dmc_wait == 0;
+            dmc_wait = dmc_wait.saturating_sub(1);
+            match (!self.odd_cycle, dmc, oam) {
+                (true, Some(sample), _) if dmc_ready => {
+                    let v = self.bus_read(bus, sample);
+                    bus.dmc_dma_done(v);
+                    dmc = None;
+                }
+                (true, _, Some(page)) => {
+                    value = self.bus_read(bus, page + count / 2);
+                    count += 1;
+                }
+                (false, _, Some(_)) if count % 2 == 1 => {
+                    self.write(bus, 0x2004, value);
+                    count += 1;
+                    if count == 0x200 {
+                        oam = None;
+                    }
+                }
+                _ => self.dma_dummy_read(bus, addr),
+            }
         }
-        value + self.register.get_pc() - 0x100
     }
-    fn fetch_indirect_absolute<B: Bus>(&mut self, bus: &mut B) -> u16 {
-        let hi = bus.peek(self.register.get_pc()) as u16;
-        self.register.incr_pc();
-        let low = bus.peek(self.register.get_pc()) as u16;
-        self.register.incr_pc();
-        let addr = (low << 8) | hi;
-        let upper = bus.peek((addr & 0xFF00) | ((((addr & 0xFF) + 1) & 0xFF))) as u16;
-        (bus.peek(addr) as u16) + (upper << 8) as u16
+    // Consecutive reads of the controller ports only clock them once
+    fn dma_dummy_read<B: Bus>(&mut self, bus: &mut B, addr: u16) {
+        if addr == 0x4016 || addr == 0x4017 {
+            self.cycle(bus);
+            self.poll_interrupts(bus);
+        } else {
+            self.bus_read(bus, addr);
+        }
     }
-    fn fetch_indexed_indirect<B: Bus>(&mut self, bus: &mut B) -> u16 {
-        let value = bus.peek(self.register.get_pc());
-        self.register.incr_pc();
-        let addr = value.wrapping_add(self.register.get_x()) as u16;
-        bus.peek(addr) as u16 + ((bus.peek((addr.wrapping_add(1)) & 0xFF) as u16) << 8) & 0xFFFF
+    fn push<B: Bus>(&mut self, bus: &mut B, v: u8) {
+        let sp = self.register.get_sp();
+        self.write(bus, 0x0100 | sp as u16, v);
+        self.register.decr_sp();
     }
-    fn fetch_indirect_indexed<B: Bus>(&mut self, bus: &mut B) -> u16 {
-        let addr = bus.peek(self.register.get_pc()) as u16;
-        self.register.incr_pc();
-        let base_addr = bus.peek(addr) as u16 + ((bus.peek((addr + 1) & 0x00FF) as u16) * 0x100);
-        let result = (base_addr.wrapping_add(self.register.get_y() as u16)) & 0xFFFF;
-        self.page_crossed = base_addr & 0xFF00 != result & 0xFF00;
-        result
+    fn pop<B: Bus>(&mut self, bus: &mut B) -> u8 {
+        self.register.incr_sp();
+        let sp = self.register.get_sp();
+        self.read(bus, 0x0100 | sp as u16)
     }
-    fn fetch_word<B: Bus>(&mut self, bus: &mut B) -> u16 {
-        let hi = bus.peek(self.register.get_pc()) as u16;
-        self.register.incr_pc();
-        let low = bus.peek(self.register.get_pc()) as u16;
-        self.register.incr_pc();
-        (low << 8) | hi
+    fn dummy_read_pc<B: Bus>(&mut self, bus: &mut B) {
+        let pc = self.register.get_pc();
+        self.read(bus, pc);
+    }
+    fn dummy_read_stack<B: Bus>(&mut self, bus: &mut B) {
+        let sp = self.register.get_sp();
+        self.read(bus, 0x0100 | sp as u16);
+    }
+    fn interrupt_sequence<B: Bus>(&mut self, bus: &mut B) {
+        self.dummy_read_pc(bus);
+        self.dummy_read_pc(bus);
+        let sr = self.register.get_sr() & !0x10 | 0x20;
+        self.push_interrupt_frame(bus, sr);
+    }
+    // Shared by BRK, IRQ and NMI: an NMI detected while the return address is pushed
+    // hijacks the sequence and the NMI vector is used instead of the IRQ/BRK one
+    fn push_interrupt_frame<B: Bus>(&mut self, bus: &mut B, sr: u8) {
+        let pc = self.register.get_pc();
+        self.push(bus, (pc >> 8) as u8);
+        self.push(bus, (pc & 0xFF) as u8);
+        let vector = if self.need_nmi {
+            self.need_nmi = false;
+            self.in_nmi = true;
+            0xFFFA
+        } else {
+            0xFFFE
+        };
+        self.push(bus, sr);
+        self.register.set_flag(StatusFlags::INTERRUPT, true);
+        if self.variant.is_cmos() {
+            self.register.set_flag(StatusFlags::DECIMAL, false);
+        }
+        let low = self.read(bus, vector) as u16;
+        let hi = self.read(bus, vector + 1) as u16;
+        self.register.set_pc((hi << 8) | low);
+        // The first instruction of the handler always runs before another interrupt
+        self.prev_need_nmi = false;
     }
-    fn fetch<B: Bus>(&mut self, bus: &mut B) -> u16 {
-        let value = bus.peek(self.register.get_pc()) as u16;
+
+    fn fetch<B: Bus>(&mut self, bus: &mut B) -> u8 {
+        let pc = self.register.get_pc();
+        let value = self.read(bus, pc);
         self.register.incr_pc();
         value
     }
-    fn fetch_operand<B: Bus>(&mut self, bus: &mut B, opcode: &Opcode) -> u16 {
-        match opcode.mode {
-            Addressing::Accumulator => return 0x0000,
-            Addressing::Implied => return 0x0000,
-            Addressing::Immediate => self.fetch(bus),
-            Addressing::ZeroPage => self.fetch(bus),
-            Addressing::ZeroPageX => self.fetch_zeropage_x(bus),
-            Addressing::ZeroPageY => self.fetch_zeropage_y(bus),
+    fn fetch_word<B: Bus>(&mut self, bus: &mut B) -> u16 {
+        let low = self.fetch(bus) as u16;
+        let hi = self.fetch(bus) as u16;
+        (hi << 8) | low
+    }
+    fn fetch_indexed<B: Bus>(&mut self, bus: &mut B, base: u16, index: u8, always_dummy: bool) -> u16 {
+        let addr = base.wrapping_add(index as u16);
+        self.page_crossed = base & 0xFF00 != addr & 0xFF00;
+        // The high byte is fixed up one cycle late, the CPU first reads from the wrong page
+        if self.page_crossed || always_dummy {
+            self.read(bus, (base & 0xFF00) | (addr & 0x00FF));
+        }
+        addr
+    }
+    // Resolves the effective address of the operand, performing every bus access of the addressing mode.
+    // Write and read-modify-write instructions always take the extra indexing cycle.
+    fn fetch_address<B: Bus>(&mut self, bus: &mut B, mode: &Addressing, always_dummy: bool) -> u16 {
+        match mode {
+            Addressing::Immediate => {
+                let pc = self.register.get_pc();
+                self.register.incr_pc();
+                pc
+            }
+            Addressing::ZeroPage => self.fetch(bus) as u16,
+            Addressing::ZeroPageX | Addressing::ZeroPageY => {
+                let base = self.fetch(bus);
+                self.read(bus, base as u16);
+                let index = if *mode == Addressing::ZeroPageX { self.register.get_x() } else { self.register.get_y() };
+                base.wrapping_add(index) as u16
+            }
             Addressing::Absolute => self.fetch_word(bus),
-            Addressing::AbsoluteX => self.fetch_absolute_x(bus),
-            Addressing::AbsoluteY => self.fetch_absolute_y(bus),
-            Addressing::Relative => self.fetch_relative_address(bus),
-            Addressing::IndirectAbsolute => self.fetch_indirect_absolute(bus),
-            Addressing::IndexedIndirect => self.fetch_indexed_indirect(bus),
-            Addressing::IndirectIndexed => self.fetch_indirect_indexed(bus),
+            Addressing::AbsoluteX => {
+                let base = self.fetch_word(bus);
+                let x = self.register.get_x();
+                self.fetch_indexed(bus, base, x, always_dummy)
+            }
+            Addressing::AbsoluteY => {
+                let base = self.fetch_word(bus);
+                let y = self.register.get_y();
+                self.fetch_indexed(bus, base, y, always_dummy)
+            }
+            Addressing::IndexedIndirect => {
+                let ptr = self.fetch(bus);
+                self.read(bus, ptr as u16);
+                let ptr = ptr.wrapping_add(self.register.get_x());
+                let low = self.read(bus, ptr as u16) as u16;
+                let hi = self.read(bus, ptr.wrapping_add(1) as u16) as u16;
+                (hi << 8) | low
+            }
+            Addressing::IndirectIndexed => {
+                let ptr = self.fetch(bus);
+                let low = self.read(bus, ptr as u16) as u16;
+                let hi = self.read(bus, ptr.wrapping_add(1) as u16) as u16;
+                let y = self.register.get_y();
+                self.fetch_indexed(bus, (hi << 8) | low, y, always_dummy)
+            }
+            Addressing::IndirectAbsolute if self.variant.is_cmos() => {
+                let ptr = self.fetch_word(bus);
+                self.dummy_read_pc(bus);
+                let low = self.read(bus, ptr) as u16;
+                let hi = self.read(bus, ptr.wrapping_add(1)) as u16;
+                (hi << 8) | low
+            }
+            Addressing::IndirectAbsolute => {
+                let ptr = self.fetch_word(bus);
+                let low = self.read(bus, ptr) as u16;
+                let hi = self.read(bus, (ptr & 0xFF00) | (ptr.wrapping_add(1) & 0x00FF)) as u16;
+                (hi << 8) | low
+            }
+            Addressing::ZeroPageIndirect => {
+                let ptr = self.fetch(bus);
+                let low = self.read(bus, ptr as u16) as u16;
+                let hi = self.read(bus, ptr.wrapping_add(1) as u16) as u16;
+                (hi << 8) | low
+            }
+            Addressing::IndexedAbsoluteIndirect => {
+                let ptr = self.fetch_word(bus).wrapping_add(self.register.get_x() as u16);
+                self.dummy_read_pc(bus);
+                let low = self.read(bus, ptr) as u16;
+                let hi = self.read(bus, ptr.wrapping_add(1)) as u16;
+                (hi << 8) | low
+            }
+            Addressing::Implied | Addressing::Accumulator | Addressing::Relative | Addressing::ZeroPageRelative => {
+                unreachable!("{:?} has no effective address", mode)
+            }
+        }
+    }
+    fn fetch_operand<B: Bus>(&mut self, bus: &mut B, mode: &Addressing) -> u8 {
+        let addr = self.fetch_address(bus, mode, false);
+        self.read(bus, addr)
+    }
+    fn store<B: Bus>(&mut self, bus: &mut B, mode: &Addressing, v: u8) {
+        let addr = self.fetch_address(bus, mode, true);
+        self.write(bus, addr, v);
+    }
+    fn modify<B: Bus, F: FnOnce(&mut Self, u8) -> u8>(&mut self, bus: &mut B, mode: &Addressing, f: F) -> u8 {
+        self.modify_at(bus, mode, true, f)
+    }
+    // Read-modify-write instructions write the unmodified value back before the result,
+    // the 65C02 reads it a second time instead
+    fn modify_at<B: Bus, F: FnOnce(&mut Self, u8) -> u8>(&mut self, bus: &mut B, mode: &Addressing, always_dummy: bool, f: F) -> u8 {
+        let addr = self.fetch_address(bus, mode, always_dummy);
+        let old = self.read(bus, addr);
+        if self.variant.is_cmos() {
+            self.read(bus, addr);
+        } else {
+            self.write(bus, addr, old);
+        }
+        let res = f(self, old);
+        self.write(bus, addr, res);
+        res
+    }
+    // The 65C02 only takes the extra indexing cycle of shifts and rotates on page crossing
+    fn shift<B: Bus, F: FnOnce(&mut Self, u8) -> u8>(&mut self, bus: &mut B, mode: &Addressing, f: F) {
+        if *mode == Addressing::Accumulator {
+            self.accumulator(bus, f);
+        } else {
+            let always_dummy = !self.variant.is_cmos();
+            self.modify_at(bus, mode, always_dummy, f);
+        }
+    }
+    fn store_high_and<B: Bus>(&mut self, bus: &mut B, mode: &Addressing, index: u8, v: u8) {
+        let addr = self.fetch_address(bus, mode, true);
+        let base = addr.wrapping_sub(index as u16);
+        let res = v & ((base >> 8) as u8).wrapping_add(1);
+        let addr = if self.page_crossed { ((res as u16) << 8) | (addr & 0xFF) } else { addr };
+        self.write(bus, addr, res);
+    }
+    fn branch<B: Bus>(&mut self, bus: &mut B, condition: bool) {
+        let offset = self.fetch(bus);
+        if condition {
+            // A taken branch without page crossing does not poll interrupts on its last cycle
+            if self.run_irq && !self.prev_run_irq {
+                self.run_irq = false;
+            }
+            let pc = self.register.get_pc();
+            let target = pc.wrapping_add(offset as i8 as u16);
+            self.read(bus, pc);
+            if pc & 0xFF00 != target & 0xFF00 {
+                self.read(bus, (pc & 0xFF00) | (target & 0x00FF));
+            }
+            self.register.set_pc(target);
+        }
+    }
+
+    fn set_zn(&mut self, v: u8) {
+        self.register
+            .set_flag(StatusFlags::ZERO, v == 0)
+            .set_flag(StatusFlags::NEGATIVE, v & (1 << 7) != 0);
+    }
+    fn is_decimal(&self) -> bool {
+        self.variant.has_decimal() && self.register.get_flag(StatusFlags::DECIMAL)
+    }
+    // The 65C02 takes one more cycle to fix up the flags of a decimal ADC/SBC
+    fn decimal_cycle<B: Bus>(&mut self, bus: &mut B) {
+        if self.variant.is_cmos() && self.is_decimal() {
+            self.dummy_read_pc(bus);
+        }
+    }
+    fn adc_binary(&mut self, m: u8) {
+        let a = self.register.get_a() as u16;
+        let c: u16 = match self.register.get_flag(StatusFlags::CARRY) {
+            true => 1,
+            false => 0,
+        };
+        let res = a + m as u16 + c;
+        self.register
+            .set_flag(StatusFlags::CARRY, res > 0xff)
+            .set_flag(StatusFlags::OVERFLOW, (res ^ a) & (res ^ m as u16) & 0x80 != 0)
+            .set_a(res as u8);
+        self.set_zn(res as u8);
+    }
+    // Decimal mode sequences from Bruce Clark's "Decimal Mode" tutorial (6502.org).
+    // On NMOS, Z is the one of the binary addition and N/V come from the intermediate result.
+    fn adc(&mut self, m: u8) {
+        if !self.is_decimal() {
+            return self.adc_binary(m);
+        }
+        let a = self.register.get_a();
+        let c = self.register.get_flag(StatusFlags::CARRY) as i16;
+        let mut low = (a & 0x0F) as i16 + (m & 0x0F) as i16 + c;
+        if low >= 0x0A {
+            low = ((low + 0x06) & 0x0F) + 0x10;
+        }
+        let signed = (a & 0xF0) as i8 as i16 + (m & 0xF0) as i8 as i16 + low;
+        let mut res = (a & 0xF0) as i16 + (m & 0xF0) as i16 + low;
+        if res >= 0xA0 {
+            res += 0x60;
+        }
+        self.register
+            .set_flag(StatusFlags::CARRY, res >= 0x100)
+            .set_flag(StatusFlags::OVERFLOW, !(-128..=127).contains(&signed))
+            .set_a(res as u8);
+        if self.variant.is_cmos() {
+            self.set_zn(res as u8);
+        } else {
+            self.register
+                .set_flag(StatusFlags::ZERO, a.wrapping_add(m).wrapping_add(c as u8) == 0)
+                .set_flag(StatusFlags::NEGATIVE, signed & 0x80 != 0);
+        }
+    }
+    // C and V are always the ones of the binary subtraction, so is every flag on NMOS
+    fn sbc(&mut self, m: u8) {
+        let a = self.register.get_a();
+        let c = self.register.get_flag(StatusFlags::CARRY) as i16;
+        self.adc_binary(!m);
+        if !self.is_decimal() {
+            return;
         }
+        let low = (a & 0x0F) as i16 - (m & 0x0F) as i16 + c - 1;
+        let res = if self.variant.is_cmos() {
+            let mut res = a as i16 - m as i16 + c - 1;
+            if res < 0 {
+                res -= 0x60;
+            }
+            if low < 0 {
+                res -= 0x06;
+            }
+            self.set_zn(res as u8);
+            res
+        } else {
+            let low = if low < 0 { ((low - 0x06) & 0x0F) - 0x10 } else { low };
+            let mut res = (a & 0xF0) as i16 - (m & 0xF0) as i16 + low;
+            if res < 0 {
+                res -= 0x60;
+            }
+            res
+        };
+        self.register.set_a(res as u8);
+    }
+    fn compare(&mut self, r: u8, m: u8) {
+        self.register.set_flag(StatusFlags::CARRY, r >= m);
+        self.set_zn(r.wrapping_sub(m));
+    }
+    fn asl(&mut self, v: u8) -> u8 {
+        let res = v << 1;
+        self.register.set_flag(StatusFlags::CARRY, v & (1 << 7) != 0);
+        self.set_zn(res);
+        res
+    }
+    fn lsr(&mut self, v: u8) -> u8 {
+        let res = v >> 1;
+        self.register.set_flag(StatusFlags::CARRY, v & 0x1 != 0);
+        self.set_zn(res);
+        res
+    }
+    fn rol(&mut self, v: u8) -> u8 {
+        let mut res = v << 1;
+        if self.register.get_flag(StatusFlags::CARRY) {
+            res |= 0x1;
+        }
+        self.register.set_flag(StatusFlags::CARRY, v & (1 << 7) != 0);
+        self.set_zn(res);
+        res
+    }
+    fn ror(&mut self, v: u8) -> u8 {
+        let mut res = v >> 1;
+        if self.register.get_flag(StatusFlags::CARRY) {
+            res |= 0x80;
+        }
+        self.register.set_flag(StatusFlags::CARRY, v & 0x1 != 0);
+        self.set_zn(res);
+        res
+    }
+    fn load_a(&mut self, v: u8) {
+        self.register.set_a(v);
+        self.set_zn(v);
     }
-    fn execute_op<B: Bus>(&mut self, value: u16, bus: &mut B, opcode: &Opcode) -> EmulationStatus {
+    fn load_x(&mut self, v: u8) {
+        self.register.set_x(v);
+        self.set_zn(v);
+    }
+    fn load_y(&mut self, v: u8) {
+        self.register.set_y(v);
+        self.set_zn(v);
+    }
+    fn accumulator<B: Bus, F: FnOnce(&mut Self, u8) -> u8>(&mut self, bus: &mut B, f: F) {
+        self.dummy_read_pc(bus);
+        let a = self.register.get_a();
+        let res = f(self, a);
+        self.register.set_a(res);
+    }
+
+    fn execute_op<B: Bus>(&mut self, bus: &mut B, code: u8, opcode: &Opcode) -> EmulationStatus {
+        let mode = &opcode.mode;
         match opcode.name {
             Instruction::ADC => {
-                let a = self.register.get_a() as u16;
-                let mut m = value;
-                if opcode.mode != Addressing::Immediate {
-                    m = bus.peek(value) as u16;
-                }
-                let c: u16 = match self.register.get_flag(StatusFlags::CARRY) {
-                    true => 1,
-                    false => 0,
-                };
-                let res = a + m + c;
-                self.register
-                    .set_flag(StatusFlags::CARRY, res > 0xff)
-                    .set_flag(StatusFlags::ZERO, res as u8 == 0)
-                    .set_flag(StatusFlags::NEGATIVE, res & (1 << 7) != 0)
-                    .set_flag(StatusFlags::OVERFLOW, (res ^ m) & ((res ^ a)) & 0x80 != 0)
-                    .set_a(res as u8);
+                let m = self.fetch_operand(bus, mode);
+                self.adc(m);
+                self.decimal_cycle(bus);
+            }
+            Instruction::ALR => {
+                let m = self.fetch_operand(bus, mode);
+                let a = self.register.get_a() & m;
+                let res = self.lsr(a);
+                self.register.set_a(res);
+            }
+            Instruction::ANC => {
+                let m = self.fetch_operand(bus, mode);
+                let res = self.register.get_a() & m;
+                self.load_a(res);
+                self.register.set_flag(StatusFlags::CARRY, res & (1 << 7) != 0);
             }
             Instruction::AND => {
-                let a = self.register.get_a();
-                let mut m = value as u8;
-                if opcode.mode != Addressing::Immediate {
-                    m = bus.peek(value) as u8;
+                let m = self.fetch_operand(bus, mode);
+                let res = self.register.get_a() & m;
+                self.load_a(res);
+            }
+            Instruction::ARR => {
+                let m = self.fetch_operand(bus, mode);
+                let a = self.register.get_a() & m;
+                let mut res = a >> 1;
+                if self.register.get_flag(StatusFlags::CARRY) {
+                    res |= 0x80;
                 }
-                let res = a & m;
+                self.load_a(res);
                 self.register
-                    .set_flag(StatusFlags::NEGATIVE, res & (1 << 7) != 0)
-                    .set_flag(StatusFlags::ZERO, res == 0)
-                    .set_a(res);
-            }
-            Instruction::ASL => {
-                let old: u8;
-                if opcode.mode == Addressing::Accumulator {
-                    old = self.register.get_a();
-                    let a = self.register.set_a(old << 1).get_a();
-                    self.register
-                        .set_flag(StatusFlags::ZERO, a == 0)
-                        .set_flag(StatusFlags::NEGATIVE, a & (1 << 7) != 0);
-                } else {
-                    old = bus.peek(value);
-                    let m = bus.write(value, old << 1);
-                    self.register
-                        .set_flag(StatusFlags::ZERO, m == 0)
-                        .set_flag(StatusFlags::NEGATIVE, m & (1 << 7) != 0);
-                }
-                self.register.set_flag(StatusFlags::CARRY, old & (1 << 7) != 0);
+                    .set_flag(StatusFlags::CARRY, res & (1 << 6) != 0)
+                    .set_flag(StatusFlags::OVERFLOW, ((res >> 6) ^ (res >> 5)) & 0x1 != 0);
+            }
+            Instruction::ASL => self.shift(bus, mode, Self::asl),
+            Instruction::AXS => {
+                let m = self.fetch_operand(bus, mode);
+                let ax = self.register.get_a() & self.register.get_x();
+                let res = ax.wrapping_sub(m);
+                self.load_x(res);
+                self.register.set_flag(StatusFlags::CARRY, ax >= m);
             }
             Instruction::BCC => {
-                if !self.register.get_flag(StatusFlags::CARRY) {
-                    self.extra_cycle = if self.register.get_pc() & 0xFF00 != value & 0xFF00 { 2 } else { 1 };
-                    self.register.set_pc(value);
-                }
+                let condition = !self.register.get_flag(StatusFlags::CARRY);
+                self.branch(bus, condition);
             }
             Instruction::BCS => {
-                if self.register.get_flag(StatusFlags::CARRY) {
-                    self.extra_cycle = if self.register.get_pc() & 0xFF00 != value & 0xFF00 { 2 } else { 1 };
-                    self.register.set_pc(value);
-                }
+                let condition = self.register.get_flag(StatusFlags::CARRY);
+                self.branch(bus, condition);
             }
             Instruction::BEQ => {
-                if self.register.get_flag(StatusFlags::ZERO) {
-                    self.extra_cycle = if self.register.get_pc() & 0xFF00 != value & 0xFF00 { 2 } else { 1 };
-                    self.register.set_pc(value);
-                }
+                let condition = self.register.get_flag(StatusFlags::ZERO);
+                self.branch(bus, condition);
             }
             Instruction::BIT => {
-                let a = self.register.get_a();
-                let m = bus.peek(value);
-                let res =  a & m;
-                self.register
-                    .set_flag(StatusFlags::ZERO, res == 0)
-                    .set_flag(StatusFlags::OVERFLOW, m & (1 << 6) != 0)
-                    .set_flag(StatusFlags::NEGATIVE, m & (1 << 7) != 0);
+                let m = self.fetch_operand(bus, mode);
+                let res = self.register.get_a() & m;
+                self.register.set_flag(StatusFlags::ZERO, res == 0);
+                // BIT #imm (65C02) only sets Z
+                if *mode != Addressing::Immediate {
+                    self.register
+                        .set_flag(StatusFlags::OVERFLOW, m & (1 << 6) != 0)
+                        .set_flag(StatusFlags::NEGATIVE, m & (1 << 7) != 0);
+                }
+            }
+            Instruction::BBR | Instruction::BBS => {
+                let zp = self.fetch(bus) as u16;
+                let m = self.read(bus, zp);
+                self.read(bus, zp);
+                let set = m & (1 << ((code >> 4) & 0x07)) != 0;
+                self.branch(bus, set == (opcode.name == Instruction::BBS));
             }
             Instruction::BMI => {
-                if self.register.get_flag(StatusFlags::NEGATIVE) {
-                    self.extra_cycle = if self.register.get_pc() & 0xFF00 != value & 0xFF00 { 2 } else { 1 };
-                    self.register.set_pc(value);
-                }
+                let condition = self.register.get_flag(StatusFlags::NEGATIVE);
+                self.branch(bus, condition);
             }
             Instruction::BNE => {
-                if !self.register.get_flag(StatusFlags::ZERO) {
-                    self.extra_cycle = if self.register.get_pc() & 0xFF00 != value & 0xFF00 { 2 } else { 1 };
-                    self.register.set_pc(value);
-                }
+                let condition = !self.register.get_flag(StatusFlags::ZERO);
+                self.branch(bus, condition);
             }
             Instruction::BPL => {
-                if !self.register.get_flag(StatusFlags::NEGATIVE) {
-                    self.extra_cycle = if self.register.get_pc() & 0xFF00 != value & 0xFF00 { 2 } else { 1 };
-                    self.register.set_pc(value);
-                }
+                let condition = !self.register.get_flag(StatusFlags::NEGATIVE);
+                self.branch(bus, condition);
             }
+            Instruction::BRA => self.branch(bus, true),
             Instruction::BRK => {
-                self.register
-                    .set_flag(StatusFlags::BREAK, true)
-                    .set_flag(StatusFlags::INTERRUPT, true);
-                return EmulationStatus::BREAK;
+                self.fetch(bus);
+                let sr = self.register.get_sr() | 0x30;
+                self.push_interrupt_frame(bus, sr);
             }
             Instruction::BVC => {
-                if !self.register.get_flag(StatusFlags::OVERFLOW) {
-                    self.extra_cycle = if self.register.get_pc() & 0xFF00 != value & 0xFF00 { 2 } else { 1 };
-                    self.register.set_pc(value);
-                }
+                let condition = !self.register.get_flag(StatusFlags::OVERFLOW);
+                self.branch(bus, condition);
             }
             Instruction::BVS => {
-                if self.register.get_flag(StatusFlags::OVERFLOW) {
-                    self.extra_cycle = if self.register.get_pc() & 0xFF00 != value & 0xFF00 { 2 } else { 1 };
-                    self.register.set_pc(value);
-                }
+                let condition = self.register.get_flag(StatusFlags::OVERFLOW);
+                self.branch(bus, condition);
             }
             Instruction::CLC => {
+                self.dummy_read_pc(bus);
                 self.register.set_flag(StatusFlags::CARRY, false);
             }
             Instruction::CLD => {
+                self.dummy_read_pc(bus);
                 self.register.set_flag(StatusFlags::DECIMAL, false);
             }
             Instruction::CLI => {
+                self.dummy_read_pc(bus);
                 self.register.set_flag(StatusFlags::INTERRUPT, false);
             }
             Instruction::CLV => {
+                self.dummy_read_pc(bus);
                 self.register.set_flag(StatusFlags::OVERFLOW, false);
             }
             Instruction::CMP => {
+                let m = self.fetch_operand(bus, mode);
                 let a = self.register.get_a();
-                let mut m = value as u8;
-                if opcode.mode != Addressing::Immediate {
-                    m = bus.peek(value);
-                }
-                let res = a.wrapping_sub(m);
-                if opcode.mode == Addressing::AbsoluteX || opcode.mode == Addressing::AbsoluteY || opcode.mode == Addressing::IndirectIndexed {
-                    self.extra_cycle = if self.page_crossed { 1 } else { 0 };
-                }
-                self.register
-                    .set_flag(StatusFlags::NEGATIVE, res & (1 << 7) != 0)
-                    .set_flag(StatusFlags::CARRY, a >= m)
-                    .set_flag(StatusFlags::ZERO, a == m);
+                self.compare(a, m);
             }
             Instruction::CPX => {
+                let m = self.fetch_operand(bus, mode);
                 let x = self.register.get_x();
-                let mut m = value as u8;
-                if opcode.mode != Addressing::Immediate {
-                    m = bus.peek(value);
-                }
-                let res = x.wrapping_sub(m);
-                self.register
-                    .set_flag(StatusFlags::NEGATIVE, res & (1 << 7) != 0)
-                    .set_flag(StatusFlags::CARRY, x >= m)
-                    .set_flag(StatusFlags::ZERO, x == m);
+                self.compare(x, m);
             }
             Instruction::CPY => {
+                let m = self.fetch_operand(bus, mode);
                 let y = self.register.get_y();
-                let mut m = value as u8;
-                if opcode.mode != Addressing::Immediate {
-                    m = bus.peek(value);
-                }
-                let res = y.wrapping_sub(m);
-                self.register
-                    .set_flag(StatusFlags::NEGATIVE, res & (1 << 7) != 0)
-                    .set_flag(StatusFlags::CARRY, y >= m)
-                    .set_flag(StatusFlags::ZERO, y == m);
+                self.compare(y, m);
+            }
+            Instruction::DEC if *mode == Addressing::Accumulator => {
+                self.accumulator(bus, |cpu, v| {
+                    let res = v.wrapping_sub(1);
+                    cpu.set_zn(res);
+                    res
+                });
             }
             Instruction::DEC => {
-                let old_value = bus.peek(value);
-                let res = old_value.wrapping_sub(1);
-                bus.write(value, res);
-                self.register
-                    .set_flag(StatusFlags::ZERO, res == 0)
-                    .set_flag(StatusFlags::NEGATIVE, res & (1 << 7) != 0);
+                self.modify(bus, mode, |cpu, v| {
+                    let res = v.wrapping_sub(1);
+                    cpu.set_zn(res);
+                    res
+                });
             }
             Instruction::DEX => {
+                self.dummy_read_pc(bus);
                 let res = self.register.get_x().wrapping_sub(1);
-                self.register
-                    .set_flag(StatusFlags::ZERO, res == 0)
-                    .set_flag(StatusFlags::NEGATIVE, res & (1 << 7) != 0)
-                    .set_x(res);
+                self.load_x(res);
             }
             Instruction::DEY => {
+                self.dummy_read_pc(bus);
                 let res = self.register.get_y().wrapping_sub(1);
-                self.register
-                    .set_flag(StatusFlags::ZERO, res == 0)
-                    .set_flag(StatusFlags::NEGATIVE, res & (1 << 7) != 0)
-                    .set_y(res);
+                self.load_y(res);
             }
             Instruction::DCP => {
-                let old_value = bus.peek(value);
-                let res = old_value.wrapping_sub(1);
-                bus.write(value, res);
+                let res = self.modify(bus, mode, |_, v| v.wrapping_sub(1));
                 let a = self.register.get_a();
-                if opcode.mode == Addressing::AbsoluteX || opcode.mode == Addressing::AbsoluteY || opcode.mode == Addressing::IndirectIndexed {
-                    self.extra_cycle = if self.page_crossed { 1 } else { 0 };
-                }
-                self.register
-                    .set_flag(StatusFlags::ZERO, a.wrapping_sub(res) == 0)
-                    .set_flag(StatusFlags::NEGATIVE, a.wrapping_sub(res) & (1 << 7) != 0)
-                    .set_flag(StatusFlags::CARRY, a >= res);
+                self.compare(a, res);
             }
             Instruction::EOR => {
-                let a = self.register.get_a();
-                let mut m = value;
-                if opcode.mode != Addressing::Immediate {
-                    m = bus.peek(value) as u16;
-                }
-                let res = a ^ m as u8;
-                self.register
-                    .set_a(res)
-                    .set_flag(StatusFlags::ZERO, res == 0)
-                    .set_flag(StatusFlags::NEGATIVE, res & (1 << 7) != 0);
+                let m = self.fetch_operand(bus, mode);
+                let res = self.register.get_a() ^ m;
+                self.load_a(res);
+            }
+            Instruction::INC if *mode == Addressing::Accumulator => {
+                self.accumulator(bus, |cpu, v| {
+                    let res = v.wrapping_add(1);
+                    cpu.set_zn(res);
+                    res
+                });
             }
             Instruction::INC => {
-                let res = bus.peek(value).wrapping_add(1);
-                bus.write(value, res);
-                self.register
-                    .set_flag(StatusFlags::ZERO, res == 0)
-                    .set_flag(StatusFlags::NEGATIVE, res & (1 << 7) != 0);
+                self.modify(bus, mode, |cpu, v| {
+                    let res = v.wrapping_add(1);
+                    cpu.set_zn(res);
+                    res
+                });
             }
             Instruction::INX => {
+                self.dummy_read_pc(bus);
                 let res = self.register.get_x().wrapping_add(1);
-                self.register
-                    .set_flag(StatusFlags::ZERO, res == 0)
-                    .set_flag(StatusFlags::NEGATIVE, res & (1 << 7) != 0)
-                    .set_x(res);
+                self.load_x(res);
             }
             Instruction::INY => {
+                self.dummy_read_pc(bus);
                 let res = self.register.get_y().wrapping_add(1);
-                self.register
-                    .set_flag(StatusFlags::ZERO, res == 0)
-                    .set_flag(StatusFlags::NEGATIVE, res & (1 << 7) != 0)
-                    .set_y(res);
+                self.load_y(res);
             }
             Instruction::ISB => {
-                let res = bus.peek(value).wrapping_add(1);
-                bus.write(value, res);
-                let a = self.register.get_a();
-                let c = match self.register.get_flag(StatusFlags::CARRY) {
-                    true => 0,
-                    false => 1,
-                };
-                let res: i16 = a as i16 - res as i16 - c as i16;
-                self.register
-                    .set_flag(StatusFlags::CARRY, res >= 0)
-                    .set_flag(StatusFlags::ZERO, res as u8 == 0)
-                    .set_flag(StatusFlags::NEGATIVE, res & (1 << 7) != 0)
-                    .set_flag(StatusFlags::OVERFLOW, (a as u8 ^ value as u8) & ((res as u8 ^ a)) & 0x80 != 0)
-                    .set_a(res as u8);
+                let res = self.modify(bus, mode, |_, v| v.wrapping_add(1));
+                self.sbc(res);
+            }
+            Instruction::JAM => {
+                self.jammed = true;
+                self.register.decr_pc();
+                return EmulationStatus::JAMMED;
             }
             Instruction::JMP => {
-                self.register.set_pc(value);
+                let addr = self.fetch_address(bus, mode, false);
+                self.register.set_pc(addr);
             }
             Instruction::JSR => {
-                let pc = self.register.get_pc() - 1;
-                self.register.push_stack((pc >> 8) as u8, bus);
-                self.register.push_stack(pc as u8, bus);
-                self.register.set_pc(value);
+                let low = self.fetch(bus) as u16;
+                self.dummy_read_stack(bus);
+                let pc = self.register.get_pc();
+                self.push(bus, (pc >> 8) as u8);
+                self.push(bus, pc as u8);
+                let hi = self.fetch(bus) as u16;
+                self.register.set_pc((hi << 8) | low);
+            }
+            Instruction::LAS => {
+                let m = self.fetch_operand(bus, mode);
+                let res = m & self.register.get_sp();
+                self.load_a(res);
+                self.register.set_x(res).set_sp(res);
             }
             Instruction::LAX => {
-                let mut res = value;
-                if opcode.mode != Addressing::Immediate {
-                    res = bus.peek(value) as u16;
-                }
-                if opcode.mode == Addressing::AbsoluteX || opcode.mode == Addressing::AbsoluteY || opcode.mode == Addressing::IndirectIndexed {
-                    self.extra_cycle = if self.page_crossed { 1 } else { 0 };
-                }
-                self.register
-                    .set_flag(StatusFlags::NEGATIVE, res & (1 << 7) != 0)
-                    .set_flag(StatusFlags::ZERO, res == 0)
-                    .set_a(res as u8)
-                    .set_x(res as u8);
+                let m = self.fetch_operand(bus, mode);
+                self.load_a(m);
+                self.register.set_x(m);
             }
             Instruction::LDA => {
-                let res = if opcode.mode == Addressing::Immediate { value } else { bus.peek(value) as u16 };
-                if opcode.mode == Addressing::AbsoluteX || opcode.mode == Addressing::AbsoluteY || opcode.mode == Addressing::IndirectIndexed {
-                    self.extra_cycle = if self.page_crossed { 1 } else { 0 };
-                }
-                self.register
-                    .set_flag(StatusFlags::NEGATIVE, res & (1 << 7) != 0)
-                    .set_flag(StatusFlags::ZERO, res == 0)
-                    .set_a(res as u8);
-            }
-            Instruction::LDX => {   
-                let mut res = value;
-                if opcode.mode != Addressing::Immediate {
-                    res = bus.peek(value) as u16;
-                }
-                if opcode.mode == Addressing::AbsoluteY {
-                    self.extra_cycle = if self.page_crossed { 1 } else { 0 };
-                }
-                self.register
-                    .set_flag(StatusFlags::NEGATIVE, res & (1 << 7) != 0)
-                    .set_flag(StatusFlags::ZERO, res == 0)
-                    .set_x(res as u8);
+                let m = self.fetch_operand(bus, mode);
+                self.load_a(m);
+            }
+            Instruction::LDX => {
+                let m = self.fetch_operand(bus, mode);
+                self.load_x(m);
             }
             Instruction::LDY => {
-                let mut res = value;
-                if opcode.mode != Addressing::Immediate {
-                    res = bus.peek(value) as u16;
-                }
-                if opcode.mode == Addressing::AbsoluteX {
-                    self.extra_cycle = if self.page_crossed { 1 } else { 0 };
+                let m = self.fetch_operand(bus, mode);
+                self.load_y(m);
+            }
+            Instruction::LSR => self.shift(bus, mode, Self::lsr),
+            Instruction::LXA => {
+                let m = self.fetch_operand(bus, mode);
+                // Unstable: the same magic constant as XAA
+                let res = (self.register.get_a() | 0xEE) & m;
+                self.load_a(res);
+                self.register.set_x(res);
+            }
+            Instruction::NOP => {
+                // Some 65C02 NOPs take a single cycle, others more than their addressing mode
+                let start = self.cycles - 1;
+                if *mode != Addressing::Implied {
+                    self.fetch_operand(bus, mode);
                 }
-                self.register
-                    .set_flag(StatusFlags::NEGATIVE, res & (1 << 7) != 0)
-                    .set_flag(StatusFlags::ZERO, res == 0)
-                    .set_y(res as u8);
-            }
-            Instruction::LSR => {
-                let old: u8;
-                if opcode.mode == Addressing::Accumulator {
-                    old = self.register.get_a();
-                    let a = self.register.set_a(old>>1).get_a();
-                    self.register
-                        .set_flag(StatusFlags::ZERO, a == 0)
-                        .set_flag(StatusFlags::NEGATIVE, a & (1 << 7) != 0);
-                } else {
-                    old = bus.peek(value);
-                    let m = bus.write(value, old >> 1);
-                    self.register
-                        .set_flag(StatusFlags::ZERO, m == 0)
-                        .set_flag(StatusFlags::NEGATIVE, m & (1 << 7) != 0);
+                while self.cycles - start < opcode.cycle as Cycle {
+                    self.dummy_read_pc(bus);
                 }
-                self.register.set_flag(StatusFlags::CARRY, old & 0x1 != 0);
             }
-            Instruction::NOP => return EmulationStatus::PROCESSING,
             Instruction::ORA => {
-                let a = self.register.get_a();
-                let mut m = value;
-                if opcode.mode != Addressing::Immediate {
-                    m = bus.peek(value) as u16;
-                }
-                let res = a | m as u8;
-                if opcode.mode == Addressing::AbsoluteX || opcode.mode == Addressing::AbsoluteY || opcode.mode == Addressing::IndirectIndexed {
-                    self.extra_cycle = if self.page_crossed { 1 } else { 0 };
-                }
-                self.register
-                    .set_a(res)
-                    .set_flag(StatusFlags::ZERO, res == 0)
-                    .set_flag(StatusFlags::NEGATIVE, res & (1 << 7) != 0);
-
+                let m = self.fetch_operand(bus, mode);
+                let res = self.register.get_a() | m;
+                self.load_a(res);
             }
             Instruction::PHA => {
+                self.dummy_read_pc(bus);
                 let a = self.register.get_a();
-                self.register.push_stack(a, bus);
+                self.push(bus, a);
+            }
+            Instruction::PHX => {
+                self.dummy_read_pc(bus);
+                let x = self.register.get_x();
+                self.push(bus, x);
+            }
+            Instruction::PHY => {
+                self.dummy_read_pc(bus);
+                let y = self.register.get_y();
+                self.push(bus, y);
             }
             Instruction::PHP => {
-                self.register.set_flag(StatusFlags::BREAK, true);
-                self.register.push_stack(self.register.get_sr(), bus);
-                self.register.set_flag(StatusFlags::BREAK, false);
+                self.dummy_read_pc(bus);
+                let sr = self.register.get_sr() | 0x30;
+                self.push(bus, sr);
             }
             Instruction::PLA => {
-                let res = self.register.pop_stack(bus);
-                self.register
-                    .set_a(res)
-                    .set_flag(StatusFlags::ZERO, res == 0)
-                    .set_flag(StatusFlags::NEGATIVE, res & (1 << 7) != 0);
+                self.dummy_read_pc(bus);
+                self.dummy_read_stack(bus);
+                let res = self.pop(bus);
+                self.load_a(res);
+            }
+            Instruction::PLX => {
+                self.dummy_read_pc(bus);
+                self.dummy_read_stack(bus);
+                let res = self.pop(bus);
+                self.load_x(res);
+            }
+            Instruction::PLY => {
+                self.dummy_read_pc(bus);
+                self.dummy_read_stack(bus);
+                let res = self.pop(bus);
+                self.load_y(res);
             }
             Instruction::PLP => {
-                let res = self.register.pop_stack(bus);
+                self.dummy_read_pc(bus);
+                self.dummy_read_stack(bus);
+                let res = self.pop(bus);
                 self.register
-                    .set_sr(res as u8)
+                    .set_sr(res)
                     .set_flag(StatusFlags::UNUSED, true)
                     .set_flag(StatusFlags::BREAK, false);
             }
-            Instruction::RLA => {
-                let old = bus.peek(value);
-                let mut m = old << 1;
-                if self.register.get_flag(StatusFlags::CARRY) {
-                    m += 1;
-                }
-                bus.write(value, m);
-                let result = self.register.get_a() & m;
-                self.register
-                    .set_flag(StatusFlags::ZERO, result == 0)
-                    .set_flag(StatusFlags::NEGATIVE, result & (1 << 7) != 0)
-                    .set_flag(StatusFlags::CARRY, old & (1 << 7) != 0)
-                    .set_a(result);
-
+            Instruction::RMB => {
+                let bit = (code >> 4) & 0x07;
+                self.modify(bus, mode, |_, v| v & !(1 << bit));
             }
-            Instruction::ROL => {
-                if opcode.mode == Addressing::Accumulator {
-                    let old = self.register.get_a();
-                    let mut a = old << 1;
-                    if self.register.get_flag(StatusFlags::CARRY) {
-                        a += 1;
-                    }
-                    self.register
-                        .set_flag(StatusFlags::CARRY, old & (1 << 7) != 0)
-                        .set_flag(StatusFlags::NEGATIVE, a & (1 << 7) != 0)
-                        .set_a(a);
-                } else {
-                    let old = bus.peek(value);
-                    let mut m = old << 1;
-                    if self.register.get_flag(StatusFlags::CARRY) {
-                        m += 1;
-                    }
-                    self.register
-                        .set_flag(StatusFlags::CARRY, old & (1 << 7) != 0)
-                        .set_flag(StatusFlags::NEGATIVE, m & (1 << 7) != 0);
-                        bus.write(value, m);
-                }
-                self.register.set_flag(StatusFlags::ZERO, self.register.get_a() == 0);
-            }
-            Instruction::ROR => {
-                if opcode.mode == Addressing::Accumulator {
-                    let old = self.register.get_a();
-                    let mut a = old >> 1;
-                    if self.register.get_flag(StatusFlags::CARRY) {
-                        a += 128;
-                    }
-                    self.register
-                        .set_flag(StatusFlags::CARRY, old & (1 << 0) != 0)
-                        .set_flag(StatusFlags::NEGATIVE, a & (1 << 7) != 0)
-                        .set_a(a);
-                } else {
-                    let old = bus.peek(value);
-                    let mut m = old >> 1;
-                    if self.register.get_flag(StatusFlags::CARRY) {
-                        m += 128;
-                    }
-                    self.register
-                        .set_flag(StatusFlags::NEGATIVE, m & (1 << 7) != 0)
-                        .set_flag(StatusFlags::CARRY, old & (1 << 0) != 0);
-                    bus.write(value, m);
-                }
+            Instruction::SMB => {
+                let bit = (code >> 4) & 0x07;
+                self.modify(bus, mode, |_, v| v | (1 << bit));
             }
+            Instruction::RLA => {
+                let res = self.modify(bus, mode, Self::rol);
+                let res = self.register.get_a() & res;
+                self.load_a(res);
+            }
+            Instruction::ROL => self.shift(bus, mode, Self::rol),
+            Instruction::ROR => self.shift(bus, mode, Self::ror),
             Instruction::RRA => {
-                let a = self.register.get_a();
-                let old = bus.peek(value);
-                let mut m = old >> 1;
-                if self.register.get_flag(StatusFlags::CARRY) {
-                    m += 128;
-                }
-                self.register
-                    .set_flag(StatusFlags::CARRY, old & (1 << 0) != 0);
-                bus.write(value, m);
-                let c = match self.register.get_flag(StatusFlags::CARRY) {
-                    true => 1,
-                    false => 0,
-                };
-                let result = a as u16 + m as u16 + c as u16;
-                self.register
-                    .set_flag(StatusFlags::ZERO, result == 0)
-                    .set_flag(StatusFlags::NEGATIVE, result & (1 << 7) != 0)
-                    .set_flag(StatusFlags::CARRY, result > 0xFF)
-                    .set_flag(StatusFlags::OVERFLOW, (a as u8 ^ result as u8) & ((value as u8 ^ result as u8)) & 0x80 != 0)
-                    .set_a(result as u8);
+                let res = self.modify(bus, mode, Self::ror);
+                self.adc(res);
             }
             Instruction::RTI => {
-                let sr = self.register.pop_stack(bus);
-                let low = self.register.pop_stack(bus) as u16;
-                let hi = self.register.pop_stack(bus) as u16;
+                self.dummy_read_pc(bus);
+                self.dummy_read_stack(bus);
+                let sr = self.pop(bus);
                 self.register
                     .set_sr(sr)
-                    .set_pc(low | (hi << 8))
-                    .set_flag(StatusFlags::UNUSED, true);
+                    .set_flag(StatusFlags::UNUSED, true)
+                    .set_flag(StatusFlags::BREAK, false);
+                let low = self.pop(bus) as u16;
+                let hi = self.pop(bus) as u16;
+                self.register.set_pc(low | (hi << 8));
+                self.in_nmi = false;
             }
             Instruction::RTS => {
-                let low = self.register.pop_stack(bus) as u16;
-                let hi = self.register.pop_stack(bus) as u16;
+                self.dummy_read_pc(bus);
+                self.dummy_read_stack(bus);
+                let low = self.pop(bus) as u16;
+                let hi = self.pop(bus) as u16;
                 self.register.set_pc(low | (hi << 8));
-                self.register.incr_pc();
+                self.fetch(bus);
             }
             Instruction::SAX => {
                 let res = self.register.get_a() & self.register.get_x();
-                bus.write(value, res);
+                self.store(bus, mode, res);
             }
             Instruction::SBC => {
-                let a = self.register.get_a();
-                let mut m = value as u8;
-                if opcode.mode != Addressing::Immediate {
-                    m = bus.peek(value);
-                }
-                let c = match self.register.get_flag(StatusFlags::CARRY) {
-                    true => 0,
-                    false => 1,
-                };
-                let res: i16 = a as i16 - m as i16 - c as i16;
-                self.register
-                    .set_flag(StatusFlags::CARRY, res >= 0)
-                    .set_flag(StatusFlags::ZERO, res as u8 == 0)
-                    .set_flag(StatusFlags::NEGATIVE, res & (1 << 7) != 0)
-                    .set_flag(StatusFlags::OVERFLOW, (a as u8 ^ m) & ((res as u8 ^ a)) & 0x80 != 0)
-                    .set_a(res as u8);
+                let m = self.fetch_operand(bus, mode);
+                self.sbc(m);
+                self.decimal_cycle(bus);
             }
             Instruction::SEC => {
+                self.dummy_read_pc(bus);
                 self.register.set_flag(StatusFlags::CARRY, true);
             }
             Instruction::SED => {
+                self.dummy_read_pc(bus);
                 self.register.set_flag(StatusFlags::DECIMAL, true);
             }
             Instruction::SEI => {
+                self.dummy_read_pc(bus);
                 self.register.set_flag(StatusFlags::INTERRUPT, true);
             }
+            Instruction::SHA => {
+                let v = self.register.get_a() & self.register.get_x();
+                let y = self.register.get_y();
+                self.store_high_and(bus, mode, y, v);
+            }
+            Instruction::SHX => {
+                let v = self.register.get_x();
+                let y = self.register.get_y();
+                self.store_high_and(bus, mode, y, v);
+            }
+            Instruction::SHY => {
+                let v = self.register.get_y();
+                let x = self.register.get_x();
+                self.store_high_and(bus, mode, x, v);
+            }
             Instruction::SLO => {
-                let old = bus.peek(value);
-                let m = bus.write(value, old << 1);
-                self.register.set_flag(StatusFlags::CARRY, old & (1 << 7) != 0);
-                let result = self.register.get_a() | m;
-                self.register
-                    .set_flag(StatusFlags::ZERO, result == 0)
-                    .set_flag(StatusFlags::NEGATIVE, result & (1 << 7) != 0)
-                    .set_a(result);
+                let res = self.modify(bus, mode, Self::asl);
+                let res = self.register.get_a() | res;
+                self.load_a(res);
             }
             Instruction::SRE => {
-                let old = bus.peek(value);
-                let m = bus.write(value, old >> 1);
-                let result = self.register.get_a() ^ m;
-                self.register
-                    .set_flag(StatusFlags::ZERO, result == 0)
-                    .set_flag(StatusFlags::NEGATIVE, result & (1 << 7) != 0)
-                    .set_flag(StatusFlags::CARRY, old & 0x1 != 0)
-                    .set_a(result);
+                let res = self.modify(bus, mode, Self::lsr);
+                let res = self.register.get_a() ^ res;
+                self.load_a(res);
             }
             Instruction::STA => {
-                bus.write(value, self.register.get_a());
+                let a = self.register.get_a();
+                self.store(bus, mode, a);
             }
+            Instruction::STP => {
+                self.jammed = true;
+                self.dummy_read_pc(bus);
+                return EmulationStatus::JAMMED;
+            }
+            Instruction::STZ => self.store(bus, mode, 0),
             Instruction::STX => {
-                bus.write(value, self.register.get_x());
+                let x = self.register.get_x();
+                self.store(bus, mode, x);
             }
             Instruction::STY => {
-                bus.write(value, self.register.get_y());
+                let y = self.register.get_y();
+                self.store(bus, mode, y);
+            }
+            Instruction::TAS => {
+                let sp = self.register.get_a() & self.register.get_x();
+                self.register.set_sp(sp);
+                let y = self.register.get_y();
+                self.store_high_and(bus, mode, y, sp);
             }
             Instruction::TAX => {
+                self.dummy_read_pc(bus);
                 let a = self.register.get_a();
-                self.register
-                    .set_flag(StatusFlags::ZERO, a == 0)
-                    .set_flag(StatusFlags::NEGATIVE, a & (1 << 7) != 0)
-                    .set_x(a);
+                self.load_x(a);
             }
             Instruction::TAY => {
+                self.dummy_read_pc(bus);
                 let a = self.register.get_a();
-                self.register
-                    .set_flag(StatusFlags::ZERO, a == 0)
-                    .set_flag(StatusFlags::NEGATIVE, a & (1 << 7) != 0)
-                    .set_y(a);
+                self.load_y(a);
+            }
+            Instruction::TRB | Instruction::TSB => {
+                let a = self.register.get_a();
+                let set = opcode.name == Instruction::TSB;
+                self.modify(bus, mode, |cpu, v| {
+                    cpu.register.set_flag(StatusFlags::ZERO, v & a == 0);
+                    if set { v | a } else { v & !a }
+                });
             }
             Instruction::TSX => {
+                self.dummy_read_pc(bus);
                 let sp = self.register.get_sp();
-                self.register
-                    .set_flag(StatusFlags::ZERO, sp == 0)
-                    .set_flag(StatusFlags::NEGATIVE, sp & (1 << 7) != 0)
-                    .set_x(sp);
+                self.load_x(sp);
             }
             Instruction::TXA => {
+                self.dummy_read_pc(bus);
                 let x = self.register.get_x();
-                self.register
-                    .set_flag(StatusFlags::ZERO, x == 0)
-                    .set_flag(StatusFlags::NEGATIVE, x & (1 << 7) != 0)
-                    .set_a(x);
+                self.load_a(x);
             }
             Instruction::TXS => {
+                self.dummy_read_pc(bus);
                 let x = self.register.get_x();
                 self.register.set_sp(x);
             }
             Instruction::TYA => {
+                self.dummy_read_pc(bus);
                 let y = self.register.get_y();
-                self.register
-                    .set_flag(StatusFlags::ZERO, y == 0)
-                    .set_flag(StatusFlags::NEGATIVE, y & (1 << 7) != 0)
-                    .set_a(y);
+                self.load_a(y);
+            }
+            Instruction::WAI => {
+                self.dummy_read_pc(bus);
+                self.dummy_read_pc(bus);
+                self.waiting = true;
+            }
+            Instruction::XAA => {
+                let m = self.fetch_operand(bus, mode);
+                let res = (self.register.get_a() | 0xEE) & self.register.get_x() & m;
+                self.load_a(res);
             }
         }
         EmulationStatus::PROCESSING
@@ -788,6 +1116,7 @@ mod tests {
     use crate::CpuBus;
     use crate::controller::Controller;
     use crate::cpu::memory::Ram;
+    use crate::cpu::interrupt::IrqLine;
     #[allow(unused_imports)]
     use crate::cpu::register::Register;
     use crate::memory::Memory;
@@ -798,9 +1127,96 @@ mod tests {
         ram: Ram,
         ppu: Ppu,
         rom: Cartbridge,
-        controller: Controller
+        controller: Controller,
+        irq: IrqLine,
+    }
+
+    // Flat 64K bus recording every access that happens on a CPU cycle
+    struct TestBus {
+        mem: Vec<u8>,
+        accesses: Vec<(u16, u8, bool)>,
+        ticked: bool,
+        cycle: usize,
+        irq_cycle: Option<usize>,
+        nmi_cycle: Option<usize>,
+        oam_dma: Option<u8>,
+        // Cycle of the DMC request, the sample is read from $C000
+        dmc_cycle: Option<usize>,
+        dmc_sample: Option<u8>,
+    }
+
+    impl TestBus {
+        fn new(program: &[u8], org: u16) -> TestBus {
+            let mut mem = vec![0u8; 0x10000];
+            mem[org as usize..org as usize + program.len()].copy_from_slice(program);
+            TestBus {
+                mem,
+                accesses: Vec::new(),
+                ticked: false,
+                cycle: 0,
+                irq_cycle: None,
+                nmi_cycle: None,
+                oam_dma: None,
+                dmc_cycle: None,
+                dmc_sample: None,
+            }
+        }
+    }
+
+    impl Bus for TestBus {
+        fn peek(&mut self, i: u16) -> u8 {
+            let v = self.mem[i as usize];
+            if self.ticked {
+                self.accesses.push((i, v, false));
+                self.ticked = false;
+            }
+            v
+        }
+        fn write(&mut self, i: u16, v: u8) -> u8 {
+            self.mem[i as usize] = v;
+            if self.ticked {
+                self.accesses.push((i, v, true));
+                self.ticked = false;
+            }
+            if i == 0x4014 {
+                self.oam_dma = Some(v);
+            }
+            v
+        }
+        fn tick(&mut self) {
+            self.ticked = true;
+            self.cycle += 1;
+        }
+        fn poll_nmi(&mut self) -> bool {
+            if self.nmi_cycle == Some(self.cycle) {
+                self.nmi_cycle = None;
+                return true;
+            }
+            false
+        }
+        fn irq_line(&mut self) -> bool {
+            self.irq_cycle.is_some_and(|c| self.cycle >= c)
+        }
+        fn poll_oam_dma(&mut self) -> Option<u8> {
+            self.oam_dma.take()
+        }
+        fn poll_dmc_dma(&mut self) -> Option<u16> {
+            match self.dmc_cycle {
+                Some(c) if self.cycle >= c => {
+                    self.dmc_cycle = None;
+                    Some(0xC000)
+                }
+                _ => None,
+            }
+        }
+        fn dmc_dma_done(&mut self, v: u8) {
+            self.dmc_sample = Some(v);
+        }
     }
 
+    fn assembled(source: &str) -> Vec<u8> {
+        asm::assemble(Variant::Ricoh2A03, source).unwrap().bytes
+    }
     fn create_test_context(program: &Vec<u8>) -> TestContext {
         let mut cartbridge = Cartbridge::new();
         let cpu = Cpu::new();
@@ -813,7 +1229,8 @@ mod tests {
             ram: cpu_ram,
             ppu: ppu,
             rom: cartbridge,
-            controller
+            controller,
+            irq: IrqLine::new(),
         }
     }
     #[test]
@@ -821,18 +1238,38 @@ mod tests {
         let mut ctx = create_test_context(&Vec::new());
         ctx.rom.write(0xFFFC - 0xC000, 0x01);
         ctx.rom.write(0xFFFD - 0xC000, 0x80);
-        let mut cpu_bus = CpuBus::new(&mut ctx.ram, &mut ctx.rom, &mut ctx.ppu, &mut ctx.controller);
-        ctx.cpu.reset(&mut cpu_bus);
+        let mut cpu_bus = CpuBus::new(&mut ctx.ram, &mut ctx.rom, &mut ctx.ppu, &mut ctx.controller, &mut ctx.irq);
+        ctx.cpu.register.set_a(0x12);
+        assert_eq!(ctx.cpu.power_on(&mut cpu_bus), 7);
         assert_eq!(ctx.cpu.register.get_x(), 0);
         assert_eq!(ctx.cpu.register.get_a(), 0);
         assert_eq!(ctx.cpu.register.get_y(), 0);
+        assert_eq!(ctx.cpu.register.get_sp(), 0xFD);
+        assert_eq!(ctx.cpu.register.get_sr(), 0x24);
         assert_eq!(ctx.cpu.register.get_pc(), 0x8001);
     }
     #[test]
+    fn soft_reset_should_keep_registers_and_ram() {
+        let mut bus = TestBus::new(&[], 0x0200);
+        bus.mem[0xFFFC] = 0x00;
+        bus.mem[0xFFFD] = 0x02;
+        bus.mem[0x01F0] = 0x99;
+        let mut cpu = Cpu::new();
+        cpu.register.set_a(0x12).set_x(0x34).set_y(0x56).set_sp(0xF3).set_sr(0x21);
+        assert_eq!(cpu.soft_reset(&mut bus), 7);
+        assert_eq!((cpu.register.get_a(), cpu.register.get_x(), cpu.register.get_y()), (0x12, 0x34, 0x56));
+        assert_eq!(cpu.register.get_sp(), 0xF0);
+        assert_eq!(cpu.register.get_sr(), 0x25);
+        assert_eq!(cpu.register.get_pc(), 0x0200);
+        // Nothing is written, the stack is only read
+        assert!(bus.accesses.iter().all(|(_, _, write)| !write));
+        assert_eq!(bus.mem[0x01F0], 0x99);
+    }
+    #[test]
     fn adc_immediate_should_add_to_acc() {
-        let program:Vec<u8> = vec!(0x69, 0xfe, 0x69, 0x01); // ADC #$a1
+        let program = assembled("ADC #$FE\nADC #$01");
         let mut ctx = create_test_context(&program);
-        let mut cpu_bus = CpuBus::new(&mut ctx.ram, &mut ctx.rom, &mut ctx.ppu, &mut ctx.controller);
+        let mut cpu_bus = CpuBus::new(&mut ctx.ram, &mut ctx.rom, &mut ctx.ppu, &mut ctx.controller, &mut ctx.irq);
         ctx.cpu.register.set_a(0x01);
         ctx.cpu.run_instructions(1, &mut cpu_bus);
         assert_eq!(ctx.cpu.register.get_a(), 0xff);
@@ -849,57 +1286,57 @@ mod tests {
     }
     #[test]
     fn test_adc_zeropage() {
-        let program:Vec<u8> = vec!(0x65, 0xa1); // ADC $a1
+        let program = assembled("ADC $A1");
         let mut ctx = create_test_context(&program);
         ctx.ram.write(0x00a1, 0x08);
-        let mut cpu_bus = CpuBus::new(&mut ctx.ram, &mut ctx.rom, &mut ctx.ppu, &mut ctx.controller);
+        let mut cpu_bus = CpuBus::new(&mut ctx.ram, &mut ctx.rom, &mut ctx.ppu, &mut ctx.controller, &mut ctx.irq);
         ctx.cpu.run_instructions(1, &mut cpu_bus);
         assert_eq!(ctx.cpu.register.get_a(), 0x08);
     }
     #[test]
     fn test_adc_zeropagex() {
-        let program:Vec<u8> = vec!(0x75, 0xa1); // ADC $a1,X
+        let program = assembled("ADC $A1,X");
         let mut ctx = create_test_context(&program);
         ctx.ram.write(0x00a2, 0x08);
         ctx.cpu.register.set_x(0x01);
-        let mut cpu_bus = CpuBus::new(&mut ctx.ram, &mut ctx.rom, &mut ctx.ppu, &mut ctx.controller);
+        let mut cpu_bus = CpuBus::new(&mut ctx.ram, &mut ctx.rom, &mut ctx.ppu, &mut ctx.controller, &mut ctx.irq);
         ctx.cpu.run_instructions(1, &mut cpu_bus);
         assert_eq!(ctx.cpu.register.get_a(), 0x08);
     }
     #[test]
     fn test_adc_absolute() {
-        let program:Vec<u8> = vec!(0x6D, 0xa1, 0x00); // ADC $00a1
+        let program = assembled("ADC a:$00A1");
         let mut ctx = create_test_context(&program);
         ctx.ram.write(0x00a1, 0x08);
-        let mut cpu_bus = CpuBus::new(&mut ctx.ram, &mut ctx.rom, &mut ctx.ppu, &mut ctx.controller);
+        let mut cpu_bus = CpuBus::new(&mut ctx.ram, &mut ctx.rom, &mut ctx.ppu, &mut ctx.controller, &mut ctx.irq);
         ctx.cpu.run_instructions(1, &mut cpu_bus);
         assert_eq!(ctx.cpu.register.get_a(), 0x08);
     }
     #[test]
     fn test_adc_absolutex() {
-        let program:Vec<u8> = vec!(0x7D, 0xa1, 0x00); // ADC $00a1
+        let program = assembled("ADC a:$00A1,X");
         let mut ctx = create_test_context(&program);
         ctx.ram.write(0x00a2, 0x08);
         ctx.cpu.register.set_x(0x01);
-        let mut cpu_bus = CpuBus::new(&mut ctx.ram, &mut ctx.rom, &mut ctx.ppu, &mut ctx.controller);
+        let mut cpu_bus = CpuBus::new(&mut ctx.ram, &mut ctx.rom, &mut ctx.ppu, &mut ctx.controller, &mut ctx.irq);
         ctx.cpu.run_instructions(1, &mut cpu_bus);
         assert_eq!(ctx.cpu.register.get_a(), 0x08);
     }
     #[test]
     fn test_asl_accumulator() {
-        let program:Vec<u8> = vec!(0x0A); // ASL A
+        let program = assembled("ASL A");
         let mut ctx = create_test_context(&program);
         ctx.cpu.register.set_a(0x02);
-        let mut cpu_bus = CpuBus::new(&mut ctx.ram, &mut ctx.rom, &mut ctx.ppu, &mut ctx.controller);
+        let mut cpu_bus = CpuBus::new(&mut ctx.ram, &mut ctx.rom, &mut ctx.ppu, &mut ctx.controller, &mut ctx.irq);
         ctx.cpu.run_instructions(1, &mut cpu_bus);
         assert_eq!(ctx.cpu.register.get_a(), 0x04);
     }
     #[test]
     fn test_asl() {
-        let program:Vec<u8> = vec!(0x06, 0x04); // ASL $04
+        let program = assembled("ASL $04");
         let mut ctx = create_test_context(&program);
         ctx.ram.write(0x0004, 0x02);
-        let mut cpu_bus = CpuBus::new(&mut ctx.ram, &mut ctx.rom, &mut ctx.ppu, &mut ctx.controller);
+        let mut cpu_bus = CpuBus::new(&mut ctx.ram, &mut ctx.rom, &mut ctx.ppu, &mut ctx.controller, &mut ctx.irq);
         ctx.cpu.run_instructions(1, &mut cpu_bus);
         assert_eq!(ctx.ram.peek(0x0004), 0x04);
     }
@@ -909,7 +1346,7 @@ mod tests {
         let mut ctx = create_test_context(&program);
         ctx.ram.write(0x0004, 0xaa);
         ctx.cpu.register.set_a(0x40);
-        let mut cpu_bus = CpuBus::new(&mut ctx.ram, &mut ctx.rom, &mut ctx.ppu, &mut ctx.controller);
+        let mut cpu_bus = CpuBus::new(&mut ctx.ram, &mut ctx.rom, &mut ctx.ppu, &mut ctx.controller, &mut ctx.irq);
         ctx.cpu.run_instructions(1, &mut cpu_bus);
         assert!(ctx.cpu.register.get_flag(StatusFlags::ZERO));
         assert!(ctx.cpu.register.get_flag(StatusFlags::NEGATIVE));
@@ -917,7 +1354,7 @@ mod tests {
         ctx = create_test_context(&program);
         ctx.cpu.register.set_a(0x40);
         ctx.ram.write(0x0004, 0x40);
-        cpu_bus = CpuBus::new(&mut ctx.ram, &mut ctx.rom, &mut ctx.ppu, &mut ctx.controller);
+        cpu_bus = CpuBus::new(&mut ctx.ram, &mut ctx.rom, &mut ctx.ppu, &mut ctx.controller, &mut ctx.irq);
         ctx.cpu.run_instructions(1, &mut cpu_bus);
         assert!(ctx.cpu.register.get_flag(StatusFlags::OVERFLOW));
     }
@@ -926,7 +1363,7 @@ mod tests {
         let program:Vec<u8> = vec!(0x4a); // LSR
         let mut ctx = create_test_context(&program);
         ctx.cpu.register.set_a(0x41);
-        let mut cpu_bus = CpuBus::new(&mut ctx.ram, &mut ctx.rom, &mut ctx.ppu, &mut ctx.controller);
+        let mut cpu_bus = CpuBus::new(&mut ctx.ram, &mut ctx.rom, &mut ctx.ppu, &mut ctx.controller, &mut ctx.irq);
         ctx.cpu.run_instructions(1, &mut cpu_bus);
         assert_eq!(ctx.cpu.register.get_a(), 0x20);
         assert_eq!(ctx.cpu.register.get_flag(StatusFlags::CARRY), true);
@@ -937,7 +1374,7 @@ mod tests {
         let mut ctx = create_test_context(&program);
         ctx.cpu.register.set_flag(StatusFlags::CARRY, true);
         ctx.cpu.register.set_flag(StatusFlags::NEGATIVE, true);
-        let mut cpu_bus = CpuBus::new(&mut ctx.ram, &mut ctx.rom, &mut ctx.ppu, &mut ctx.controller);
+        let mut cpu_bus = CpuBus::new(&mut ctx.ram, &mut ctx.rom, &mut ctx.ppu, &mut ctx.controller, &mut ctx.irq);
         ctx.cpu.register.push_stack(ctx.cpu.register.get_sr(), &mut cpu_bus);
         ctx.cpu.register.set_flag(StatusFlags::CARRY, false);
         ctx.cpu.run_instructions(1, &mut cpu_bus);
@@ -949,81 +1386,81 @@ mod tests {
     fn test_lda_immediate() {
         let program:Vec<u8> = vec!(0xa9, 0xff); // LDA #$ff
         let mut ctx = create_test_context(&program);
-        let mut cpu_bus = CpuBus::new(&mut ctx.ram, &mut ctx.rom, &mut ctx.ppu, &mut ctx.controller);
+        let mut cpu_bus = CpuBus::new(&mut ctx.ram, &mut ctx.rom, &mut ctx.ppu, &mut ctx.controller, &mut ctx.irq);
         ctx.cpu.run_instructions(1, &mut cpu_bus);
         assert_eq!(ctx.cpu.register.get_a(), 0xFF);
     }
     #[test]
     fn test_bne() {
-        let program:Vec<u8> = vec!(0xD0, 0x02, 0xa9, 0xff, 0x00); // BNE $02
+        let program = assembled("BNE skip\nLDA #$FF\nskip: BRK");
         let mut ctx = create_test_context(&program);
         ctx.ram.write(0x10, 0x0c);
         ctx.cpu.register.set_flag(StatusFlags::ZERO, true);
-        let mut cpu_bus = CpuBus::new(&mut ctx.ram, &mut ctx.rom, &mut ctx.ppu, &mut ctx.controller);
+        let mut cpu_bus = CpuBus::new(&mut ctx.ram, &mut ctx.rom, &mut ctx.ppu, &mut ctx.controller, &mut ctx.irq);
         ctx.cpu.run_instructions(2, &mut cpu_bus);
         assert_eq!(ctx.cpu.register.get_a(), 0xff);
     }
     #[test]
     fn test_bmi() {
-        let program:Vec<u8> = vec!(0x30, 0x02, 0xa9, 0xff, 0x00); // BMI $02
+        let program = assembled("BMI skip\nLDA #$FF\nskip: BRK");
         let mut ctx = create_test_context(&program);
         ctx.ram.write(0x10, 0x0c);
         ctx.cpu.register.set_flag(StatusFlags::NEGATIVE, false);
-        let mut cpu_bus = CpuBus::new(&mut ctx.ram, &mut ctx.rom, &mut ctx.ppu, &mut ctx.controller);
+        let mut cpu_bus = CpuBus::new(&mut ctx.ram, &mut ctx.rom, &mut ctx.ppu, &mut ctx.controller, &mut ctx.irq);
         ctx.cpu.run_instructions(2, &mut cpu_bus);
         assert_eq!(ctx.cpu.register.get_a(), 0xff);
     }
     #[test]
     fn test_bpl() {
-        let program:Vec<u8> = vec!(0x10, 0x02, 0xa9, 0xff, 0x00); // BPL $02
+        let program = assembled("BPL skip\nLDA #$FF\nskip: BRK");
         let mut ctx = create_test_context(&program);
         ctx.ram.write(0x10, 0x0c);
         ctx.cpu.register.set_flag(StatusFlags::NEGATIVE, true);
-        let mut cpu_bus = CpuBus::new(&mut ctx.ram, &mut ctx.rom, &mut ctx.ppu, &mut ctx.controller);
+        let mut cpu_bus = CpuBus::new(&mut ctx.ram, &mut ctx.rom, &mut ctx.ppu, &mut ctx.controller, &mut ctx.irq);
         ctx.cpu.run_instructions(2, &mut cpu_bus);
         assert_eq!(ctx.cpu.register.get_a(), 0xff);
     }
     #[test]
     fn test_bvc() {
-        let program:Vec<u8> = vec!(0x50, 0x02, 0xa9, 0xff, 0x00); // BVC $02
+        let program = assembled("BVC skip\nLDA #$FF\nskip: BRK");
         let mut ctx = create_test_context(&program);
         ctx.ram.write(0x10, 0x0c);
         ctx.cpu.register.set_flag(StatusFlags::OVERFLOW, true);
-        let mut cpu_bus = CpuBus::new(&mut ctx.ram, &mut ctx.rom, &mut ctx.ppu, &mut ctx.controller);
+        let mut cpu_bus = CpuBus::new(&mut ctx.ram, &mut ctx.rom, &mut ctx.ppu, &mut ctx.controller, &mut ctx.irq);
         let res = ctx.cpu.run_instructions(3, &mut cpu_bus);
         assert_eq!(ctx.cpu.register.get_a(), 0xff);
         assert_eq!(res.0, 2 + 2 + 7);
         ctx = create_test_context(&program);
         ctx.cpu.register.set_flag(StatusFlags::OVERFLOW, false);
-        cpu_bus = CpuBus::new(&mut ctx.ram, &mut ctx.rom, &mut ctx.ppu, &mut ctx.controller);
+        cpu_bus = CpuBus::new(&mut ctx.ram, &mut ctx.rom, &mut ctx.ppu, &mut ctx.controller, &mut ctx.irq);
         let res = ctx.cpu.run_instructions(2, &mut cpu_bus);
         assert_eq!(ctx.cpu.register.get_a(), 0x00);
         assert_eq!(res.0, 2 + 1 + 7);
     }
     #[test]
     fn test_bvs() {
-        let program:Vec<u8> = vec!(0x70, 0x02, 0xa9, 0xff, 0x00); // BVS $02
+        let program = assembled("BVS skip\nLDA #$FF\nskip: BRK");
         let mut ctx = create_test_context(&program);
         ctx.ram.write(0x10, 0x0c);
         ctx.cpu.register.set_flag(StatusFlags::OVERFLOW, false);
-        let mut cpu_bus = CpuBus::new(&mut ctx.ram, &mut ctx.rom, &mut ctx.ppu, &mut ctx.controller);
+        let mut cpu_bus = CpuBus::new(&mut ctx.ram, &mut ctx.rom, &mut ctx.ppu, &mut ctx.controller, &mut ctx.irq);
         let res = ctx.cpu.run_instructions(3, &mut cpu_bus);
         assert_eq!(ctx.cpu.register.get_a(), 0xff);
         assert_eq!(res.0, 2 + 2 + 7);
         let mut ctx = create_test_context(&program);
         ctx.ram.write(0x10, 0x0c);
         ctx.cpu.register.set_flag(StatusFlags::OVERFLOW, true);
-        let mut cpu_bus = CpuBus::new(&mut ctx.ram, &mut ctx.rom, &mut ctx.ppu, &mut ctx.controller);
+        let mut cpu_bus = CpuBus::new(&mut ctx.ram, &mut ctx.rom, &mut ctx.ppu, &mut ctx.controller, &mut ctx.irq);
         let res = ctx.cpu.run_instructions(2, &mut cpu_bus);
         assert_eq!(ctx.cpu.register.get_a(), 0x00);
         assert_eq!(res.0, 2 + 1 + 7);
     }
     #[test]
     fn test_dec_zeropage() {
-        let program:Vec<u8> = vec!(0xC6, 0x10, 0xC6, 0x10, 0xD6, 0x0F); // DEC #$10
+        let program = assembled("DEC $10\nDEC $10\nDEC $0F,X");
         let mut ctx = create_test_context(&program);
         ctx.ram.write(0x10, 0x02);
-        let mut cpu_bus = CpuBus::new(&mut ctx.ram, &mut ctx.rom, &mut ctx.ppu, &mut ctx.controller);
+        let mut cpu_bus = CpuBus::new(&mut ctx.ram, &mut ctx.rom, &mut ctx.ppu, &mut ctx.controller, &mut ctx.irq);
         let res = ctx.cpu.run_instructions(1, &mut cpu_bus);
         assert_eq!(cpu_bus.peek(0x10), 0x01);
         assert!(!ctx.cpu.register.get_flag(StatusFlags::ZERO));
@@ -1043,10 +1480,10 @@ mod tests {
     }
     #[test]
     fn test_dec_absolute() {
-        let program:Vec<u8> = vec!(0xCE, 0x01, 0x10, 0xCE, 0x01, 0x10, 0xDE, 0x00, 0x10); // DEC #$10
+        let program = assembled("DEC $1001\nDEC $1001\nDEC $1000,X");
         let mut ctx = create_test_context(&program);
         ctx.ram.write(0x1001, 0x02);
-        let mut cpu_bus = CpuBus::new(&mut ctx.ram, &mut ctx.rom, &mut ctx.ppu, &mut ctx.controller);
+        let mut cpu_bus = CpuBus::new(&mut ctx.ram, &mut ctx.rom, &mut ctx.ppu, &mut ctx.controller, &mut ctx.irq);
         let res = ctx.cpu.run_instructions(1, &mut cpu_bus);
         assert_eq!(cpu_bus.peek(0x1001), 0x01);
         assert!(!ctx.cpu.register.get_flag(StatusFlags::ZERO));
@@ -1069,7 +1506,7 @@ mod tests {
         let program:Vec<u8> = vec!(0xa5, 0xa0); // LDA $a0
         let mut ctx = create_test_context(&program);
         ctx.ram.write(0x00a0, 0x08);
-        let mut cpu_bus = CpuBus::new(&mut ctx.ram, &mut ctx.rom, &mut ctx.ppu, &mut ctx.controller);
+        let mut cpu_bus = CpuBus::new(&mut ctx.ram, &mut ctx.rom, &mut ctx.ppu, &mut ctx.controller, &mut ctx.irq);
         ctx.cpu.run_instructions(1, &mut cpu_bus);
         assert_eq!(ctx.cpu.register.get_a(), 0x08);
     }
@@ -1079,7 +1516,7 @@ mod tests {
         let mut ctx = create_test_context(&program);
         ctx.ram.write(0x00a5, 0x08);
         ctx.cpu.register.set_x(0x05);
-        let mut cpu_bus = CpuBus::new(&mut ctx.ram, &mut ctx.rom, &mut ctx.ppu, &mut ctx.controller);
+        let mut cpu_bus = CpuBus::new(&mut ctx.ram, &mut ctx.rom, &mut ctx.ppu, &mut ctx.controller, &mut ctx.irq);
         ctx.cpu.run_instructions(1, &mut cpu_bus);
         assert_eq!(ctx.cpu.register.get_a(), 0x08);
     }
@@ -1088,7 +1525,7 @@ mod tests {
         let program:Vec<u8> = vec!(0xad, 0x00, 0x1F);  // LDA $f000
         let mut ctx = create_test_context(&program);
         ctx.ram.write(0x1f00, 0x0F);
-        let mut cpu_bus = CpuBus::new(&mut ctx.ram, &mut ctx.rom, &mut ctx.ppu, &mut ctx.controller);
+        let mut cpu_bus = CpuBus::new(&mut ctx.ram, &mut ctx.rom, &mut ctx.ppu, &mut ctx.controller, &mut ctx.irq);
         ctx.cpu.run_instructions(1, &mut cpu_bus);
         assert_eq!(ctx.cpu.register.get_a(), 0x0F);
     }
@@ -1098,7 +1535,7 @@ mod tests {
         let mut ctx = create_test_context(&program);
         ctx.ram.write(0x00aa, 0x0F);
         ctx.cpu.register.set_x(0x05);
-        let mut cpu_bus = CpuBus::new(&mut ctx.ram, &mut ctx.rom, &mut ctx.ppu, &mut ctx.controller);
+        let mut cpu_bus = CpuBus::new(&mut ctx.ram, &mut ctx.rom, &mut ctx.ppu, &mut ctx.controller, &mut ctx.irq);
         ctx.cpu.run_instructions(1, &mut cpu_bus);
         assert_eq!(ctx.cpu.register.get_a(), 0x0F);
     }
@@ -1108,7 +1545,7 @@ mod tests {
         let mut ctx = create_test_context(&program);
         ctx.ram.write(0x00aa, 0x0F);
         ctx.cpu.register.set_y(0x05);
-        let mut cpu_bus = CpuBus::new(&mut ctx.ram, &mut ctx.rom, &mut ctx.ppu, &mut ctx.controller);
+        let mut cpu_bus = CpuBus::new(&mut ctx.ram, &mut ctx.rom, &mut ctx.ppu, &mut ctx.controller, &mut ctx.irq);
         ctx.cpu.run_instructions(1, &mut cpu_bus);
         assert_eq!(ctx.cpu.register.get_a(), 0x0F);
     }
@@ -1119,7 +1556,7 @@ mod tests {
         ctx.ram.write(0x00a1, 0xA3);
         ctx.ram.write(0x00a3, 0xA0);
         ctx.cpu.register.set_x(0x01);
-        let mut cpu_bus = CpuBus::new(&mut ctx.ram, &mut ctx.rom, &mut ctx.ppu, &mut ctx.controller);
+        let mut cpu_bus = CpuBus::new(&mut ctx.ram, &mut ctx.rom, &mut ctx.ppu, &mut ctx.controller, &mut ctx.irq);
         ctx.cpu.run_instructions(1, &mut cpu_bus);
         assert_eq!(ctx.cpu.register.get_a(), 0xA0);
     }
@@ -1130,7 +1567,7 @@ mod tests {
         ctx.ram.write(0x00a4, 0xA0);
         ctx.ram.write(0x00a5, 0xA3);
         ctx.cpu.register.set_y(0x01);
-        let mut cpu_bus = CpuBus::new(&mut ctx.ram, &mut ctx.rom, &mut ctx.ppu, &mut ctx.controller);
+        let mut cpu_bus = CpuBus::new(&mut ctx.ram, &mut ctx.rom, &mut ctx.ppu, &mut ctx.controller, &mut ctx.irq);
         ctx.cpu.run_instructions(1, &mut cpu_bus);
         assert_eq!(ctx.cpu.register.get_a(), 0xA0);
     }
@@ -1138,11 +1575,11 @@ mod tests {
     fn test_rol_accumulator() {
         let program: Vec<u8> = vec!(0xA9, 146, 0x2A, 0x2A);
         let mut ctx = create_test_context(&program);
-        let mut cpu_bus = CpuBus::new(&mut ctx.ram, &mut ctx.rom, &mut ctx.ppu, &mut ctx.controller);
+        let mut cpu_bus = CpuBus::new(&mut ctx.ram, &mut ctx.rom, &mut ctx.ppu, &mut ctx.controller, &mut ctx.irq);
         ctx.cpu.run_instructions(2, &mut cpu_bus);
         assert_eq!(ctx.cpu.register.get_a(), 36);
         assert!(ctx.cpu.register.get_flag(StatusFlags::CARRY));
-        let mut cpu_bus = CpuBus::new(&mut ctx.ram, &mut ctx.rom, &mut ctx.ppu, &mut ctx.controller);
+        let mut cpu_bus = CpuBus::new(&mut ctx.ram, &mut ctx.rom, &mut ctx.ppu, &mut ctx.controller, &mut ctx.irq);
         ctx.cpu.run_instructions(1, &mut cpu_bus);
         assert_eq!(ctx.cpu.register.get_a(), 73);
         assert!(!ctx.cpu.register.get_flag(StatusFlags::CARRY));
@@ -1152,11 +1589,11 @@ mod tests {
         let program: Vec<u8> = vec!(0x26, 0x22, 0x26, 0x22);
         let mut ctx = create_test_context(&program);
         ctx.ram.write(0x0022, 146);
-        let mut cpu_bus = CpuBus::new(&mut ctx.ram, &mut ctx.rom, &mut ctx.ppu, &mut ctx.controller);
+        let mut cpu_bus = CpuBus::new(&mut ctx.ram, &mut ctx.rom, &mut ctx.ppu, &mut ctx.controller, &mut ctx.irq);
         ctx.cpu.run_instructions(1, &mut cpu_bus);
         assert_eq!(ctx.ram.peek(0x0022), 36);
         assert!(ctx.cpu.register.get_flag(StatusFlags::CARRY));
-        let mut cpu_bus = CpuBus::new(&mut ctx.ram, &mut ctx.rom, &mut ctx.ppu, &mut ctx.controller);
+        let mut cpu_bus = CpuBus::new(&mut ctx.ram, &mut ctx.rom, &mut ctx.ppu, &mut ctx.controller, &mut ctx.irq);
         ctx.cpu.run_instructions(1, &mut cpu_bus);
         assert_eq!(ctx.ram.peek(0x0022), 73);
         assert!(!ctx.cpu.register.get_flag(StatusFlags::CARRY));
@@ -1165,11 +1602,11 @@ mod tests {
     fn test_ror_accumulator() {
         let program: Vec<u8> = vec!(0xA9, 147, 0x6A, 0x6A);
         let mut ctx = create_test_context(&program);
-        let mut cpu_bus = CpuBus::new(&mut ctx.ram, &mut ctx.rom, &mut ctx.ppu, &mut ctx.controller);
+        let mut cpu_bus = CpuBus::new(&mut ctx.ram, &mut ctx.rom, &mut ctx.ppu, &mut ctx.controller, &mut ctx.irq);
         ctx.cpu.run_instructions(2, &mut cpu_bus);
         assert_eq!(ctx.cpu.register.get_a(), 73);
         assert!(ctx.cpu.register.get_flag(StatusFlags::CARRY));
-        let mut cpu_bus = CpuBus::new(&mut ctx.ram, &mut ctx.rom, &mut ctx.ppu, &mut ctx.controller);
+        let mut cpu_bus = CpuBus::new(&mut ctx.ram, &mut ctx.rom, &mut ctx.ppu, &mut ctx.controller, &mut ctx.irq);
         ctx.cpu.run_instructions(1, &mut cpu_bus);
         assert_eq!(ctx.cpu.register.get_a(), 164);
         assert!(ctx.cpu.register.get_flag(StatusFlags::CARRY));
@@ -1179,22 +1616,22 @@ mod tests {
         let program: Vec<u8> = vec!(0x66, 0x22, 0x66, 0x22);
         let mut ctx = create_test_context(&program);
         ctx.ram.write(0x0022, 147);
-        let mut cpu_bus = CpuBus::new(&mut ctx.ram, &mut ctx.rom, &mut ctx.ppu, &mut ctx.controller);
+        let mut cpu_bus = CpuBus::new(&mut ctx.ram, &mut ctx.rom, &mut ctx.ppu, &mut ctx.controller, &mut ctx.irq);
         ctx.cpu.run_instructions(1, &mut cpu_bus);
         assert_eq!(ctx.ram.peek(0x0022), 73);
         assert!(ctx.cpu.register.get_flag(StatusFlags::CARRY));
-        let mut cpu_bus = CpuBus::new(&mut ctx.ram, &mut ctx.rom, &mut ctx.ppu, &mut ctx.controller);
+        let mut cpu_bus = CpuBus::new(&mut ctx.ram, &mut ctx.rom, &mut ctx.ppu, &mut ctx.controller, &mut ctx.irq);
         ctx.cpu.run_instructions(1, &mut cpu_bus);
         assert_eq!(ctx.ram.peek(0x0022), 164);
         assert!(ctx.cpu.register.get_flag(StatusFlags::CARRY));
     }
     #[test]
     fn test_sbc() {
-        let program:Vec<u8> = vec!(0xe5, 0x05, 0xe9, 0x0b);  // SBC $a5 SBC #$0b
+        let program = assembled("SBC $05\nSBC #$0B");
         let mut ctx = create_test_context(&program);
         ctx.cpu.register.set_a(0x00);
         ctx.ram.write(0x05, 0x01);
-        let mut cpu_bus = CpuBus::new(&mut ctx.ram, &mut ctx.rom, &mut ctx.ppu, &mut ctx.controller);
+        let mut cpu_bus = CpuBus::new(&mut ctx.ram, &mut ctx.rom, &mut ctx.ppu, &mut ctx.controller, &mut ctx.irq);
         ctx.cpu.run_instructions(1, &mut cpu_bus);
         assert_eq!(ctx.cpu.register.get_a(), 0xfe);
         assert!(!ctx.cpu.register.get_flag(StatusFlags::OVERFLOW));
@@ -1212,18 +1649,18 @@ mod tests {
         let program:Vec<u8> = vec!(0x8d, 0xa5, 0x00);  // STA $00a5
         let mut ctx = create_test_context(&program);
         ctx.cpu.register.set_a(0x05);
-        let mut cpu_bus = CpuBus::new(&mut ctx.ram, &mut ctx.rom, &mut ctx.ppu, &mut ctx.controller);
+        let mut cpu_bus = CpuBus::new(&mut ctx.ram, &mut ctx.rom, &mut ctx.ppu, &mut ctx.controller, &mut ctx.irq);
         ctx.cpu.run_instructions(1, &mut cpu_bus);
         assert_eq!(ctx.ram.peek(0xa5u16), 0x05);
     }
     #[test]
     fn test_stx() {
-        let program:Vec<u8> = vec!(0x96, 0xa5, 0x8e, 0xa7, 0x00, 0x86, 0xaa);  // STX $05,Y
+        let program = assembled("STX $A5,Y\nSTX a:$00A7\nSTX $AA");
         let mut ctx = create_test_context(&program);
         ctx.ram.write(0x00a7, 0x00);
         ctx.cpu.register.set_y(0x02);
         ctx.cpu.register.set_x(0xaa);
-        let mut cpu_bus = CpuBus::new(&mut ctx.ram, &mut ctx.rom, &mut ctx.ppu, &mut ctx.controller);
+        let mut cpu_bus = CpuBus::new(&mut ctx.ram, &mut ctx.rom, &mut ctx.ppu, &mut ctx.controller, &mut ctx.irq);
         let res = ctx.cpu.run_instructions(1, &mut cpu_bus);
         assert_eq!(cpu_bus.peek(0x00a7), 0xaa);
         assert_eq!(res.0, 4);
@@ -1236,4 +1673,628 @@ mod tests {
         assert_eq!(cpu_bus.peek(0x00aa), 0x01);
         assert_eq!(res.0, 3);
     }
-}
\ No newline at end of file
+    #[test]
+    fn cycles_should_match_opcode_table() {
+        for i in 0..=0xFFu8 {
+            let opcode = &opcode::OPCODES[i as usize];
+            if opcode.mode == Addressing::Relative || opcode.name == Instruction::JAM {
+                continue;
+            }
+            let mut bus = TestBus::new(&[i], 0x0200);
+            let mut cpu = Cpu::new();
+            cpu.register.set_pc(0x0200);
+            let res = cpu.run_instructions(1, &mut bus);
+            assert_eq!(res.0, opcode.cycle as Cycle, "opcode {:02x?}", i);
+            assert_eq!(bus.accesses.len() as Cycle, res.0, "opcode {:02x?}", i);
+        }
+    }
+    #[test]
+    fn branch_should_take_extra_cycles() {
+        let mut bus = TestBus::new(&[0xD0, 0x7F], 0x02F0); // BNE +$7F
+        let mut cpu = Cpu::new();
+        cpu.register.set_pc(0x02F0);
+        let res = cpu.run_instructions(1, &mut bus);
+        assert_eq!(res.0, 4);
+        assert_eq!(cpu.register.get_pc(), 0x0371);
+        assert_eq!(bus.accesses[3], (0x0271, 0x00, false));
+        let mut bus = TestBus::new(&[0xF0, 0x7F], 0x02F0); // BEQ +$7F
+        let mut cpu = Cpu::new();
+        cpu.register.set_pc(0x02F0);
+        let res = cpu.run_instructions(1, &mut bus);
+        assert_eq!(res.0, 2);
+    }
+    #[test]
+    fn sta_absolute_x_should_dummy_read() {
+        let mut bus = TestBus::new(&[0x9D, 0xF0, 0x20], 0x0200); // STA $20F0,X
+        let mut cpu = Cpu::new();
+        cpu.register.set_pc(0x0200).set_x(0x20).set_a(0x42);
+        cpu.run_instructions(1, &mut bus);
+        assert_eq!(bus.accesses, vec!(
+            (0x0200, 0x9D, false),
+            (0x0201, 0xF0, false),
+            (0x0202, 0x20, false),
+            (0x2010, 0x00, false),
+            (0x2110, 0x42, true),
+        ));
+    }
+    #[test]
+    fn lda_absolute_x_should_dummy_read_on_page_cross_only() {
+        let mut bus = TestBus::new(&[0xBD, 0x10, 0x20, 0xBD, 0xF0, 0x20], 0x0200); // LDA $2010,X LDA $20F0,X
+        let mut cpu = Cpu::new();
+        cpu.register.set_pc(0x0200).set_x(0x20);
+        let res = cpu.run_instructions(1, &mut bus);
+        assert_eq!(res.0, 4);
+        assert_eq!(bus.accesses[3], (0x2030, 0x00, false));
+        let res = cpu.run_instructions(1, &mut bus);
+        assert_eq!(res.0, 5);
+        assert_eq!(bus.accesses[7], (0x2010, 0x00, false));
+        assert_eq!(bus.accesses[8], (0x2110, 0x00, false));
+    }
+    #[test]
+    fn read_modify_write_should_write_twice() {
+        let mut bus = TestBus::new(&[0xEE, 0x00, 0x03], 0x0200); // INC $0300
+        bus.mem[0x0300] = 0x41;
+        let mut cpu = Cpu::new();
+        cpu.register.set_pc(0x0200);
+        cpu.run_instructions(1, &mut bus);
+        assert_eq!(&bus.accesses[3..], &[
+            (0x0300, 0x41, false),
+            (0x0300, 0x41, true),
+            (0x0300, 0x42, true),
+        ]);
+    }
+    #[test]
+    fn jsr_rts_should_return_after_call() {
+        let mut bus = TestBus::new(&[0x20, 0x00, 0x03], 0x0200); // JSR $0300
+        bus.mem[0x0300] = 0x60; // RTS
+        let mut cpu = Cpu::new();
+        cpu.register.set_pc(0x0200);
+        let res = cpu.run_instructions(2, &mut bus);
+        assert_eq!(res.0, 12);
+        assert_eq!(cpu.register.get_pc(), 0x0203);
+        assert_eq!(bus.mem[0x01FF], 0x02);
+        assert_eq!(bus.mem[0x01FE], 0x02);
+    }
+    #[test]
+    fn irq_should_be_ignored_while_interrupt_disabled() {
+        let mut bus = TestBus::new(&[0xEA], 0x0200); // NOP
+        bus.irq_cycle = Some(0);
+        let mut cpu = Cpu::new();
+        cpu.register.set_pc(0x0200).set_flag(StatusFlags::INTERRUPT, true);
+        let res = cpu.run(&mut bus);
+        assert_eq!(res.0, 2);
+        assert_eq!(cpu.register.get_pc(), 0x0201);
+    }
+    #[test]
+    fn irq_should_push_state_and_jump_to_vector() {
+        let mut bus = TestBus::new(&[0xEA], 0x0200); // NOP
+        bus.mem[0xFFFE] = 0x00;
+        bus.mem[0xFFFF] = 0x03;
+        bus.irq_cycle = Some(0);
+        let mut cpu = Cpu::new();
+        cpu.register.set_pc(0x0200);
+        let res = cpu.run(&mut bus);
+        assert_eq!(res.0, 9);
+        assert_eq!(cpu.register.get_pc(), 0x0300);
+        assert_eq!(bus.mem[0x01FF], 0x02);
+        assert_eq!(bus.mem[0x01FE], 0x01);
+        assert_eq!(bus.mem[0x01FD] & 0x30, 0x20);
+        assert!(cpu.register.get_flag(StatusFlags::INTERRUPT));
+    }
+    #[test]
+    fn cli_should_delay_irq_by_one_instruction() {
+        let mut bus = TestBus::new(&[0x58, 0xEA], 0x0200); // CLI, NOP
+        bus.mem[0xFFFF] = 0x03;
+        bus.irq_cycle = Some(0);
+        let mut cpu = Cpu::new();
+        cpu.register.set_pc(0x0200).set_flag(StatusFlags::INTERRUPT, true);
+        cpu.run(&mut bus);
+        assert_eq!(cpu.register.get_pc(), 0x0201);
+        cpu.run(&mut bus);
+        assert_eq!(cpu.register.get_pc(), 0x0300);
+        assert_eq!(bus.mem[0x01FE], 0x02);
+    }
+    #[test]
+    fn sei_should_let_pending_irq_through() {
+        let mut bus = TestBus::new(&[0x78], 0x0200); // SEI
+        bus.mem[0xFFFF] = 0x03;
+        bus.irq_cycle = Some(0);
+        let mut cpu = Cpu::new();
+        cpu.register.set_pc(0x0200);
+        cpu.run(&mut bus);
+        assert_eq!(cpu.register.get_pc(), 0x0300);
+        // The pushed status already has I set
+        assert_eq!(bus.mem[0x01FD] & 0x04, 0x04);
+    }
+    #[test]
+    fn nmi_should_jump_to_nmi_vector() {
+        let mut bus = TestBus::new(&[0xEA], 0x0200); // NOP
+        bus.mem[0xFFFA] = 0x00;
+        bus.mem[0xFFFB] = 0x04;
+        bus.nmi_cycle = Some(1);
+        let mut cpu = Cpu::new();
+        cpu.register.set_pc(0x0200).set_flag(StatusFlags::INTERRUPT, true);
+        let res = cpu.run(&mut bus);
+        assert_eq!(res.0, 9);
+        assert_eq!(cpu.register.get_pc(), 0x0400);
+    }
+    #[test]
+    fn brk_should_be_hijacked_by_nmi() {
+        let mut bus = TestBus::new(&[0x00, 0x00], 0x0200); // BRK
+        bus.mem[0xFFFB] = 0x04;
+        bus.mem[0xFFFF] = 0x03;
+        bus.nmi_cycle = Some(3);
+        let mut cpu = Cpu::new();
+        cpu.register.set_pc(0x0200);
+        let res = cpu.run(&mut bus);
+        assert_eq!(res.0, 7);
+        assert_eq!(cpu.register.get_pc(), 0x0400);
+        // B flag is still pushed for the hijacked BRK
+        assert_eq!(bus.mem[0x01FD] & 0x10, 0x10);
+        assert_eq!(bus.mem[0x01FE], 0x02);
+    }
+    #[test]
+    fn taken_branch_should_delay_irq() {
+        let mut bus = TestBus::new(&[0xD0, 0x00, 0xEA], 0x0200); // BNE +0, NOP
+        bus.mem[0xFFFF] = 0x03;
+        // IRQ raised during the operand fetch of the branch
+        bus.irq_cycle = Some(2);
+        let mut cpu = Cpu::new();
+        cpu.register.set_pc(0x0200);
+        let res = cpu.run(&mut bus);
+        assert_eq!(res.0, 3);
+        assert_eq!(cpu.register.get_pc(), 0x0202);
+        cpu.run(&mut bus);
+        assert_eq!(cpu.register.get_pc(), 0x0300);
+    }
+    // Program at $0200, OAM DMA source page at $0300 and DMC sample at $C000
+    fn dma_bus(source: &str) -> TestBus {
+        let mut bus = TestBus::new(&assembled(&format!(".org $0200\n{}", source)), 0x0200);
+        for i in 0..0x100 {
+            bus.mem[0x0300 + i] = i as u8 ^ 0x5A;
+        }
+        bus.mem[0x0003] = 0x03;
+        bus.mem[0xC000] = 0x77;
+        bus
+    }
+    #[test]
+    fn oam_dma_should_take_513_or_514_cycles() {
+        // The halt lands on a get cycle after 6 cycles, on a put cycle after 7
+        for (source, dma_cycles) in [("LDA #$03\nSTA $4014\nNOP", 514), ("LDA $03\nSTA $4014\nNOP", 513)].iter() {
+            let mut bus = dma_bus(source);
+            let mut cpu = Cpu::new();
+            cpu.register.set_pc(0x0200);
+            cpu.run_instructions(2, &mut bus);
+            assert_eq!(cpu.run(&mut bus).0, 2 + dma_cycles);
+            let oam: Vec<u8> = bus.accesses.iter().filter(|(addr, _, write)| *write && *addr == 0x2004).map(|a| a.1).collect();
+            assert_eq!(oam, bus.mem[0x0300..0x0400].to_vec());
+        }
+    }
+    #[test]
+    fn dmc_dma_should_steal_cycles_and_repeat_the_halted_read() {
+        let mut bus = dma_bus("NOP");
+        bus.dmc_cycle = Some(0);
+        let mut cpu = Cpu::new();
+        cpu.register.set_pc(0x0200);
+        // Halt on a get cycle, dummy on the put cycle then the sample read
+        assert_eq!(cpu.run(&mut bus).0, 2 + 3);
+        assert_eq!(bus.dmc_sample, Some(0x77));
+        let reads: Vec<u16> = bus.accesses.iter().map(|a| a.0).collect();
+        assert_eq!(reads[..4].to_vec(), vec![0x0200, 0x0200, 0xC000, 0x0200]);
+    }
+    #[test]
+    fn dmc_dma_during_oam_dma_should_take_two_cycles() {
+        let mut bus = dma_bus("LDA #$03\nSTA $4014\nNOP");
+        bus.dmc_cycle = Some(100);
+        let mut cpu = Cpu::new();
+        cpu.register.set_pc(0x0200);
+        cpu.run_instructions(2, &mut bus);
+        assert_eq!(cpu.run(&mut bus).0, 2 + 514 + 2);
+        assert_eq!(bus.dmc_sample, Some(0x77));
+        let oam: Vec<u8> = bus.accesses.iter().filter(|(addr, _, write)| *write && *addr == 0x2004).map(|a| a.1).collect();
+        assert_eq!(oam, bus.mem[0x0300..0x0400].to_vec());
+    }
+    #[test]
+    fn dmc_dma_should_read_the_controller_port_twice() {
+        // The halt read and the resumed read clock the controller, the dummy reads do not
+        let mut bus = dma_bus("NOP\nLDA $4016");
+        let mut cpu = Cpu::new();
+        cpu.register.set_pc(0x0200);
+        cpu.run(&mut bus);
+        // Requested during the operand fetch, the CPU halts on the read of $4016
+        bus.dmc_cycle = Some(bus.cycle + 3);
+        cpu.run(&mut bus);
+        assert_eq!(bus.accesses.iter().filter(|a| a.0 == 0x4016).count(), 2);
+        assert_eq!(bus.dmc_sample, Some(0x77));
+    }
+    #[test]
+    fn oam_dma_should_copy_any_page() {
+        let program = assembled(".org $8000\nLDA #$80\nSTA $4014\nNOP");
+        let mut ctx = create_test_context(&program);
+        let mut cpu_bus = CpuBus::new(&mut ctx.ram, &mut ctx.rom, &mut ctx.ppu, &mut ctx.controller, &mut ctx.irq);
+        ctx.cpu.register.set_pc(0x8000);
+        ctx.cpu.run_instructions(3, &mut cpu_bus);
+        assert_eq!(ctx.ppu.mem.spr_mem.get_oam()[..5].to_vec(), program[..5].to_vec());
+    }
+    #[test]
+    fn oam_dma_should_survive_the_bus_of_its_instruction() {
+        // A bus is created for every instruction, as the emulator loop does
+        let program = assembled(".org $8000\nLDA #$80\nSTA $4014\nNOP");
+        let mut ctx = create_test_context(&program);
+        ctx.cpu.register.set_pc(0x8000);
+        let mut cycles = Vec::new();
+        for _ in 0..3 {
+            let mut cpu_bus = CpuBus::new(&mut ctx.ram, &mut ctx.rom, &mut ctx.ppu, &mut ctx.controller, &mut ctx.irq);
+            cycles.push(ctx.cpu.run(&mut cpu_bus).0);
+        }
+        assert_eq!(cycles, vec![2, 4, 2 + 514]);
+        assert_eq!(ctx.ppu.mem.spr_mem.get_oam()[..5].to_vec(), program[..5].to_vec());
+    }
+    #[test]
+    fn run_should_not_touch_zero_page() {
+        let mut bus = TestBus::new(&[0xEA, 0x00], 0x0200); // NOP, BRK
+        let mut cpu = Cpu::new();
+        cpu.register.set_pc(0x0200);
+        assert!(cpu.run(&mut bus).1 == EmulationStatus::PROCESSING);
+        assert!(cpu.run(&mut bus).1 == EmulationStatus::PROCESSING);
+        assert!(bus.mem[0x0000..0x0100].iter().all(|&b| b == 0));
+    }
+    #[test]
+    fn lxa_should_use_the_magic_constant() {
+        let mut bus = TestBus::new(&[0xAB, 0xF0], 0x0200); // LXA #$F0
+        let mut cpu = Cpu::new();
+        cpu.register.set_pc(0x0200).set_a(0x01);
+        cpu.run(&mut bus);
+        assert_eq!(cpu.register.get_a(), 0xE0);
+        assert_eq!(cpu.register.get_x(), 0xE0);
+    }
+    fn decimal_adc(variant: Variant, a: u8, m: u8, carry: bool) -> Cpu {
+        let mut bus = TestBus::new(&[0xF8, 0x69, m], 0x0200); // SED, ADC #m
+        let mut cpu = Cpu::with_variant(variant);
+        cpu.register.set_pc(0x0200).set_a(a).set_flag(StatusFlags::CARRY, carry);
+        cpu.run_instructions(2, &mut bus);
+        cpu
+    }
+    fn decimal_sbc(variant: Variant, a: u8, m: u8, carry: bool) -> Cpu {
+        let mut bus = TestBus::new(&[0xF8, 0xE9, m], 0x0200); // SED, SBC #m
+        let mut cpu = Cpu::with_variant(variant);
+        cpu.register.set_pc(0x0200).set_a(a).set_flag(StatusFlags::CARRY, carry);
+        cpu.run_instructions(2, &mut bus);
+        cpu
+    }
+    #[test]
+    fn ricoh_2a03_should_ignore_decimal_flag() {
+        let cpu = decimal_adc(Variant::Ricoh2A03, 0x09, 0x01, false);
+        assert_eq!(cpu.register.get_a(), 0x0A);
+        assert!(cpu.register.get_flag(StatusFlags::DECIMAL));
+    }
+    #[test]
+    fn nmos_adc_decimal() {
+        let cpu = decimal_adc(Variant::Nmos6502, 0x58, 0x46, true);
+        assert_eq!(cpu.register.get_a(), 0x05);
+        assert!(cpu.register.get_flag(StatusFlags::CARRY));
+        // Z comes from the binary sum, N from the intermediate result
+        let cpu = decimal_adc(Variant::Nmos6502, 0x99, 0x01, false);
+        assert_eq!(cpu.register.get_a(), 0x00);
+        assert!(cpu.register.get_flag(StatusFlags::CARRY));
+        assert!(!cpu.register.get_flag(StatusFlags::ZERO));
+        assert!(cpu.register.get_flag(StatusFlags::NEGATIVE));
+    }
+    #[test]
+    fn nmos_sbc_decimal() {
+        let cpu = decimal_sbc(Variant::Nmos6502, 0x46, 0x12, true);
+        assert_eq!(cpu.register.get_a(), 0x34);
+        assert!(cpu.register.get_flag(StatusFlags::CARRY));
+        let cpu = decimal_sbc(Variant::Nmos6502, 0x12, 0x21, true);
+        assert_eq!(cpu.register.get_a(), 0x91);
+        assert!(!cpu.register.get_flag(StatusFlags::CARRY));
+        assert!(cpu.register.get_flag(StatusFlags::NEGATIVE));
+    }
+    #[test]
+    fn cmos_decimal_should_set_valid_flags_and_take_one_more_cycle() {
+        let mut bus = TestBus::new(&[0x69, 0x01], 0x0200); // ADC #$01
+        let mut cpu = Cpu::with_variant(Variant::Wdc65C02);
+        cpu.register.set_pc(0x0200).set_a(0x99).set_flag(StatusFlags::DECIMAL, true);
+        let res = cpu.run_instructions(1, &mut bus);
+        assert_eq!(res.0, 3);
+        assert_eq!(cpu.register.get_a(), 0x00);
+        assert!(cpu.register.get_flag(StatusFlags::ZERO));
+        assert!(!cpu.register.get_flag(StatusFlags::NEGATIVE));
+        let cpu = decimal_sbc(Variant::Wdc65C02, 0x12, 0x21, true);
+        assert_eq!(cpu.register.get_a(), 0x91);
+        assert!(!cpu.register.get_flag(StatusFlags::CARRY));
+    }
+    #[test]
+    fn cycles_should_match_65c02_opcode_table() {
+        for i in 0..=0xFFu8 {
+            let opcode = &opcode::OPCODES_65C02[i as usize];
+            match opcode.name {
+                Instruction::STP | Instruction::WAI => continue,
+                _ if opcode.mode == Addressing::Relative || opcode.mode == Addressing::ZeroPageRelative => continue,
+                _ => {}
+            }
+            let mut bus = TestBus::new(&[i], 0x0200);
+            let mut cpu = Cpu::with_variant(Variant::Wdc65C02);
+            cpu.register.set_pc(0x0200);
+            let res = cpu.run_instructions(1, &mut bus);
+            assert_eq!(res.0, opcode.cycle as Cycle, "opcode {:02x?}", i);
+            assert_eq!(bus.accesses.len() as Cycle, res.0, "opcode {:02x?}", i);
+        }
+    }
+    fn cmos_bus(source: &str) -> TestBus {
+        let program = asm::assemble(Variant::Wdc65C02, &format!(".org $0200\n{}", source)).unwrap();
+        TestBus::new(&program.bytes, program.origin)
+    }
+    #[test]
+    fn cmos_stack_and_store_zero() {
+        let mut bus = cmos_bus("PHX\nPLY\nSTZ $10\nBRA skip\nNOP\nskip: INC A");
+        bus.mem[0x0010] = 0xAA;
+        let mut cpu = Cpu::with_variant(Variant::Wdc65C02);
+        cpu.register.set_pc(0x0200).set_x(0x42);
+        cpu.run_instructions(5, &mut bus);
+        assert_eq!(cpu.register.get_y(), 0x42);
+        assert_eq!(bus.mem[0x0010], 0x00);
+        assert_eq!(cpu.register.get_pc(), 0x0208);
+        assert_eq!(cpu.register.get_a(), 0x01);
+    }
+    #[test]
+    fn cmos_test_and_set_reset_bits() {
+        let mut bus = cmos_bus("TSB $10\nTRB $11");
+        bus.mem[0x0010] = 0x0C;
+        bus.mem[0x0011] = 0x0F;
+        let mut cpu = Cpu::with_variant(Variant::Wdc65C02);
+        cpu.register.set_pc(0x0200).set_a(0x03);
+        cpu.run_instructions(1, &mut bus);
+        assert_eq!(bus.mem[0x0010], 0x0F);
+        assert!(cpu.register.get_flag(StatusFlags::ZERO));
+        cpu.run_instructions(1, &mut bus);
+        assert_eq!(bus.mem[0x0011], 0x0C);
+        assert!(!cpu.register.get_flag(StatusFlags::ZERO));
+    }
+    #[test]
+    fn cmos_zero_page_indirect() {
+        let mut bus = cmos_bus("LDA ($10)\nSTA ($12)");
+        bus.mem[0x0010] = 0x00;
+        bus.mem[0x0011] = 0x03;
+        bus.mem[0x0012] = 0x00;
+        bus.mem[0x0013] = 0x04;
+        bus.mem[0x0300] = 0x5A;
+        let mut cpu = Cpu::with_variant(Variant::Wdc65C02);
+        cpu.register.set_pc(0x0200);
+        cpu.run_instructions(2, &mut bus);
+        assert_eq!(bus.mem[0x0400], 0x5A);
+    }
+    #[test]
+    fn jmp_indirect_page_bug_only_on_nmos() {
+        let mut program = vec![0x6C, 0xFF, 0x02]; // JMP ($02FF)
+        program.resize(0x100, 0);
+        program[0xFF] = 0x00;
+        let mut bus = TestBus::new(&program, 0x0200);
+        bus.mem[0x0300] = 0x04;
+        let mut cpu = Cpu::with_variant(Variant::Nmos6502);
+        cpu.register.set_pc(0x0200);
+        cpu.run_instructions(1, &mut bus);
+        assert_eq!(cpu.register.get_pc(), 0x6C00);
+        let mut cpu = Cpu::with_variant(Variant::Wdc65C02);
+        cpu.register.set_pc(0x0200);
+        cpu.run_instructions(1, &mut bus);
+        assert_eq!(cpu.register.get_pc(), 0x0400);
+    }
+    #[test]
+    fn cmos_jmp_indexed_indirect() {
+        let mut bus = cmos_bus("JMP ($0300,X)");
+        bus.mem[0x0304] = 0x34;
+        bus.mem[0x0305] = 0x12;
+        let mut cpu = Cpu::with_variant(Variant::Wdc65C02);
+        cpu.register.set_pc(0x0200).set_x(0x04);
+        cpu.run_instructions(1, &mut bus);
+        assert_eq!(cpu.register.get_pc(), 0x1234);
+    }
+    #[test]
+    fn cmos_bit_branch_and_modify() {
+        let mut bus = cmos_bus("SMB3 $10\nBBS3 $10,set\nNOP\nNOP\nset: RMB3 $10\nloop: BBR3 $10,loop");
+        let mut cpu = Cpu::with_variant(Variant::Wdc65C02);
+        cpu.register.set_pc(0x0200);
+        cpu.run_instructions(2, &mut bus);
+        assert_eq!(bus.mem[0x0010], 0x08);
+        assert_eq!(cpu.register.get_pc(), 0x0207);
+        cpu.run_instructions(2, &mut bus);
+        assert_eq!(bus.mem[0x0010], 0x00);
+        assert_eq!(cpu.register.get_pc(), 0x0209);
+    }
+    #[test]
+    fn cmos_bit_immediate_should_only_set_zero() {
+        let mut bus = TestBus::new(&[0x89, 0xC0], 0x0200); // BIT #$C0
+        let mut cpu = Cpu::with_variant(Variant::Wdc65C02);
+        cpu.register.set_pc(0x0200).set_a(0x01);
+        cpu.run_instructions(1, &mut bus);
+        assert!(cpu.register.get_flag(StatusFlags::ZERO));
+        assert!(!cpu.register.get_flag(StatusFlags::NEGATIVE));
+        assert!(!cpu.register.get_flag(StatusFlags::OVERFLOW));
+    }
+    #[test]
+    fn cmos_interrupt_should_clear_decimal() {
+        let mut bus = TestBus::new(&[0x00, 0x00], 0x0200); // BRK
+        let mut cpu = Cpu::with_variant(Variant::Wdc65C02);
+        cpu.register.set_pc(0x0200).set_flag(StatusFlags::DECIMAL, true);
+        cpu.run(&mut bus);
+        assert!(!cpu.register.get_flag(StatusFlags::DECIMAL));
+        assert_eq!(bus.mem[0x01FD] & 0x08, 0x08);
+    }
+    #[test]
+    fn wai_should_resume_on_masked_irq() {
+        let mut bus = TestBus::new(&[0xCB, 0xEA], 0x0200); // WAI, NOP
+        let mut cpu = Cpu::with_variant(Variant::Wdc65C02);
+        cpu.register.set_pc(0x0200).set_flag(StatusFlags::INTERRUPT, true);
+        cpu.run_instructions(5, &mut bus);
+        assert_eq!(cpu.register.get_pc(), 0x0201);
+        bus.irq_cycle = Some(0);
+        cpu.run_instructions(2, &mut bus);
+        assert_eq!(cpu.register.get_pc(), 0x0202);
+    }
+    #[test]
+    fn stp_should_halt_cpu() {
+        let mut bus = TestBus::new(&[0xDB], 0x0200); // STP
+        let mut cpu = Cpu::with_variant(Variant::Wdc65C02);
+        cpu.register.set_pc(0x0200);
+        assert!(cpu.run(&mut bus).1 == EmulationStatus::JAMMED);
+        assert!(cpu.run(&mut bus).1 == EmulationStatus::JAMMED);
+    }
+    #[test]
+    fn every_opcode_is_decoded() {
+        assert_eq!(opcode::OPCODES.len(), 256);
+        let jams: Vec<u8> = (0..=0xFFu8).filter(|&i| opcode::OPCODES[i as usize].name == Instruction::JAM).collect();
+        assert_eq!(jams, vec![0x02, 0x12, 0x22, 0x32, 0x42, 0x52, 0x62, 0x72, 0x92, 0xB2, 0xD2, 0xF2]);
+    }
+    #[test]
+    fn test_anc() {
+        let program = assembled("ANC #$81");
+        let mut ctx = create_test_context(&program);
+        ctx.cpu.register.set_a(0xf0);
+        let mut cpu_bus = CpuBus::new(&mut ctx.ram, &mut ctx.rom, &mut ctx.ppu, &mut ctx.controller, &mut ctx.irq);
+        ctx.cpu.run_instructions(1, &mut cpu_bus);
+        assert_eq!(ctx.cpu.register.get_a(), 0x80);
+        assert!(ctx.cpu.register.get_flag(StatusFlags::CARRY));
+        assert!(ctx.cpu.register.get_flag(StatusFlags::NEGATIVE));
+    }
+    #[test]
+    fn test_alr() {
+        let program = assembled("ALR #$0F");
+        let mut ctx = create_test_context(&program);
+        ctx.cpu.register.set_a(0xff);
+        let mut cpu_bus = CpuBus::new(&mut ctx.ram, &mut ctx.rom, &mut ctx.ppu, &mut ctx.controller, &mut ctx.irq);
+        ctx.cpu.run_instructions(1, &mut cpu_bus);
+        assert_eq!(ctx.cpu.register.get_a(), 0x07);
+        assert!(ctx.cpu.register.get_flag(StatusFlags::CARRY));
+    }
+    #[test]
+    fn test_arr() {
+        let program = assembled("ARR #$FF");
+        let mut ctx = create_test_context(&program);
+        ctx.cpu.register.set_a(0xc0);
+        ctx.cpu.register.set_flag(StatusFlags::CARRY, true);
+        let mut cpu_bus = CpuBus::new(&mut ctx.ram, &mut ctx.rom, &mut ctx.ppu, &mut ctx.controller, &mut ctx.irq);
+        ctx.cpu.run_instructions(1, &mut cpu_bus);
+        assert_eq!(ctx.cpu.register.get_a(), 0xe0);
+        assert!(ctx.cpu.register.get_flag(StatusFlags::CARRY));
+        assert!(!ctx.cpu.register.get_flag(StatusFlags::OVERFLOW));
+        assert!(ctx.cpu.register.get_flag(StatusFlags::NEGATIVE));
+    }
+    #[test]
+    fn test_axs() {
+        let program = assembled("AXS #$02");
+        let mut ctx = create_test_context(&program);
+        ctx.cpu.register.set_a(0x0f);
+        ctx.cpu.register.set_x(0x05);
+        let mut cpu_bus = CpuBus::new(&mut ctx.ram, &mut ctx.rom, &mut ctx.ppu, &mut ctx.controller, &mut ctx.irq);
+        ctx.cpu.run_instructions(1, &mut cpu_bus);
+        assert_eq!(ctx.cpu.register.get_x(), 0x03);
+        assert!(ctx.cpu.register.get_flag(StatusFlags::CARRY));
+    }
+    #[test]
+    fn test_las() {
+        let program:Vec<u8> = vec!(0xbb, 0x10, 0x00);  // LAS $0010,Y
+        let mut ctx = create_test_context(&program);
+        ctx.ram.write(0x0012, 0x3c);
+        ctx.cpu.register.set_y(0x02);
+        let mut cpu_bus = CpuBus::new(&mut ctx.ram, &mut ctx.rom, &mut ctx.ppu, &mut ctx.controller, &mut ctx.irq);
+        ctx.cpu.run_instructions(1, &mut cpu_bus);
+        assert_eq!(ctx.cpu.register.get_a(), 0x3c);
+        assert_eq!(ctx.cpu.register.get_x(), 0x3c);
+        assert_eq!(ctx.cpu.register.get_sp(), 0x3c);
+    }
+    #[test]
+    fn test_shx_page_cross() {
+        let program = assembled("SHX $00FF,Y\nSHX $0010,Y");
+        let mut ctx = create_test_context(&program);
+        ctx.cpu.register.set_x(0xff);
+        ctx.cpu.register.set_y(0x02);
+        let mut cpu_bus = CpuBus::new(&mut ctx.ram, &mut ctx.rom, &mut ctx.ppu, &mut ctx.controller, &mut ctx.irq);
+        ctx.cpu.run_instructions(1, &mut cpu_bus);
+        assert_eq!(cpu_bus.peek(0x0101), 0x01);
+        ctx.cpu.run_instructions(1, &mut cpu_bus);
+        assert_eq!(cpu_bus.peek(0x0012), 0x01);
+    }
+    #[test]
+    fn jam_should_halt_cpu() {
+        let program = assembled("JAM\nLDA #$FF");
+        let mut ctx = create_test_context(&program);
+        let mut cpu_bus = CpuBus::new(&mut ctx.ram, &mut ctx.rom, &mut ctx.ppu, &mut ctx.controller, &mut ctx.irq);
+        let res = ctx.cpu.run(&mut cpu_bus);
+        assert!(res.1 == EmulationStatus::JAMMED);
+        let res = ctx.cpu.run(&mut cpu_bus);
+        assert!(res.1 == EmulationStatus::JAMMED);
+        assert_eq!(ctx.cpu.register.get_a(), 0x00);
+        assert_eq!(ctx.cpu.register.get_pc(), 0x8000);
+    }
+    // cargo test --release bench_instructions_per_second -- --ignored --nocapture
+    #[test]
+    #[ignore]
+    fn bench_instructions_per_second() {
+        struct FlatBus {
+            mem: Vec<u8>,
+        }
+        impl Bus for FlatBus {
+            fn peek(&mut self, i: u16) -> u8 {
+                self.mem[i as usize]
+            }
+            fn write(&mut self, i: u16, v: u8) -> u8 {
+                self.mem[i as usize] = v;
+                v
+            }
+        }
+        // loop: LDA $0300,X / ADC #$01 / STA $0300,X / ASL $10 / INX / BNE loop / JMP loop
+        let program = [0xBD, 0x00, 0x03, 0x69, 0x01, 0x9D, 0x00, 0x03, 0x06, 0x10, 0xE8, 0xD0, 0xF3, 0x4C, 0x00, 0x02];
+        let mut mem = vec![0u8; 0x10000];
+        mem[0x0200..0x0200 + program.len()].copy_from_slice(&program);
+        let mut bus = FlatBus { mem };
+        let mut cpu = Cpu::new();
+        cpu.register.set_pc(0x0200);
+        let instructions = 20_000_000;
+        let start = std::time::Instant::now();
+        let mut cycles: Cycle = 0;
+        for _ in 0..instructions {
+            cycles += cpu.run(&mut bus).0;
+        }
+        let elapsed = start.elapsed().as_secs_f64();
+        println!(
+            "{} instructions ({} cycles) in {:.3}s : {:.1} M instructions/s",
+            instructions,
+            cycles,
+            elapsed,
+            instructions as f64 / elapsed / 1_000_000.0
+        );
+    }
+    // Headless frames of nestest.nes on the NES bus:
+    // cargo test --release bench_frames_per_second -- --ignored --nocapture
+    #[test]
+    #[ignore]
+    fn bench_frames_per_second() {
+        let mut rom = Cartbridge::new();
+        let data = rom.read_file(String::from("roms/nestest.nes")).unwrap();
+        rom.load_program(&data).unwrap();
+        let mut ctx = TestContext {
+            cpu: Cpu::new(),
+            ram: Ram::new(),
+            ppu: Ppu::new(),
+            rom,
+            controller: Controller::new(),
+            irq: IrqLine::new(),
+        };
+        let mut cpu_bus = CpuBus::new(&mut ctx.ram, &mut ctx.rom, &mut ctx.ppu, &mut ctx.controller, &mut ctx.irq);
+        ctx.cpu.power_on(&mut cpu_bus);
+        let frames = 600;
+        let start = std::time::Instant::now();
+        let mut done = 0;
+        while done < frames {
+            // One bus per instruction, as the emulator loop does
+            let mut cpu_bus = CpuBus::new(&mut ctx.ram, &mut ctx.rom, &mut ctx.ppu, &mut ctx.controller, &mut ctx.irq);
+            ctx.cpu.run(&mut cpu_bus);
+            if cpu_bus.is_frame_complete() {
+                done += 1;
+            }
+        }
+        let elapsed = start.elapsed().as_secs_f64();
+        println!("{} frames in {:.3}s : {:.1} frames/s", frames, elapsed, frames as f64 / elapsed);
+    }
+}
@@ -11,6 +11,7 @@ pub enum StatusFlags {
     NEGATIVE,
 }
 
+#[derive(Hash)]
 pub struct Register {
     r_a: u8,
     r_x: u8,
@@ -44,8 +45,8 @@ pub trait CpuRegister {
     fn incr_sp(&mut self) -> &mut Self;
     fn decr_sp(&mut self) -> &mut Self;
 
+    #[allow(dead_code)]
     fn push_stack<B: Bus>(&mut self, v: u8, bus: &mut B) -> &mut Self;
-    fn pop_stack<B: Bus>(&mut self, bus: &mut B) -> u8;
 }
 
 impl Register {
@@ -130,19 +131,19 @@ impl CpuRegister for Register {
         }
     }
     fn incr_pc(&mut self) -> &mut Self {
-        self.r_pc += 1;
+        self.r_pc = self.r_pc.wrapping_add(1);
         self
     }
     fn decr_pc(&mut self) -> &mut Self {
-        self.r_pc -= 1;
+        self.r_pc = self.r_pc.wrapping_sub(1);
         self
     }
     fn incr_sp(&mut self) -> &mut Self {
-        self.r_sp += 1;
+        self.r_sp = self.r_sp.wrapping_add(1);
         self
     }
     fn decr_sp(&mut self) -> &mut Self {
-        self.r_sp -= 1;
+        self.r_sp = self.r_sp.wrapping_sub(1);
         self
     }
     fn push_stack<B: Bus>(&mut self, v: u8, bus: &mut B) -> &mut Self {
@@ -150,9 +151,4 @@ impl CpuRegister for Register {
         self.decr_sp();
         self
     }
-    fn pop_stack<B: Bus>(&mut self, bus: &mut B) -> u8 {
-        self.incr_sp();
-        let val = bus.peek(0x0100 | self.r_sp as u16);
-        val
-    }
 }
\ No newline at end of file
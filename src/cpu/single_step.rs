@@ -0,0 +1,255 @@
+// Runner for the SingleStepTests (Tom Harte) per-opcode test vectors:
+// https://github.com/SingleStepTests/65x02
+//
+// Each <opcode>.json file holds cases made of an initial state, the final state after
+// one instruction and the bus access of every cycle:
+// { "name": "a9 7b 1d", "initial": { "pc": 4660, "s": 253, "a": 0, "x": 0, "y": 0, "p": 36,
+//   "ram": [[4660, 169], [4661, 123]] }, "final": { ... }, "cycles": [[4660, 169, "read"], ...] }
+//
+// The vectors are not part of the repository, point SINGLE_STEP_TESTS to one of the
+// directories (nes6502/v1, 6502/v1, wdc65c02/v1) and run:
+// cargo test single_step_tests -- --ignored --nocapture
+use crate::cpu::bus::Bus;
+use crate::cpu::register::CpuRegister;
+use crate::cpu::{Cpu, Variant};
+
+use serde_json::Value;
+
+use std::fs;
+use std::path::Path;
+
+// B and the unused bit only exist on the stack
+const STATUS_MASK: u8 = 0xCF;
+
+struct RecordingBus {
+    mem: Vec<u8>,
+    accesses: Vec<(u16, u8, bool)>,
+    ticked: bool,
+}
+
+impl RecordingBus {
+    fn new() -> RecordingBus {
+        RecordingBus {
+            mem: vec![0u8; 0x10000],
+            accesses: Vec::new(),
+            ticked: false,
+        }
+    }
+    fn record(&mut self, i: u16, v: u8, write: bool) {
+        if self.ticked {
+            self.accesses.push((i, v, write));
+            self.ticked = false;
+        }
+    }
+}
+
+impl Bus for RecordingBus {
+    fn peek(&mut self, i: u16) -> u8 {
+        let v = self.mem[i as usize];
+        self.record(i, v, false);
+        v
+    }
+    fn write(&mut self, i: u16, v: u8) -> u8 {
+        self.mem[i as usize] = v;
+        self.record(i, v, true);
+        v
+    }
+    fn tick(&mut self) {
+        self.ticked = true;
+    }
+}
+
+#[derive(Debug, Default)]
+pub struct Report {
+    pub cases: usize,
+    pub registers: usize,
+    pub memory: usize,
+    pub bus: usize,
+    pub failed: usize,
+    pub first_failure: Option<String>,
+}
+
+fn number(value: &Value, key: &str) -> Result<u64, String> {
+    value[key].as_u64().ok_or(format!("missing or invalid '{}'", key))
+}
+
+fn pairs(value: &Value) -> Result<Vec<(u16, u8)>, String> {
+    let ram = value["ram"].as_array().ok_or("missing 'ram'")?;
+    ram.iter()
+        .map(|entry| match (entry[0].as_u64(), entry[1].as_u64()) {
+            (Some(addr), Some(v)) => Ok((addr as u16, v as u8)),
+            _ => Err(format!("invalid ram entry {}", entry)),
+        })
+        .collect()
+}
+
+// PC, S, A, X, Y, P
+fn registers(value: &Value) -> Result<[u16; 6], String> {
+    Ok([
+        number(value, "pc")? as u16,
+        number(value, "s")? as u16,
+        number(value, "a")? as u16,
+        number(value, "x")? as u16,
+        number(value, "y")? as u16,
+        number(value, "p")? as u16 & STATUS_MASK as u16,
+    ])
+}
+
+// Runs one case, returns the mismatches as (kind, description)
+pub fn run_case(variant: Variant, case: &Value) -> Result<Vec<(&'static str, String)>, String> {
+    let initial = &case["initial"];
+    let expected = &case["final"];
+    let mut bus = RecordingBus::new();
+    for (addr, v) in pairs(initial)? {
+        bus.mem[addr as usize] = v;
+    }
+    let [pc, s, a, x, y, _] = registers(initial)?;
+    let mut cpu = Cpu::with_variant(variant);
+    cpu.register
+        .set_pc(pc)
+        .set_sp(s as u8)
+        .set_a(a as u8)
+        .set_x(x as u8)
+        .set_y(y as u8)
+        .set_sr(number(initial, "p")? as u8);
+    cpu.run_instructions(1, &mut bus);
+
+    let mut mismatches = Vec::new();
+    let r = &cpu.register;
+    let actual = [r.get_pc(), r.get_sp() as u16, r.get_a() as u16, r.get_x() as u16, r.get_y() as u16, (r.get_sr() & STATUS_MASK) as u16];
+    let expected_registers = registers(expected)?;
+    for (i, name) in ["PC", "S", "A", "X", "Y", "P"].iter().enumerate() {
+        if actual[i] != expected_registers[i] {
+            mismatches.push(("registers", format!("{}: expected {:02X}, got {:02X}", name, expected_registers[i], actual[i])));
+        }
+    }
+    for (addr, v) in pairs(expected)? {
+        if bus.mem[addr as usize] != v {
+            mismatches.push(("memory", format!("${:04X}: expected {:02X}, got {:02X}", addr, v, bus.mem[addr as usize])));
+        }
+    }
+    let cycles = case["cycles"].as_array().ok_or("missing 'cycles'")?;
+    let expected_accesses: Vec<(u16, u8, bool)> = cycles
+        .iter()
+        .map(|c| (c[0].as_u64().unwrap_or(0) as u16, c[1].as_u64().unwrap_or(0) as u8, c[2] == "write"))
+        .collect();
+    if expected_accesses != bus.accesses {
+        let format = |accesses: &[(u16, u8, bool)]| {
+            let cycles: Vec<String> = accesses
+                .iter()
+                .map(|(addr, v, write)| format!("{:04X}{}{:02X}", addr, if *write { '<' } else { '>' }, v))
+                .collect();
+            cycles.join(" ")
+        };
+        mismatches.push(("bus", format!("expected {}, got {}", format(&expected_accesses), format(&bus.accesses))));
+    }
+    Ok(mismatches)
+}
+
+pub fn run_file(variant: Variant, path: &Path) -> Result<Report, String> {
+    let text = fs::read_to_string(path).map_err(|e| format!("Cannot open {}: {}", path.display(), e))?;
+    let cases: Value = serde_json::from_str(&text).map_err(|e| format!("Invalid JSON in {}: {}", path.display(), e))?;
+    let cases = cases.as_array().ok_or(format!("{} is not an array of cases", path.display()))?;
+    let mut report = Report::default();
+    for case in cases {
+        report.cases += 1;
+        let mismatches = run_case(variant, case)?;
+        if mismatches.is_empty() {
+            continue;
+        }
+        report.failed += 1;
+        for (kind, _) in &mismatches {
+            match *kind {
+                "registers" => report.registers += 1,
+                "memory" => report.memory += 1,
+                _ => report.bus += 1,
+            }
+        }
+        if report.first_failure.is_none() {
+            let details: Vec<String> = mismatches.iter().map(|(_, m)| m.clone()).collect();
+            report.first_failure = Some(format!("{}: {}", case["name"].as_str().unwrap_or("?"), details.join("; ")));
+        }
+    }
+    Ok(report)
+}
+
+// The sets are named after the CPU they were recorded on
+fn variant_of(dir: &Path) -> Variant {
+    let name = dir.to_string_lossy().to_lowercase();
+    if name.contains("65c02") {
+        Variant::Wdc65C02
+    } else if name.contains("nes6502") {
+        Variant::Ricoh2A03
+    } else {
+        Variant::Nmos6502
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{run_case, run_file, variant_of};
+    use crate::cpu::opcode::Instruction;
+    use crate::cpu::Variant;
+
+    use std::env;
+    use std::path::Path;
+
+    const LDA_IMMEDIATE: &str = r#"{ "name": "a9 80 1d",
+        "initial": { "pc": 512, "s": 253, "a": 0, "x": 0, "y": 0, "p": 36, "ram": [[512, 169], [513, 128]] },
+        "final": { "pc": 514, "s": 253, "a": 128, "x": 0, "y": 0, "p": 164, "ram": [[512, 169], [513, 128]] },
+        "cycles": [[512, 169, "read"], [513, 128, "read"]] }"#;
+
+    #[test]
+    fn passing_case_should_have_no_mismatch() {
+        let case = serde_json::from_str(LDA_IMMEDIATE).unwrap();
+        assert_eq!(run_case(Variant::Ricoh2A03, &case), Ok(vec![]));
+    }
+    #[test]
+    fn mismatches_should_be_reported_by_kind() {
+        let mut case: serde_json::Value = serde_json::from_str(LDA_IMMEDIATE).unwrap();
+        case["final"]["a"] = 0x7F.into();
+        case["final"]["ram"][0][1] = 0x00.into();
+        case["cycles"][1][2] = "write".into();
+        let kinds: Vec<&str> = run_case(Variant::Ricoh2A03, &case).unwrap().iter().map(|(k, _)| *k).collect();
+        assert_eq!(kinds, vec!["registers", "memory", "bus"]);
+    }
+
+    #[test]
+    #[ignore]
+    fn single_step_tests() {
+        let dir = match env::var("SINGLE_STEP_TESTS") {
+            Ok(dir) => dir,
+            Err(_) => panic!("SINGLE_STEP_TESTS must point to a directory of <opcode>.json files"),
+        };
+        let dir = Path::new(&dir);
+        let variant = variant_of(dir);
+        println!("SingleStepTests: {} as {:?}", dir.display(), variant);
+        let mut failed = Vec::new();
+        for code in 0..=0xFFu8 {
+            // JAM and STP never end, WAI waits for an interrupt
+            match variant.opcodes()[code as usize].name {
+                Instruction::JAM | Instruction::STP | Instruction::WAI => continue,
+                _ => {}
+            }
+            let path = dir.join(format!("{:02x}.json", code));
+            if !path.exists() {
+                continue;
+            }
+            let report = run_file(variant, &path).unwrap();
+            if report.failed > 0 {
+                println!(
+                    "{:02x}: {}/{} failed (registers {}, memory {}, bus {}) first: {}",
+                    code,
+                    report.failed,
+                    report.cases,
+                    report.registers,
+                    report.memory,
+                    report.bus,
+                    report.first_failure.unwrap_or_default()
+                );
+                failed.push(code);
+            }
+        }
+        assert!(failed.is_empty(), "{} opcodes failed: {:02x?}", failed.len(), failed);
+    }
+}
@@ -0,0 +1,514 @@
+use crate::cpu::bus::Bus;
+use crate::cpu::disasm::Instr;
+use crate::cpu::opcode::{Addressing, Instruction};
+use crate::cpu::register::CpuRegister;
+use crate::cpu::Cpu;
+use crate::error::{Component, EmuError};
+
+use std::fs::File;
+use std::io::{BufWriter, Write};
+
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub enum TraceFormat {
+    Nintendulator,
+    Mesen,
+    Fceux,
+}
+
+impl TraceFormat {
+    pub fn from_name(name: &str) -> Option<TraceFormat> {
+        match name.to_lowercase().as_str() {
+            "nintendulator" => Some(TraceFormat::Nintendulator),
+            "mesen" => Some(TraceFormat::Mesen),
+            "fceux" => Some(TraceFormat::Fceux),
+            _ => None,
+        }
+    }
+}
+
+// Every filter has to accept an instruction for it to be written
+#[derive(Debug, Clone, Default)]
+pub struct TraceFilter {
+    pub pc_range: Option<(u16, u16)>,
+    pub after_frame: u64,
+    pub nmi_only: bool,
+    pub max_lines: Option<u64>,
+}
+
+pub struct Tracer<W: Write> {
+    out: W,
+    format: TraceFormat,
+    filter: TraceFilter,
+    lines: u64,
+    enabled: bool,
+}
+
+impl Tracer<BufWriter<File>> {
+    pub fn create(path: &str, format: TraceFormat, filter: TraceFilter) -> Result<Self, EmuError> {
+        let file = File::create(path)
+            .map_err(|e| EmuError::new(Component::Frontend, format!("Cannot create trace file {}: {}", path, e)))?;
+        println!("TRACE: Logging to {} ({:?})", path, format);
+        Ok(Tracer::new(BufWriter::new(file), format, filter))
+    }
+}
+
+impl<W: Write> Tracer<W> {
+    pub fn new(out: W, format: TraceFormat, filter: TraceFilter) -> Tracer<W> {
+        Tracer {
+            out,
+            format,
+            filter,
+            lines: 0,
+            enabled: true,
+        }
+    }
+    pub fn is_enabled(&self) -> bool {
+        self.enabled
+    }
+    // Enabling the trace again starts a new count of max_lines
+    pub fn toggle(&mut self) -> bool {
+        self.enabled = !self.enabled;
+        if self.enabled {
+            self.lines = 0;
+        } else {
+            self.out.flush().unwrap_or(());
+        }
+        self.enabled
+    }
+    fn accept(&self, cpu: &Cpu, frame: u64) -> bool {
+        let pc = cpu.register.get_pc();
+        if let Some((start, end)) = self.filter.pc_range {
+            if pc < start || pc > end {
+                return false;
+            }
+        }
+        frame >= self.filter.after_frame && (!self.filter.nmi_only || cpu.is_in_nmi())
+    }
+    // Must be called before the instruction at PC is executed
+    pub fn trace<B: Bus>(&mut self, cpu: &Cpu, bus: &mut B, ppu: (i16, i16), frame: u64, cycle: u64) {
+        if !self.enabled || !self.accept(cpu, frame) {
+            return;
+        }
+        let line = match self.format {
+            TraceFormat::Nintendulator => nintendulator(cpu, bus, ppu, cycle),
+            TraceFormat::Mesen => mesen(cpu, bus, ppu, frame, cycle),
+            TraceFormat::Fceux => fceux(cpu, bus),
+        };
+        if writeln!(self.out, "{}", line).is_err() {
+            println!("TRACE: Write failed, tracing stopped");
+            self.enabled = false;
+            return;
+        }
+        self.lines += 1;
+        if self.filter.max_lines.is_some_and(|max| self.lines >= max) {
+            println!("TRACE: {} lines written, tracing stopped", self.lines);
+            self.toggle();
+        }
+    }
+}
+
+impl<W: Write> Drop for Tracer<W> {
+    fn drop(&mut self) {
+        self.out.flush().unwrap_or(());
+    }
+}
+
+// One line per instruction, in the format used by Nintendulator (and nestest.log) :
+// C000  4C F5 C5  JMP $C5F5                       A:00 X:00 Y:00 P:24 SP:FD PPU:  0, 21 CYC:7
+pub fn nintendulator<B: Bus>(cpu: &Cpu, bus: &mut B, ppu: (i16, i16), cycle: u64) -> String {
+    let pc = cpu.register.get_pc();
+    let instr = Instr::read(cpu.variant, bus, pc);
+    let bytes: Vec<String> = instr.bytes().iter().map(|b| format!("{:02X}", b)).collect();
+    let unofficial = if instr.is_unofficial() { '*' } else { ' ' };
+    let disasm = format!("{} {}", instr.mnemonic(), operand(cpu, bus, &instr));
+    format!(
+        "{:04X}  {:<8} {}{:<32}A:{:02X} X:{:02X} Y:{:02X} P:{:02X} SP:{:02X} PPU:{:>3},{:>3} CYC:{}",
+        pc,
+        bytes.join(" "),
+        unofficial,
+        disasm.trim_end(),
+        cpu.register.get_a(),
+        cpu.register.get_x(),
+        cpu.register.get_y(),
+        cpu.register.get_sr(),
+        cpu.register.get_sp(),
+        ppu.0,
+        ppu.1,
+        cycle
+    )
+}
+
+// Mesen default trace format :
+// C000  $4C $F5 $C5  JMP $C5F5                       A:00 X:00 Y:00 P:24 SP:FD CYC:21  SL:0   FC:0 CPU Cycle:7
+pub fn mesen<B: Bus>(cpu: &Cpu, bus: &mut B, ppu: (i16, i16), frame: u64, cycle: u64) -> String {
+    let pc = cpu.register.get_pc();
+    let instr = Instr::read(cpu.variant, bus, pc);
+    let bytes: Vec<String> = instr.bytes().iter().map(|b| format!("${:02X}", b)).collect();
+    let mut disasm = instr.text();
+    if let Some(addr) = effective_address(cpu, bus, &instr) {
+        if is_indexed(&instr.opcode.mode) {
+            disasm.push_str(&format!(" [${:04X}]", addr));
+        }
+        disasm.push_str(&format!(" = ${:02X}", bus.inspect(addr)));
+    }
+    format!(
+        "{:04X}  {:<13}{:<32}A:{:02X} X:{:02X} Y:{:02X} P:{:02X} SP:{:02X} CYC:{:<3} SL:{:<3} FC:{} CPU Cycle:{}",
+        pc,
+        bytes.join(" "),
+        disasm.trim_end(),
+        cpu.register.get_a(),
+        cpu.register.get_x(),
+        cpu.register.get_y(),
+        cpu.register.get_sr(),
+        cpu.register.get_sp(),
+        ppu.1,
+        ppu.0,
+        frame,
+        cycle
+    )
+}
+
+// FCEUX trace logger format, flags are upper case when set :
+// $C000:4C F5 C5  JMP $C5F5                        A:00 X:00 Y:00 S:FD P:nvUbdIzc
+pub fn fceux<B: Bus>(cpu: &Cpu, bus: &mut B) -> String {
+    let pc = cpu.register.get_pc();
+    let instr = Instr::read(cpu.variant, bus, pc);
+    let bytes: Vec<String> = instr.bytes().iter().map(|b| format!("{:02X}", b)).collect();
+    let mut disasm = instr.text();
+    if let Some(addr) = effective_address(cpu, bus, &instr) {
+        if is_indexed(&instr.opcode.mode) {
+            disasm.push_str(&format!(" @ ${:04X}", addr));
+        }
+        disasm.push_str(&format!(" = #${:02X}", bus.inspect(addr)));
+    }
+    let sr = cpu.register.get_sr();
+    let flags: String = "NVUBDIZC"
+        .chars()
+        .enumerate()
+        .map(|(i, c)| if sr & (0x80 >> i) != 0 { c } else { c.to_ascii_lowercase() })
+        .collect();
+    format!(
+        "${:04X}:{:<9} {:<32} A:{:02X} X:{:02X} Y:{:02X} S:{:02X} P:{}",
+        pc,
+        bytes.join(" "),
+        disasm.trim_end(),
+        cpu.register.get_a(),
+        cpu.register.get_x(),
+        cpu.register.get_y(),
+        cpu.register.get_sp(),
+        flags
+    )
+}
+
+fn read_word<B: Bus>(bus: &mut B, addr: u16) -> u16 {
+    bus.inspect(addr) as u16 | (bus.inspect(addr.wrapping_add(1)) as u16) << 8
+}
+
+// Same as read_word but the high byte is read from the same page, like the CPU does
+fn read_word_wrapped<B: Bus>(bus: &mut B, addr: u16) -> u16 {
+    let hi = (addr & 0xFF00) | (addr.wrapping_add(1) & 0x00FF);
+    bus.inspect(addr) as u16 | (bus.inspect(hi) as u16) << 8
+}
+
+fn is_indexed(mode: &Addressing) -> bool {
+    matches!(
+        mode,
+        Addressing::ZeroPageX | Addressing::ZeroPageY | Addressing::AbsoluteX | Addressing::AbsoluteY
+            | Addressing::IndexedIndirect | Addressing::IndirectIndexed
+    )
+}
+
+// Address of the data accessed by the instruction, if it accesses memory
+fn effective_address<B: Bus>(cpu: &Cpu, bus: &mut B, instr: &Instr) -> Option<u16> {
+    let x = cpu.register.get_x();
+    let y = cpu.register.get_y();
+    let byte = instr.operand as u8;
+    let word = instr.operand;
+    match instr.opcode.mode {
+        Addressing::ZeroPage => Some(byte as u16),
+        Addressing::ZeroPageX => Some(byte.wrapping_add(x) as u16),
+        Addressing::ZeroPageY => Some(byte.wrapping_add(y) as u16),
+        Addressing::Absolute => match instr.opcode.name {
+            Instruction::JMP | Instruction::JSR => None,
+            _ => Some(word),
+        },
+        Addressing::AbsoluteX => Some(word.wrapping_add(x as u16)),
+        Addressing::AbsoluteY => Some(word.wrapping_add(y as u16)),
+        Addressing::IndexedIndirect => Some(read_word_wrapped(bus, byte.wrapping_add(x) as u16)),
+        Addressing::IndirectIndexed => Some(read_word_wrapped(bus, byte as u16).wrapping_add(y as u16)),
+        Addressing::ZeroPageIndirect => Some(read_word_wrapped(bus, byte as u16)),
+        Addressing::ZeroPageRelative => Some(byte as u16),
+        _ => None,
+    }
+}
+
+fn operand<B: Bus>(cpu: &Cpu, bus: &mut B, instr: &Instr) -> String {
+    let x = cpu.register.get_x();
+    let y = cpu.register.get_y();
+    let byte = instr.operand as u8;
+    let word = instr.operand;
+    match instr.opcode.mode {
+        Addressing::Implied | Addressing::Accumulator | Addressing::Immediate | Addressing::Relative => instr.operand(),
+        Addressing::ZeroPage => format!("${:02X} = {:02X}", byte, bus.inspect(byte as u16)),
+        Addressing::ZeroPageX => {
+            let addr = byte.wrapping_add(x);
+            format!("${:02X},X @ {:02X} = {:02X}", byte, addr, bus.inspect(addr as u16))
+        }
+        Addressing::ZeroPageY => {
+            let addr = byte.wrapping_add(y);
+            format!("${:02X},Y @ {:02X} = {:02X}", byte, addr, bus.inspect(addr as u16))
+        }
+        Addressing::Absolute => match instr.opcode.name {
+            Instruction::JMP | Instruction::JSR => format!("${:04X}", word),
+            _ => format!("${:04X} = {:02X}", word, bus.inspect(word)),
+        },
+        Addressing::AbsoluteX => {
+            let addr = word.wrapping_add(x as u16);
+            format!("${:04X},X @ {:04X} = {:02X}", word, addr, bus.inspect(addr))
+        }
+        Addressing::AbsoluteY => {
+            let addr = word.wrapping_add(y as u16);
+            format!("${:04X},Y @ {:04X} = {:02X}", word, addr, bus.inspect(addr))
+        }
+        Addressing::IndexedIndirect => {
+            let ptr = byte.wrapping_add(x);
+            let addr = read_word_wrapped(bus, ptr as u16);
+            format!("(${:02X},X) @ {:02X} = {:04X} = {:02X}", byte, ptr, addr, bus.inspect(addr))
+        }
+        Addressing::IndirectIndexed => {
+            let base = read_word_wrapped(bus, byte as u16);
+            let addr = base.wrapping_add(y as u16);
+            format!("(${:02X}),Y = {:04X} @ {:04X} = {:02X}", byte, base, addr, bus.inspect(addr))
+        }
+        Addressing::IndirectAbsolute if cpu.variant.is_cmos() => format!("(${:04X}) = {:04X}", word, read_word(bus, word)),
+        Addressing::IndirectAbsolute => format!("(${:04X}) = {:04X}", word, read_word_wrapped(bus, word)),
+        Addressing::ZeroPageIndirect => {
+            let addr = read_word_wrapped(bus, byte as u16);
+            format!("(${:02X}) = {:04X} = {:02X}", byte, addr, bus.inspect(addr))
+        }
+        Addressing::IndexedAbsoluteIndirect => {
+            let ptr = word.wrapping_add(x as u16);
+            format!("(${:04X},X) = {:04X}", word, read_word(bus, ptr))
+        }
+        Addressing::ZeroPageRelative => {
+            format!("${:02X} = {:02X},${:04X}", byte, bus.inspect(byte as u16), instr.target().unwrap_or(0))
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::controller::Controller;
+    use crate::cpu::bus::{Bus, CpuBus};
+    use crate::cpu::interrupt::IrqLine;
+    use crate::cpu::memory::Ram;
+    use crate::cpu::register::CpuRegister;
+    use crate::cpu::{Cpu, EmulationStatus};
+    use crate::ppu::Ppu;
+    use crate::rom::Cartbridge;
+
+    use super::{TraceFilter, TraceFormat, Tracer};
+    use std::path::Path;
+
+    const NESTEST_ROM: &str = "roms/nestest.nes";
+    // Reference trace from Nintendulator, not shipped with the repo: the comparison is skipped
+    // until the log is in roms/
+    const NESTEST_LOG: &str = "roms/nestest.log";
+    // Last instruction of the automation mode
+    const NESTEST_END: u16 = 0xC66E;
+
+    fn first_divergence(expected: &str, got: &str) -> usize {
+        expected.chars().zip(got.chars()).take_while(|(a, b)| a == b).count()
+    }
+
+    // Trace of the automation mode, with the results left by nestest at $02/$03
+    fn nestest_trace() -> (Vec<String>, [u8; 2]) {
+        let mut rom = Cartbridge::new();
+        let data = rom.read_file(String::from(NESTEST_ROM)).unwrap();
+        rom.load_program(&data).unwrap();
+        let mut ram = Ram::new();
+        let mut ppu = Ppu::new();
+        let mut controller = Controller::new();
+        let mut irq = IrqLine::new();
+        let mut bus = CpuBus::new(&mut ram, &mut rom, &mut ppu, &mut controller, &mut irq);
+        let mut cpu = Cpu::new();
+        // Automation mode: start at $C000 with the state left by the reset sequence
+        cpu.register.set_pc(0xC000).set_sp(0xFD).set_sr(0x24);
+        let mut cycle: u64 = 7;
+        for _ in 0..cycle {
+            bus.tick();
+        }
+        let mut history = Vec::new();
+        loop {
+            let position = bus.ppu_position();
+            history.push(super::nintendulator(&cpu, &mut bus, position, cycle));
+            let pc = cpu.register.get_pc();
+            let (cycles, status) = cpu.run(&mut bus);
+            cycle += cycles;
+            assert!(status != EmulationStatus::JAMMED, "CPU jammed at {:04X}", pc);
+            if pc == NESTEST_END {
+                break;
+            }
+            assert!(history.len() < 10000, "nestest did not reach ${:04X}", NESTEST_END);
+        }
+        (history, [bus.peek(0x0002), bus.peek(0x0003)])
+    }
+
+    #[test]
+    fn nestest_should_pass_its_own_checks() {
+        // nestest stores the number of the first failing official/unofficial test at $02/$03
+        let (_, results) = nestest_trace();
+        assert_eq!(results[0], 0, "official opcodes test failed");
+        assert_eq!(results[1], 0, "unofficial opcodes test failed");
+    }
+    #[test]
+    fn nestest_should_match_reference_log() {
+        let path = Path::new(NESTEST_LOG);
+        if !path.exists() {
+            println!("{} not found, skipping the comparison", NESTEST_LOG);
+            return;
+        }
+        let reference: Vec<String> = std::fs::read_to_string(path)
+            .unwrap()
+            .lines()
+            .map(|l| String::from(l.trim_end()))
+            .collect();
+        let (history, _) = nestest_trace();
+        for (n, (expected, line)) in reference.iter().zip(history.iter()).enumerate() {
+            if expected != line {
+                let column = first_divergence(expected, line);
+                panic!(
+                    "nestest diverges at line {} :\n{}\nexpected: {}\ngot     : {}\n          {}^",
+                    n + 1,
+                    history[n.saturating_sub(5)..n].iter().map(|l| format!("          {}\n", l)).collect::<String>(),
+                    expected,
+                    line,
+                    " ".repeat(column)
+                );
+            }
+        }
+        assert_eq!(history.len(), reference.len(), "nestest ended at ${:04X}", NESTEST_END);
+    }
+
+    struct FlatBus {
+        mem: Vec<u8>,
+    }
+
+    impl Bus for FlatBus {
+        fn peek(&mut self, i: u16) -> u8 {
+            self.mem[i as usize]
+        }
+        fn write(&mut self, i: u16, v: u8) -> u8 {
+            self.mem[i as usize] = v;
+            v
+        }
+    }
+
+    fn flat_bus(program: &[u8]) -> (Cpu, FlatBus) {
+        let mut mem = vec![0u8; 0x10000];
+        mem[0x0200..0x0200 + program.len()].copy_from_slice(program);
+        let mut cpu = Cpu::new();
+        cpu.register.set_pc(0x0200).set_sp(0xFD).set_sr(0x24);
+        (cpu, FlatBus { mem })
+    }
+
+    fn run_traced(tracer: &mut Tracer<Vec<u8>>, cpu: &mut Cpu, bus: &mut FlatBus, n: usize, frame: u64) {
+        for _ in 0..n {
+            tracer.trace(cpu, bus, (0, 0), frame, 0);
+            cpu.run(bus);
+        }
+    }
+
+    fn output(tracer: &Tracer<Vec<u8>>) -> Vec<String> {
+        String::from_utf8(tracer.out.clone()).unwrap().lines().map(String::from).collect()
+    }
+
+    #[test]
+    fn format_should_be_parsed_from_name() {
+        assert_eq!(TraceFormat::from_name("Mesen"), Some(TraceFormat::Mesen));
+        assert_eq!(TraceFormat::from_name("fceux"), Some(TraceFormat::Fceux));
+        assert_eq!(TraceFormat::from_name("nestopia"), None);
+    }
+    #[test]
+    fn tracer_should_only_log_pc_range() {
+        let (mut cpu, mut bus) = flat_bus(&[0xEA, 0xEA, 0xEA]);
+        let filter = TraceFilter { pc_range: Some((0x0201, 0x0201)), ..Default::default() };
+        let mut tracer = Tracer::new(Vec::new(), TraceFormat::Nintendulator, filter);
+        run_traced(&mut tracer, &mut cpu, &mut bus, 3, 0);
+        let lines = output(&tracer);
+        assert_eq!(lines.len(), 1);
+        assert!(lines[0].starts_with("0201  EA"));
+    }
+    #[test]
+    fn tracer_should_stop_after_max_lines() {
+        let (mut cpu, mut bus) = flat_bus(&[0xEA, 0xEA, 0xEA]);
+        let filter = TraceFilter { max_lines: Some(2), ..Default::default() };
+        let mut tracer = Tracer::new(Vec::new(), TraceFormat::Nintendulator, filter);
+        run_traced(&mut tracer, &mut cpu, &mut bus, 3, 0);
+        assert_eq!(output(&tracer).len(), 2);
+        assert!(!tracer.is_enabled());
+    }
+    #[test]
+    fn tracer_should_stop_again_after_being_enabled() {
+        let (mut cpu, mut bus) = flat_bus(&[0xEA, 0xEA, 0xEA, 0xEA, 0xEA]);
+        let filter = TraceFilter { max_lines: Some(2), ..Default::default() };
+        let mut tracer = Tracer::new(Vec::new(), TraceFormat::Nintendulator, filter);
+        run_traced(&mut tracer, &mut cpu, &mut bus, 2, 0);
+        assert!(tracer.toggle());
+        run_traced(&mut tracer, &mut cpu, &mut bus, 3, 0);
+        let lines = output(&tracer);
+        assert_eq!(lines.len(), 4);
+        assert!(lines[3].starts_with("0203"));
+        assert!(!tracer.is_enabled());
+    }
+    #[test]
+    fn tracer_should_wait_for_frame() {
+        let (mut cpu, mut bus) = flat_bus(&[0xEA, 0xEA, 0xEA]);
+        let filter = TraceFilter { after_frame: 5, ..Default::default() };
+        let mut tracer = Tracer::new(Vec::new(), TraceFormat::Nintendulator, filter);
+        run_traced(&mut tracer, &mut cpu, &mut bus, 2, 4);
+        run_traced(&mut tracer, &mut cpu, &mut bus, 1, 5);
+        let lines = output(&tracer);
+        assert_eq!(lines.len(), 1);
+        assert!(lines[0].starts_with("0202"));
+    }
+    #[test]
+    fn tracer_should_only_log_inside_nmi() {
+        let (mut cpu, mut bus) = flat_bus(&[0xEA, 0xEA]);
+        bus.mem[0x0300] = 0x40; // RTI
+        let filter = TraceFilter { nmi_only: true, ..Default::default() };
+        let mut tracer = Tracer::new(Vec::new(), TraceFormat::Nintendulator, filter);
+        run_traced(&mut tracer, &mut cpu, &mut bus, 1, 0);
+        // Enter the handler at $0300 as if an NMI was taken
+        cpu.in_nmi = true;
+        cpu.register.set_pc(0x0300);
+        run_traced(&mut tracer, &mut cpu, &mut bus, 2, 0);
+        let lines = output(&tracer);
+        assert_eq!(lines.len(), 1);
+        assert!(lines[0].starts_with("0300  40        RTI"));
+    }
+    #[test]
+    fn tracer_should_toggle() {
+        let (mut cpu, mut bus) = flat_bus(&[0xEA, 0xEA]);
+        let mut tracer = Tracer::new(Vec::new(), TraceFormat::Nintendulator, TraceFilter::default());
+        assert!(!tracer.toggle());
+        run_traced(&mut tracer, &mut cpu, &mut bus, 1, 0);
+        assert!(tracer.toggle());
+        run_traced(&mut tracer, &mut cpu, &mut bus, 1, 0);
+        assert_eq!(output(&tracer).len(), 1);
+    }
+    #[test]
+    fn mesen_and_fceux_lines() {
+        let (mut cpu, mut bus) = flat_bus(&[0xBD, 0x00, 0x03]); // LDA $0300,X
+        bus.mem[0x0305] = 0x42;
+        cpu.register.set_x(0x05);
+        assert_eq!(
+            super::mesen(&cpu, &mut bus, (241, 30), 2, 1234),
+            "0200  $BD $00 $03  LDA $0300,X [$0305] = $42       A:00 X:05 Y:00 P:24 SP:FD CYC:30  SL:241 FC:2 CPU Cycle:1234"
+        );
+        assert_eq!(
+            super::fceux(&cpu, &mut bus),
+            "$0200:BD 00 03  LDA $0300,X @ $0305 = #$42       A:00 X:05 Y:00 S:FD P:nvUbdIzc"
+        );
+    }
+}
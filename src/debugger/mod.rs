@@ -71,26 +71,14 @@ impl PpuDebugger {
     }
     pub fn draw_palette(&mut self, palette: &Palette) -> DebuggerStatus {
         self.draw_rect(256, 0, 256, 240);
-        for (tile,t) in palette.background.iter().enumerate() {
-            let xcoord = (SCREEN_WIDTH / 2) + (((tile % 32) * 8)) as u32;
-            let ycoord = (((tile / 32) * 8)) as u32;
-            if *t == 0 {
-                continue;
-            }
-            let color = get_rgb(*t);
-            for i in 0..8 {
-                for j in 0..8 {
-                    self.set_pixel_rgb(xcoord + i, ycoord + j, color);
-                }
-            }
-        }
-        for (tile,t) in palette.sprite.iter().enumerate() {
-            let xcoord = (SCREEN_WIDTH / 2) + (((tile % 32) * 8)) as u32;
-            let ycoord = 128 + (((tile / 32) * 8)) as u32;
-            if *t == 0 {
-                continue;
-            }
-            let color = get_rgb(*t);
+        // Background palettes on the first row, sprite palettes on the second
+        for entry in 0..32u8 {
+            let xcoord = (SCREEN_WIDTH / 2) + ((entry as u32 % 16) * 8);
+            let ycoord = (entry as u32 / 16) * 128;
+            let color = match entry & 0x10 {
+                0 => get_rgb(palette.peek_color_background(entry)),
+                _ => get_rgb(palette.peek_color_sprite((entry >> 2) & 3, entry & 3)),
+            };
             for i in 0..8 {
                 for j in 0..8 {
                     self.set_pixel_rgb(xcoord + i, ycoord + j, color);
@@ -124,7 +112,8 @@ impl PpuDebugger {
         }
         self.draw_rect(256, 240, 256, 240);
     }
-    pub fn draw_sprites(&mut self, tileset: &Vec<Tile>, mem: &SpriteMem, palette: &Palette) {
+    // 8x16 sprites take their pattern table from bit 0 of the index, the top tile is the even one
+    pub fn draw_sprites(&mut self, tileset: &Vec<Tile>, mem: &SpriteMem, palette: &Palette, table: u8, height: u8) {
         self.draw_rect(256, 240, 256, 240);
         let spr_mem = mem.get_oam();
         for i in (0..spr_mem.len()).step_by(4) {
@@ -146,18 +135,27 @@ impl PpuDebugger {
                     x = spr_mem[i+j];
                 }
             }
-            let tile = tileset.get(index as usize).unwrap();
-            for (y2, tt) in tile.iter().enumerate() {
-                for (x2, tile_v) in tt.iter().enumerate() {
-                    let col = palette.peek_color_sprite(attr & 3, *tile_v);
-                    let xcoord = 256 + (((x as usize) + x2)) as u32;
-                    let ycoord = 240 + (((y as usize) + y2)) as u32;
-                    let color = get_rgb(col);
-                    if color == (0,0,0) || color == get_rgb(palette.peek_color_sprite(0, 0)) {
+            let first = match height {
+                16 => 0x100 * (index as usize & 1) + (index as usize & 0xFE),
+                _ => 0x100 * table as usize + index as usize,
+            };
+            for half in 0..(height as usize / 8) {
+                let tile = match tileset.get(first + half) {
+                    Some(tile) => tile,
+                    None => continue,
+                };
+                for (y2, tt) in tile.iter().enumerate() {
+                    for (x2, tile_v) in tt.iter().enumerate() {
+                        // Colour 0 is transparent
+                        if *tile_v == 0 {
+                            continue;
+                        }
+                        let col = palette.peek_color_sprite(attr & 3, *tile_v);
+                        let xcoord = 256 + (x as usize + x2) as u32;
+                        let ycoord = 240 + (y as usize + 8 * half + y2) as u32;
+                        let color = get_rgb(col);
                         self.set_pixel_rgb(xcoord, ycoord, color);
-                        continue;
                     }
-                    self.set_pixel_rgb(xcoord, ycoord, color);
                 }
             }
         }
@@ -0,0 +1,225 @@
+// Machine of the easy6502 tutorial (http://skilldrick.github.io/easy6502) :
+// a 6502 on a flat 64K bus, a random byte at $FE, the last key pressed at $FF
+// and a 32x32 display mapped at $0200-$05FF, one byte (color index) per pixel.
+use sdl2::event::Event;
+use sdl2::keyboard::Keycode;
+use sdl2::pixels::PixelFormatEnum;
+use sdl2::render::TextureAccess;
+
+use rand::rngs::StdRng;
+use rand::{Rng, SeedableRng};
+
+use crate::cpu::bus::Bus;
+use crate::cpu::{Cpu, EmulationStatus, Variant};
+use crate::error::{Component, EmuError};
+use crate::renderer::get_rgb;
+use crate::state;
+
+use std::fs;
+use std::thread;
+use std::time::Duration;
+
+pub const DISPLAY_SIZE: u32 = 32;
+const DISPLAY_START: u16 = 0x0200;
+const RANDOM: u16 = 0x00FE;
+const LAST_KEY: u16 = 0x00FF;
+const ORIGIN: u16 = 0x0600;
+const WINDOW_SCALE: u32 = 10;
+// The tutorial runs its programs slowly enough for the games to be playable
+const INSTRUCTIONS_PER_TICK: usize = 100;
+const TICK: Duration = Duration::from_millis(15);
+const HEADLESS_MAX_INSTRUCTIONS: usize = 10_000_000;
+
+const COLORS: [u32; 16] = [
+    0x000000, 0xFFFFFF, 0x880000, 0xAAFFEE, 0xCC44CC, 0x00CC55, 0x0000AA, 0xEEEE77,
+    0xDD8855, 0x664400, 0xFF7777, 0x333333, 0x777777, 0xAAFF66, 0x0088FF, 0xBBBBBB,
+];
+
+pub struct FlatBus {
+    mem: Vec<u8>,
+}
+
+impl Bus for FlatBus {
+    fn peek(&mut self, i: u16) -> u8 {
+        self.mem[i as usize]
+    }
+    fn write(&mut self, i: u16, v: u8) -> u8 {
+        self.mem[i as usize] = v;
+        v
+    }
+}
+
+pub struct Machine {
+    cpu: Cpu,
+    bus: FlatBus,
+    rng: StdRng,
+}
+
+impl Machine {
+    // Programs are assembled at $0600, like in the tutorial
+    // The random bytes at $FE come from the seed
+    pub fn new(program: &[u8], seed: u64) -> Machine {
+        let mut mem = vec![0u8; 0x10000];
+        let end = (ORIGIN as usize + program.len()).min(0xFFFC);
+        mem[ORIGIN as usize..end].copy_from_slice(&program[..end - ORIGIN as usize]);
+        mem[0xFFFC] = (ORIGIN & 0xFF) as u8;
+        mem[0xFFFD] = (ORIGIN >> 8) as u8;
+        let mut machine = Machine {
+            cpu: Cpu::with_variant(Variant::Nmos6502),
+            bus: FlatBus { mem },
+            rng: StdRng::seed_from_u64(seed),
+        };
+        machine.cpu.power_on(&mut machine.bus);
+        machine
+    }
+    pub fn set_key(&mut self, key: u8) {
+        self.bus.write(LAST_KEY, key);
+    }
+    // BRK ends the program
+    pub fn step(&mut self) -> EmulationStatus {
+        let random = self.rng.gen();
+        self.bus.write(RANDOM, random);
+        if self.bus.peek(self.cpu.get_pc()) == 0x00 {
+            return EmulationStatus::BREAK;
+        }
+        self.cpu.run(&mut self.bus).1
+    }
+    pub fn pixel(&self, x: u32, y: u32) -> u8 {
+        self.bus.mem[(DISPLAY_START as u32 + y * DISPLAY_SIZE + x) as usize] & 0x0F
+    }
+    pub fn display_rgb(&self) -> Vec<u8> {
+        let mut pixels = Vec::with_capacity((DISPLAY_SIZE * DISPLAY_SIZE * 3) as usize);
+        for y in 0..DISPLAY_SIZE {
+            for x in 0..DISPLAY_SIZE {
+                let (r, g, b) = get_rgb(COLORS[self.pixel(x, y) as usize]);
+                pixels.extend_from_slice(&[r, g, b]);
+            }
+        }
+        pixels
+    }
+}
+
+pub fn run(path: &str, headless: bool, seed: Option<u64>) -> Result<(), EmuError> {
+    let program = fs::read(path).map_err(|e| EmuError::new(Component::Cartridge, format!("Cannot open {}: {}", path, e)))?;
+    println!("EASY6502: Loaded {} bytes at ${:04X}", program.len(), ORIGIN);
+    let mut machine = Machine::new(&program, state::machine_seed(seed));
+    if headless {
+        run_headless(&mut machine);
+    } else {
+        run_window(&mut machine).map_err(|e| EmuError::new(Component::Frontend, e))?;
+    }
+    println!("{}", machine.cpu);
+    Ok(())
+}
+
+fn run_headless(machine: &mut Machine) {
+    for _ in 0..HEADLESS_MAX_INSTRUCTIONS {
+        match machine.step() {
+            EmulationStatus::PROCESSING => {}
+            _ => break,
+        }
+    }
+    for y in 0..DISPLAY_SIZE {
+        let line: String = (0..DISPLAY_SIZE).map(|x| format!("{:X}", machine.pixel(x, y))).collect();
+        println!("{}", line);
+    }
+}
+
+fn run_window(machine: &mut Machine) -> Result<(), String> {
+    let sdl_context = sdl2::init()?;
+    let video_subsys = sdl_context.video()?;
+    let window = video_subsys
+        .window("easy6502", DISPLAY_SIZE * WINDOW_SCALE, DISPLAY_SIZE * WINDOW_SCALE)
+        .position_centered()
+        .build()
+        .map_err(|e| e.to_string())?;
+    let mut canvas = window.into_canvas().build().map_err(|e| e.to_string())?;
+    let texture_creator = canvas.texture_creator();
+    let mut texture = texture_creator
+        .create_texture(PixelFormatEnum::RGB24, TextureAccess::Streaming, DISPLAY_SIZE, DISPLAY_SIZE)
+        .map_err(|e| e.to_string())?;
+    let mut events = sdl_context.event_pump()?;
+    let mut running = true;
+    loop {
+        for event in events.poll_iter() {
+            match event {
+                Event::Quit { .. } | Event::KeyDown { keycode: Some(Keycode::Escape), .. } => return Ok(()),
+                // Printable keys are reported with their ASCII code
+                Event::KeyDown { keycode: Some(key), .. } if (key as i32) < 0x80 => machine.set_key(key as i32 as u8),
+                _ => {}
+            }
+        }
+        if running {
+            for _ in 0..INSTRUCTIONS_PER_TICK {
+                if machine.step() != EmulationStatus::PROCESSING {
+                    println!("EASY6502: Program ended at ${:04X}", machine.cpu.get_pc());
+                    running = false;
+                    break;
+                }
+            }
+        }
+        texture
+            .update(None, &machine.display_rgb(), (DISPLAY_SIZE * 3) as usize)
+            .map_err(|e| e.to_string())?;
+        canvas.copy(&texture, None, None)?;
+        canvas.present();
+        thread::sleep(TICK);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::Machine;
+    use crate::cpu::bus::Bus;
+    use crate::cpu::EmulationStatus;
+
+    fn run_until_brk(machine: &mut Machine) -> usize {
+        let mut n = 0;
+        while machine.step() == EmulationStatus::PROCESSING {
+            n += 1;
+            assert!(n < 1000, "program did not reach BRK");
+        }
+        n
+    }
+
+    #[test]
+    fn myfile_should_run_until_brk() {
+        let program = std::fs::read("roms/myfile.dat").unwrap();
+        let mut machine = Machine::new(&program, 0);
+        // LDA #$80 / STA $01 / ADC $01
+        assert_eq!(run_until_brk(&mut machine), 3);
+        assert_eq!(machine.bus.peek(0x0001), 0x80);
+        assert_eq!(machine.cpu.get_pc(), 0x0606);
+    }
+    #[test]
+    fn last_key_should_be_readable_at_ff() {
+        // LDA $FF / STA $10
+        let mut machine = Machine::new(&[0xA5, 0xFF, 0x85, 0x10], 0);
+        machine.set_key(0x77);
+        run_until_brk(&mut machine);
+        assert_eq!(machine.bus.peek(0x0010), 0x77);
+    }
+    #[test]
+    fn random_bytes_should_only_depend_on_seed() {
+        // LDA $FE / STA $10 / LDA $FE / STA $11
+        let program = [0xA5, 0xFE, 0x85, 0x10, 0xA5, 0xFE, 0x85, 0x11];
+        let randoms = |seed| {
+            let mut machine = Machine::new(&program, seed);
+            run_until_brk(&mut machine);
+            (machine.bus.peek(0x0010), machine.bus.peek(0x0011))
+        };
+        assert_eq!(randoms(7), randoms(7));
+        assert_ne!(randoms(7), randoms(8));
+    }
+    #[test]
+    fn display_should_map_0200() {
+        // LDA #$01 / STA $0200 / LDA #$0E / STA $05FF
+        let mut machine = Machine::new(&[0xA9, 0x01, 0x8D, 0x00, 0x02, 0xA9, 0x0E, 0x8D, 0xFF, 0x05], 0);
+        run_until_brk(&mut machine);
+        assert_eq!(machine.pixel(0, 0), 0x01);
+        assert_eq!(machine.pixel(31, 31), 0x0E);
+        let rgb = machine.display_rgb();
+        assert_eq!(&rgb[0..3], &[0xFF, 0xFF, 0xFF]);
+        assert_eq!(&rgb[rgb.len() - 3..], &[0x00, 0x88, 0xFF]);
+    }
+}
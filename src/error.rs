@@ -0,0 +1,65 @@
+use crate::Cycle;
+
+use std::fmt;
+
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub enum Component {
+    Cpu,
+    Ppu,
+    Cartridge,
+    Config,
+    Frontend,
+}
+
+// Emulation failure. PC and cycle are only known when the machine was running.
+#[derive(Debug, Clone, PartialEq)]
+pub struct EmuError {
+    pub component: Component,
+    pub pc: Option<u16>,
+    pub cycle: Option<Cycle>,
+    pub message: String,
+}
+
+impl EmuError {
+    pub fn new<S: Into<String>>(component: Component, message: S) -> EmuError {
+        EmuError {
+            component,
+            pc: None,
+            cycle: None,
+            message: message.into(),
+        }
+    }
+    pub fn at(mut self, pc: u16, cycle: Cycle) -> EmuError {
+        self.pc = Some(pc);
+        self.cycle = Some(cycle);
+        self
+    }
+}
+
+impl fmt::Display for EmuError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{:?} error", self.component)?;
+        if let Some(pc) = self.pc {
+            write!(f, " at PC ${:04X}", pc)?;
+        }
+        if let Some(cycle) = self.cycle {
+            write!(f, ", cycle {}", cycle)?;
+        }
+        write!(f, ": {}", self.message)
+    }
+}
+
+impl std::error::Error for EmuError {}
+
+#[cfg(test)]
+mod tests {
+    use super::{Component, EmuError};
+
+    #[test]
+    fn display_should_locate_the_error() {
+        let e = EmuError::new(Component::Cartridge, "Invalid header");
+        assert_eq!(e.to_string(), "Cartridge error: Invalid header");
+        let e = EmuError::new(Component::Cpu, "Jammed on opcode $02").at(0xC123, 1042);
+        assert_eq!(e.to_string(), "Cpu error at PC $C123, cycle 1042: Jammed on opcode $02");
+    }
+}
@@ -1,5 +1,3 @@
-#[macro_use]
-extern crate lazy_static;
 extern crate ini;
 use sdl2;
 use sdl2::EventPump;
@@ -7,25 +5,35 @@ use sdl2::event::Event;
 use sdl2::keyboard::Keycode;
 use sdl2::timer::*;
 use ini::Ini;
+use rand::rngs::StdRng;
+use rand::{Rng, SeedableRng};
 
 mod controller;
 mod cpu;
 mod debugger;
 mod driver;
+mod easy6502;
+mod error;
 mod memory;
 mod ppu;
 mod renderer;
 mod rom;
+mod state;
 
 use std::env;
 use std::fmt;
 use std::fs;
+use std::fs::File;
+use std::hash::Hash;
+use std::io::BufWriter;
 use std::option::Option;
 use std::path::Path;
+use std::process;
 
 use cpu::Cpu;
 use rom::Cartbridge;
-use cpu::memory::Ram;
+use cpu::memory::{Ram, RamInit};
+use cpu::interrupt::IrqLine;
 #[allow(unused_imports)]
 use cpu::register::CpuRegister;
 #[allow(unused_imports)]
@@ -35,13 +43,19 @@ use cpu::bus::CpuBus;
 #[allow(unused_imports)]
 use cpu::bus::Bus;
 use cpu::EmulationStatus;
+use error::{Component, EmuError};
+use cpu::trace::{TraceFilter, TraceFormat, Tracer};
+use cpu::disasm::{self, Disassembly};
 use debugger::PpuDebugger;
 use renderer::Renderer;
-use ppu::PpuStatus;
 use controller::Controller;
+use state::StateHasher;
 
 pub type Cycle = u64;
 
+const DEFAULT_TRACE_FILE: &str = "trace.log";
+const LISTING_LINES: usize = 16;
+
 pub struct Context {
     ppu: ppu::Ppu,
     cpu: cpu::Cpu,
@@ -50,57 +64,83 @@ pub struct Context {
     debugger: Option<PpuDebugger>,
     renderer: Option<Renderer>,
     controller: Controller,
-    events: EventPump,
+    irq: IrqLine,
+    tracer: Option<Tracer<BufWriter<File>>>,
+    events: Option<EventPump>,
     cpu_cycle: Cycle,
     ppu_cycle: Cycle,
+    frame: u64,
+    frame_hash: u64,
+    // Every random behaviour of the machine draws from this RNG
+    rng: StdRng,
+    seed: Option<u64>,
     config_path: String,
-    sdl_context: sdl2::Sdl,
+    sdl_context: Option<sdl2::Sdl>,
 }
 
 impl Context {
-    pub fn new(path: String) -> Context {
+    pub fn new(path: String) -> Result<Context, EmuError> {
+        let mut ctx = Context::headless(path)?;
+        let sdl_context = sdl2::init().map_err(|e| EmuError::new(Component::Frontend, e))?;
+        let events: EventPump = sdl_context.event_pump().map_err(|e| EmuError::new(Component::Frontend, e))?;
+        ctx.events = Some(events);
+        ctx.sdl_context = Some(sdl_context);
+        Ok(ctx)
+    }
+    // Without window, debugger nor input events
+    pub fn headless(path: String) -> Result<Context, EmuError> {
         let cpu = Cpu::new();
         let cpu_ram = Ram::new();
         let ppu = ppu::Ppu::new();
         let mut cartbridge = Cartbridge::new();
-        let buffer = cartbridge.read_file(path);
-        cartbridge.load_program(&buffer);
-        let sdl_context = sdl2::init().unwrap();
-        let events: EventPump = sdl_context.event_pump().unwrap();
+        let buffer = cartbridge.read_file(path)?;
+        cartbridge.load_program(&buffer)?;
         let controller = Controller::new();
-        Context {
+        Ok(Context {
             ppu: ppu,
             cpu: cpu,
             cpu_ram: cpu_ram,
             rom: cartbridge,
             debugger: None,
             controller: controller,
-            events: events,
+            irq: IrqLine::new(),
+            tracer: None,
+            events: None,
             renderer: None,
             cpu_cycle: 0,
             ppu_cycle: 0,
+            frame: 0,
+            frame_hash: 0,
+            rng: StdRng::seed_from_u64(0),
+            seed: None,
             config_path: String::from("config/config.ini"),
-            sdl_context,
-        }
+            sdl_context: None,
+        })
     }
     pub fn run(&mut self) -> EmulationStatus{
-        let mut cpu_bus = CpuBus::new(&mut self.cpu_ram, &mut self.rom, &mut self.ppu, &mut self.controller);
+        let mut cpu_bus = CpuBus::new(&mut self.cpu_ram, &mut self.rom, &mut self.ppu, &mut self.controller, &mut self.irq);
+        if let Some(tracer) = &mut self.tracer {
+            let position = cpu_bus.ppu_position();
+            tracer.trace(&self.cpu, &mut cpu_bus, position, self.frame, self.cpu_cycle);
+        }
+        let pc = self.cpu.get_pc();
         let cpu_cb: (Cycle, EmulationStatus) = self.cpu.run(&mut cpu_bus);
-        let mut status = cpu_cb.1;
-        for _ in 0..cpu_cb.0 * 3 {
+        let frame_complete = cpu_bus.is_frame_complete();
+        let mut status = match cpu_cb.1 {
+            EmulationStatus::JAMMED => {
+                let message = format!("Jammed on opcode ${:02X}", cpu_bus.inspect(pc));
+                EmulationStatus::ERROR(EmuError::new(Component::Cpu, message).at(pc, self.cpu_cycle))
+            }
+            status => status,
+        };
+        if frame_complete {
+            self.frame += 1;
+            self.frame_hash = self.state_hash();
             match &mut self.renderer {
-                Some(renderer) => {
-                    match self.ppu.run(renderer) {
-                        PpuStatus::RENDERING => {
-                            renderer.draw_window();
-                            renderer.reset();
-                        },
-                        PpuStatus::INTERRUPTNMI => self.cpu.trigger_nmi(),
-                        PpuStatus::PROCESSING => {}
-                    }
-                }
+                Some(renderer) => renderer.draw_window(&self.ppu.frame),
                 None => {}
             }
+            self.ppu.frame.clear();
         }
         self.cpu_cycle += cpu_cb.0 as Cycle;
         self.ppu_cycle += cpu_cb.0 as Cycle * 3;
@@ -111,7 +151,8 @@ impl Context {
                         debugger.draw_tileset(&self.ppu.tileset, &self.ppu.mem.palette);
                         debugger.draw_palette(&self.ppu.mem.palette);
                         debugger.draw_nametable(&self.ppu.mem.nametable, &self.ppu.tileset, &self.ppu.mem.palette);
-                        debugger.draw_sprites(&self.ppu.tileset, &self.ppu.mem.spr_mem, &self.ppu.mem.palette);
+                        let (table, height) = (self.ppu.sprite_table(), self.ppu.sprite_height());
+                        debugger.draw_sprites(&self.ppu.tileset, &self.ppu.mem.spr_mem, &self.ppu.mem.palette, table, height);
                         debugger.draw();
                         self.ppu.clear_updated();
                     }
@@ -119,7 +160,11 @@ impl Context {
                 None => {}
             }
         }
-        for event in self.events.poll_iter() {
+        let events = match &mut self.events {
+            Some(events) => events,
+            None => return status,
+        };
+        for event in events.poll_iter() {
             self.controller.poll_events(&event);
             match event {
                 Event::Quit {..} | Event::KeyDown { keycode: Some(Keycode::Escape), .. } => {
@@ -134,6 +179,15 @@ impl Context {
                         None => {}
                     }
                 },
+                Event::KeyDown { keycode: Some(Keycode::F2), ..} => {
+                    Context::toggle_trace(&mut self.tracer);
+                },
+                Event::KeyDown { keycode: Some(Keycode::F3), ..} => {
+                    let mut cpu_bus = CpuBus::new(&mut self.cpu_ram, &mut self.rom, &mut self.ppu, &mut self.controller, &mut self.irq);
+                    for line in disasm::listing(self.cpu.get_variant(), &mut cpu_bus, self.cpu.get_pc(), LISTING_LINES) {
+                        println!("{}", line);
+                    }
+                },
                 Event::KeyDown { keycode: Some(Keycode::R), ..} => {
                     status = EmulationStatus::RESET;
                 }
@@ -142,9 +196,46 @@ impl Context {
         }
         status
     }
-    fn create_config_file(&self) {
+    // Seed given on the command line, it overrides the configuration
+    pub fn set_seed(&mut self, seed: Option<u64>) {
+        self.seed = seed;
+    }
+    // Two runs with the same seed and the same inputs have the same hash at every frame
+    pub fn state_hash(&self) -> u64 {
+        let mut hasher = StateHasher::new();
+        self.cpu.hash(&mut hasher);
+        self.cpu_ram.hash(&mut hasher);
+        self.rom.hash(&mut hasher);
+        self.ppu.hash(&mut hasher);
+        self.controller.hash(&mut hasher);
+        self.irq.hash(&mut hasher);
+        self.cpu_cycle.hash(&mut hasher);
+        self.frame.hash(&mut hasher);
+        std::hash::Hasher::finish(&hasher)
+    }
+    // State hash taken at the end of the last complete frame
+    pub fn frame_hash(&self) -> (u64, u64) {
+        (self.frame, self.frame_hash)
+    }
+    pub fn set_tracer(&mut self, tracer: Tracer<BufWriter<File>>) {
+        self.tracer = Some(tracer);
+    }
+    fn toggle_trace(tracer: &mut Option<Tracer<BufWriter<File>>>) {
+        match tracer {
+            Some(tracer) => {
+                let enabled = tracer.toggle();
+                println!("TRACE: {}", if enabled { "Enabled" } else { "Disabled" });
+            }
+            None => match Tracer::create(DEFAULT_TRACE_FILE, TraceFormat::Nintendulator, TraceFilter::default()) {
+                Ok(created) => *tracer = Some(created),
+                Err(e) => println!("TRACE: {}", e),
+            },
+        }
+    }
+    fn create_config_file(&self) -> Result<(), EmuError> {
+        let error = |e: std::io::Error| EmuError::new(Component::Config, format!("Cannot create {}: {}", self.config_path, e));
         if !Path::new("config").exists() {
-            fs::create_dir("config").unwrap();
+            fs::create_dir("config").map_err(error)?;
         }
         if !Path::new(&self.config_path).exists() {
             let mut conf = Ini::new();
@@ -152,69 +243,275 @@ impl Context {
                 .set("scale", "1.1");
             conf.with_section(Some("Debugger".to_owned()))
                 .set("scale", "2.0");
-            conf.write_to_file(&self.config_path).unwrap();
+            conf.with_section(Some("Memory".to_owned()))
+                .set("init", "zeros");
+            conf.with_section(Some("Emulation".to_owned()))
+                .set("seed", "0");
+            conf.write_to_file(&self.config_path).map_err(error)?;
         }
+        Ok(())
+    }
+    fn config_scale(&self, conf: &Ini, section: &str) -> Result<f32, EmuError> {
+        let error = |message: String| EmuError::new(Component::Config, format!("{}: {}", self.config_path, message));
+        let value = conf
+            .section(Some(section.to_owned()))
+            .and_then(|s| s.get("scale"))
+            .ok_or_else(|| error(format!("missing scale in [{}]", section)))?;
+        value.parse::<f32>().map_err(|_| error(format!("invalid scale '{}' in [{}]", value, section)))
+    }
+    // [Memory] init = zeros|ff|random|fceux, zeros when missing
+    fn config_ram_init(&self, conf: &Ini) -> Result<RamInit, EmuError> {
+        let name = conf.get_from(Some("Memory"), "init").unwrap_or("zeros");
+        RamInit::from_name(name).ok_or_else(|| {
+            EmuError::new(Component::Config, format!("{}: invalid init '{}' in [Memory]", self.config_path, name))
+        })
+    }
+    // [Emulation] seed = <n>, a random seed is drawn when missing
+    fn config_seed(&self, conf: &Ini) -> Result<Option<u64>, EmuError> {
+        match conf.get_from(Some("Emulation"), "seed") {
+            Some(seed) => seed.parse::<u64>().map(Some).map_err(|_| {
+                EmuError::new(Component::Config, format!("{}: invalid seed '{}' in [Emulation]", self.config_path, seed))
+            }),
+            None => Ok(None),
+        }
+    }
+    pub fn init(&mut self) -> Result<(), EmuError> {
+        self.create_config_file()?;
+        let conf = Ini::load_from_file(&self.config_path)
+            .map_err(|e| EmuError::new(Component::Config, format!("Cannot read {}: {}", self.config_path, e)))?;
+        if let Some(sdl_context) = &self.sdl_context {
+            let renderer_scale = self.config_scale(&conf, "Display")?;
+            self.renderer = Some(Renderer::new(sdl_context, "NesEMU", renderer_scale));
+            let debugger_scale = self.config_scale(&conf, "Debugger")?;
+            self.debugger = Some(PpuDebugger::new(sdl_context, debugger_scale));
+        }
+        let seed = match self.seed {
+            Some(seed) => Some(seed),
+            None => self.config_seed(&conf)?,
+        };
+        let ram_init = self.config_ram_init(&conf)?;
+        self.power_on(seed, ram_init);
+        Ok(())
     }
-    pub fn init(&mut self) {
-        self.create_config_file();
-        let conf = Ini::load_from_file(&self.config_path).unwrap();
-        let renderer_scale = conf.section(Some("Display".to_owned())).unwrap().get("scale").unwrap().parse::<f32>().unwrap();
-        self.renderer = Some(Renderer::new(&self.sdl_context, "NesEMU", renderer_scale));
-        let debugger_scale = conf.section(Some("Debugger".to_owned())).unwrap().get("scale").unwrap().parse::<f32>().unwrap();
-        self.debugger = Some(PpuDebugger::new(&self.sdl_context, debugger_scale));
-        let mut cpu_bus = CpuBus::new(&mut self.cpu_ram, &mut self.rom, &mut self.ppu, &mut self.controller);
-        println!("CPU: Resetting");
-        self.cpu_cycle += self.cpu.reset(&mut cpu_bus) as u64;
+    pub fn power_on(&mut self, seed: Option<u64>, ram_init: RamInit) {
+        self.rng = StdRng::seed_from_u64(state::machine_seed(seed));
+        println!("RAM: Power on with {:?}", ram_init);
+        self.cpu_ram.power_on(ram_init, &mut self.rng);
+        self.ppu.power_on(self.rng.gen_range(0, 3));
+        let mut cpu_bus = CpuBus::new(&mut self.cpu_ram, &mut self.rom, &mut self.ppu, &mut self.controller, &mut self.irq);
+        println!("CPU: Power on");
+        self.cpu_cycle += self.cpu.power_on(&mut cpu_bus);
         println!("PPU: Initializing ...");
         self.ppu.init(&mut self.rom);
         println!("PPU: Initialized successfully");
     }
-    pub fn reset(&mut self) {
-        self.cpu_ram = Ram::new();
-        self.cpu_cycle = 0;
-        self.ppu_cycle = 0;
-        self.ppu.reset();
-        let mut cpu_bus = CpuBus::new(&mut self.cpu_ram, &mut self.rom, &mut self.ppu, &mut self.controller);
-        self.cpu.reset(&mut cpu_bus);
-        self.ppu.reset();
-        println!("PPU: Initializing ...");
-        self.ppu.init(&mut self.rom);
-        match &mut self.renderer {
-            Some(renderer) => renderer.reset(),
-            None => {}
-        }
+    // Reset button: RAM and most of the PPU state survive
+    pub fn soft_reset(&mut self) {
+        self.ppu.soft_reset();
+        let mut cpu_bus = CpuBus::new(&mut self.cpu_ram, &mut self.rom, &mut self.ppu, &mut self.controller, &mut self.irq);
+        println!("CPU: Reset");
+        self.cpu_cycle += self.cpu.soft_reset(&mut cpu_bus);
     }
 }
 
-fn main() -> Result<(), String> {
-    let args: Vec<String> = env::args().collect();
-    let mut ctx = Context::new(String::from(&args[1]));
-    ctx.init();
-    'main: loop {
-        'run: loop {
-            match ctx.run() {
-                s => {
-                    if s == EmulationStatus::ERROR || s == EmulationStatus::BREAK {
-                        break 'main;
-                    }
-                    if s == EmulationStatus::RESET {
-                        ctx.reset();
-                        break 'run;
-                    }
-                }
+fn main() {
+    if let Err(e) = start(env::args().collect()) {
+        eprintln!("{}", e);
+        process::exit(1);
+    }
+}
+
+fn start(args: Vec<String>) -> Result<(), EmuError> {
+    let usage = |message: String| EmuError::new(Component::Config, message);
+    if args.len() < 2 {
+        return Err(usage(String::from("Usage: nes_emu_rust <rom.nes> [--seed <n>] [--trace <file> ...] | --easy6502 <program> | --disasm <rom> [--entry <addr> ...]")));
+    }
+    let mut args = args;
+    let seed = take_seed(&mut args).map_err(usage)?;
+    // nes_emu_rust --easy6502 <program> [--headless] [--seed <n>]
+    if args.len() > 2 && args[1] == "--easy6502" {
+        return easy6502::run(&args[2], args[3..].iter().any(|a| a == "--headless"), seed);
+    }
+    // nes_emu_rust --disasm <rom> [output.s] [--entry <addr> ...]
+    if args.len() > 2 && args[1] == "--disasm" {
+        let entries = take_entries(&mut args).map_err(usage)?;
+        return disassemble(&args[2], args.get(3), &entries);
+    }
+    let trace = parse_trace_args(&args[2..]).map_err(usage)?;
+    let mut ctx = Context::new(String::from(&args[1]))?;
+    ctx.set_seed(seed);
+    if let Some((path, format, filter)) = trace {
+        ctx.set_tracer(Tracer::create(&path, format, filter)?);
+    }
+    ctx.init()?;
+    loop {
+        match ctx.run() {
+            EmulationStatus::ERROR(e) => {
+                println!("{}", ctx);
+                return Err(e);
             }
+            EmulationStatus::BREAK => break,
+            EmulationStatus::RESET => ctx.soft_reset(),
+            _ => {}
         }
     }
     println!("{}", ctx);
     Ok(())
 }
 
+// The fixed bank is mapped at the end of the address space, with the vectors.
+// Code is followed from the vectors, or from the given entries instead.
+// Without an output file the source is printed.
+fn disassemble(path: &str, output: Option<&String>, entries: &[u16]) -> Result<(), EmuError> {
+    let data = fs::read(path).map_err(|e| EmuError::new(Component::Cartridge, format!("Cannot open {}: {}", path, e)))?;
+    let mut rom = Cartbridge::new();
+    rom.load_program(&data)?;
+    let prg_size = (data[4] as usize * 0x4000).min(rom.get_program().len());
+    let program = &rom.get_program()[..prg_size];
+    let image = &program[prg_size - prg_size.min(0x8000)..];
+    let origin = (0x10000 - image.len()) as u16;
+    let disasm = if entries.is_empty() {
+        Disassembly::from_vectors(cpu::Variant::Ricoh2A03, image, origin)
+    } else {
+        Disassembly::from_entries(cpu::Variant::Ricoh2A03, image, origin, entries)
+    };
+    let source = disasm.to_ca65();
+    match output {
+        Some(output) => fs::write(output, source)
+            .map_err(|e| EmuError::new(Component::Frontend, format!("Cannot write {}: {}", output, e))),
+        None => {
+            print!("{}", source);
+            Ok(())
+        }
+    }
+}
+
+// --seed <n> makes the random behaviours reproducible, it is removed from the arguments
+fn take_seed(args: &mut Vec<String>) -> Result<Option<u64>, String> {
+    let i = match args.iter().position(|a| a == "--seed") {
+        Some(i) => i,
+        None => return Ok(None),
+    };
+    let value = args.get(i + 1).ok_or("Missing value for --seed")?;
+    let seed = value.parse().map_err(|_| format!("Invalid seed {}", value))?;
+    args.drain(i..i + 2);
+    Ok(Some(seed))
+}
+
+// --entry <addr> can be repeated, the entries are removed from the arguments
+fn take_entries(args: &mut Vec<String>) -> Result<Vec<u16>, String> {
+    let mut entries = Vec::new();
+    while let Some(i) = args.iter().position(|a| a == "--entry") {
+        let value = args.get(i + 1).ok_or("Missing value for --entry")?;
+        let entry = u16::from_str_radix(value.trim_start_matches('$'), 16)
+            .map_err(|_| format!("Invalid entry {}, expected e.g. C000", value))?;
+        entries.push(entry);
+        args.drain(i..i + 2);
+    }
+    Ok(entries)
+}
+
+// --trace <file> [--trace-format nintendulator|mesen|fceux] [--trace-pc <start>-<end>]
+// [--trace-after-frame <n>] [--trace-nmi] [--trace-max <lines>]
+fn parse_trace_args(args: &[String]) -> Result<Option<(String, TraceFormat, TraceFilter)>, String> {
+    let mut path = None;
+    let mut format = TraceFormat::Nintendulator;
+    let mut filter = TraceFilter::default();
+    let mut it = args.iter();
+    while let Some(arg) = it.next() {
+        let mut value = || it.next().ok_or(format!("Missing value for {}", arg));
+        match arg.as_str() {
+            "--trace" => path = Some(value()?.clone()),
+            "--trace-format" => {
+                let name = value()?;
+                format = TraceFormat::from_name(name).ok_or(format!("Unknown trace format {}", name))?;
+            }
+            "--trace-pc" => {
+                let range = value()?;
+                let bounds: Vec<Result<u16, _>> = range
+                    .split('-')
+                    .map(|a| u16::from_str_radix(a.trim_start_matches('$'), 16))
+                    .collect();
+                filter.pc_range = match bounds.as_slice() {
+                    [Ok(start), Ok(end)] => Some((*start, *end)),
+                    _ => return Err(format!("Invalid PC range {}, expected e.g. 8000-80FF", range)),
+                };
+            }
+            "--trace-after-frame" => {
+                let n = value()?;
+                filter.after_frame = n.parse().map_err(|_| format!("Invalid frame number {}", n))?;
+            }
+            "--trace-nmi" => filter.nmi_only = true,
+            "--trace-max" => {
+                let n = value()?;
+                filter.max_lines = Some(n.parse().map_err(|_| format!("Invalid line count {}", n))?);
+            }
+            _ => return Err(format!("Unknown argument {}", arg)),
+        }
+    }
+    Ok(path.map(|p| (p, format, filter)))
+}
 
 impl fmt::Display for Context {
     fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
         writeln!(f, "{}", self.cpu)?;
         writeln!(f, "{}", self.ppu)?;
         writeln!(f, "end cpu cycle : {}", self.cpu_cycle - 7)?;
+        writeln!(f, "state hash : {:016X}", self.state_hash())?;
         Ok(())
     }
-}
\ No newline at end of file
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{take_entries, Context};
+    use crate::cpu::memory::RamInit;
+    use std::thread;
+
+    // Hash at the end of each of the first frames of nestest.nes. The pixel buffers of the
+    // renderer and the debugger live in Context, too big for the stack of a test thread.
+    fn frame_hashes(seed: u64, ram_init: RamInit, frames: u64) -> Vec<u64> {
+        let run = move || {
+            let mut ctx = Context::headless(String::from("roms/nestest.nes")).unwrap();
+            ctx.power_on(Some(seed), ram_init);
+            let mut hashes = Vec::new();
+            while ctx.frame_hash().0 < frames {
+                let frame = ctx.frame_hash().0;
+                ctx.run();
+                let (n, hash) = ctx.frame_hash();
+                if n != frame {
+                    hashes.push(hash);
+                }
+            }
+            hashes
+        };
+        thread::Builder::new().stack_size(8 << 20).spawn(run).unwrap().join().unwrap()
+    }
+
+    #[test]
+    fn same_seed_should_give_the_same_hash_at_every_frame() {
+        let first = frame_hashes(42, RamInit::Random, 5);
+        let second = frame_hashes(42, RamInit::Random, 5);
+        assert_eq!(first.len(), 5);
+        for (n, (a, b)) in first.iter().zip(second.iter()).enumerate() {
+            assert_eq!(a, b, "hashes differ at frame {}", n + 1);
+        }
+    }
+    #[test]
+    fn different_seed_should_change_the_hash_with_random_ram() {
+        let first = frame_hashes(42, RamInit::Random, 1);
+        let second = frame_hashes(43, RamInit::Random, 1);
+        assert_ne!(first, second);
+    }
+    #[test]
+    fn entries_should_be_taken_from_the_arguments() {
+        let mut args: Vec<String> = ["emu", "--disasm", "--entry", "$C000", "game.nes", "--entry", "8010"]
+            .iter()
+            .map(|a| a.to_string())
+            .collect();
+        assert_eq!(take_entries(&mut args), Ok(vec![0xC000, 0x8010]));
+        assert_eq!(args, ["emu", "--disasm", "game.nes"]);
+        let mut args = vec![String::from("--entry"), String::from("xyz")];
+        assert!(take_entries(&mut args).is_err());
+    }
+}
@@ -0,0 +1,94 @@
+// CPU address space, decoded by page of 256 bytes. Devices implementing Handler are registered
+// for ranges of pages, each device decodes its own mirrors.
+pub trait Handler {
+    // open_bus is the last value on the data bus, for the bits the device does not drive
+    fn read(&mut self, addr: u16, open_bus: u8) -> u8;
+    fn write(&mut self, addr: u16, v: u8);
+    // Same as read, without side effects
+    fn inspect(&self, addr: u16, open_bus: u8) -> u8;
+}
+
+// Index of a registered handler, 0 is reserved for unmapped pages
+pub type HandlerId = u8;
+
+const SLOTS: usize = 8;
+
+pub struct MemoryMap<'a> {
+    pages: [HandlerId; 0x100],
+    handlers: [Option<&'a mut dyn Handler>; SLOTS],
+    registered: usize,
+}
+
+impl<'a> MemoryMap<'a> {
+    pub fn new() -> MemoryMap<'a> {
+        MemoryMap {
+            pages: [0; 0x100],
+            handlers: Default::default(),
+            registered: 0,
+        }
+    }
+    // Routes the pages from start to end included to handler, over any previous mapping
+    pub fn register(&mut self, start: u16, end: u16, handler: &'a mut dyn Handler) -> HandlerId {
+        let id = self.reserve(start, end);
+        self.handlers[id as usize - 1] = Some(handler);
+        id
+    }
+    // Same as register, for a device the owner of the map serves itself
+    pub fn reserve(&mut self, start: u16, end: u16) -> HandlerId {
+        assert!(self.registered < SLOTS, "No slot left in the memory map");
+        self.registered += 1;
+        let id = self.registered as HandlerId;
+        self.pages[(start >> 8) as usize..=(end >> 8) as usize].fill(id);
+        id
+    }
+    pub fn id(&self, addr: u16) -> Option<HandlerId> {
+        match self.pages[(addr >> 8) as usize] {
+            0 => None,
+            id => Some(id),
+        }
+    }
+    pub fn get(&self, addr: u16) -> Option<&(dyn Handler + 'a)> {
+        let id = self.id(addr)?;
+        self.handlers[id as usize - 1].as_deref()
+    }
+    pub fn handler(&mut self, addr: u16) -> Option<&mut (dyn Handler + 'a)> {
+        let id = self.id(addr)?;
+        self.handlers[id as usize - 1].as_deref_mut()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{Handler, MemoryMap};
+
+    struct Port(u8);
+
+    impl Handler for Port {
+        fn read(&mut self, _addr: u16, _open_bus: u8) -> u8 {
+            self.0
+        }
+        fn write(&mut self, _addr: u16, v: u8) {
+            self.0 = v;
+        }
+        fn inspect(&self, _addr: u16, _open_bus: u8) -> u8 {
+            self.0
+        }
+    }
+
+    #[test]
+    fn map_should_route_pages_to_registered_handlers() {
+        let (mut ram, mut io) = (Port(1), Port(2));
+        let mut map = MemoryMap::new();
+        let ram_id = map.register(0x0000, 0x1FFF, &mut ram);
+        let ppu_id = map.reserve(0x2000, 0x3FFF);
+        map.register(0x4000, 0x40FF, &mut io);
+        assert_eq!(map.id(0x07FF), Some(ram_id));
+        assert_eq!(map.id(0x3FFF), Some(ppu_id));
+        assert!(map.handler(0x2000).is_none());
+        assert_eq!(map.handler(0x1800).unwrap().read(0x1800, 0), 1);
+        map.handler(0x4016).unwrap().write(0x4016, 5);
+        assert_eq!(map.handler(0x40FF).unwrap().inspect(0x40FF, 0), 5);
+        assert_eq!(map.id(0x4100), None);
+        assert_eq!(map.id(0xFFFF), None);
+    }
+}
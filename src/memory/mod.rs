@@ -1,8 +1,12 @@
+pub mod map;
+
 pub trait Memory {
+    #[allow(dead_code)]
     fn get_size(&self) -> usize;
 
     fn peek(&self, i: u16) -> u8;
     fn write(&mut self, i: u16, value: u8) -> u8;
 
+    #[allow(dead_code)]
     fn get_mem(&self) -> &[u8];
 }
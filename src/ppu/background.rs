@@ -1,10 +1,8 @@
 use crate::ppu::mem::PpuMem;
-use crate::renderer::Renderer;
-use crate::renderer::get_rgb;
 use crate::ppu::register::PpuRegister;
 use crate::ppu::register::Register;
-use crate::ppu::palette::PaletteVram;
 
+#[derive(Hash)]
 pub struct Background {
     tiles_data: [u8; 16],
     nametable_addr: usize,
@@ -46,15 +44,9 @@ impl Background {
     pub fn fetch_nametable(&mut self, register: &mut PpuRegister) {
         self.nametable_addr = register.get_nametable_address() | register.get_addr() as usize & 0x0FFF;
     }
-    pub fn render(&mut self, dot: u32, line: u32, renderer: &mut Renderer, vram: &mut PpuMem, register: &mut PpuRegister) {
-        if register.get_background_visibility() == 0 {
-            return;
-        }
-        let rgb = get_rgb(vram.palette.peek_color_background(self.tiles_data[0]));
-        let transparent = get_rgb(vram.palette.peek_color_background(0));
-        if rgb != transparent {
-            renderer.set_pixel_rgb(dot - 2, line, rgb);
-        }
+    // Palette and colour of the pixel being output, colour 0 is transparent
+    pub fn pixel(&self) -> u8 {
+        self.tiles_data[0]
     }
     pub fn shit_tile_data(&mut self) {
         for i in 0..15 {
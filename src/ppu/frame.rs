@@ -0,0 +1,32 @@
+pub const FRAME_WIDTH: u32 = 256;
+pub const FRAME_HEIGHT: u32 = 240;
+
+pub struct Frame {
+    pixels: Vec<u8>,
+}
+
+impl Frame {
+    pub fn new() -> Frame {
+        Frame {
+            pixels: vec![0u8; (FRAME_WIDTH * FRAME_HEIGHT * 3) as usize],
+        }
+    }
+    pub fn set_pixel_rgb(&mut self, x: u32, y: u32, color: (u8, u8, u8)) {
+        if x >= FRAME_WIDTH || y >= FRAME_HEIGHT {
+            return;
+        }
+        let coords = ((x + FRAME_WIDTH * y) * 3) as usize;
+        self.pixels[coords] = color.0;
+        self.pixels[coords + 1] = color.1;
+        self.pixels[coords + 2] = color.2;
+    }
+    pub fn get_line(&self, y: u32) -> &[u8] {
+        let start = (y * FRAME_WIDTH * 3) as usize;
+        &self.pixels[start..start + (FRAME_WIDTH * 3) as usize]
+    }
+    pub fn clear(&mut self) {
+        for p in self.pixels.iter_mut() {
+            *p = 0;
+        }
+    }
+}
@@ -4,6 +4,7 @@ use crate::ppu::sprite::SpriteMem;
 
 use std::fmt;
 
+#[derive(Hash)]
 pub struct PpuMem {
     vram: [u8; 0x4000],
     pub palette: Palette,
@@ -21,23 +22,26 @@ impl PpuMem {
         }
     }
 
+    // The address bus of the PPU is 14 bits wide
     pub fn peek(&self, i: usize) -> u8 {
+        let i = i & 0x3FFF;
         match i {
             0x2000..=0x23FF => self.nametable[i - 0x2000],
             0x2400..=0x27FF => self.nametable[i - 0x2400],
             0x2800..=0x2BFF => self.nametable[i - 0x2800],
             0x2C00..=0x2FFF => self.nametable[i - 0x2C00],
+            0x3F00..=0x3FFF => self.palette.peek(i),
             _ => self.vram[i]
         }
     }
     pub fn write(&mut self, i: usize, value: u8) -> u8 {
+        let i = i & 0x3FFF;
         match i {
             0x2000..=0x23FF => self.nametable[i - 0x2000] = value,
             0x2400..=0x27FF => self.nametable[i - 0x2400] = value,
             0x2800..=0x2BFF => self.nametable[i - 0x2800] = value,
             0x2C00..=0x2FFF => self.nametable[i - 0x2C00] = value,
-            0x3F00..=0x3F0F => self.palette.write_background(value),
-            0x3F10..=0x3F1F => self.palette.write_sprite(i, value),
+            0x3F00..=0x3FFF => self.palette.write(i, value),
             _ => self.vram[i] = value,
         };
         value
@@ -1,32 +1,36 @@
 pub mod background;
 pub mod colors;
+pub mod frame;
 pub mod mem;
 pub mod sprite;
 pub mod palette;
 pub mod register;
 pub mod tile;
 
-use crate::cpu::memory::Ram;
+use crate::memory::map::Handler;
 use crate::ppu::background::Background;
+use crate::ppu::frame::Frame;
 use crate::ppu::mem::PpuMem;
 use crate::renderer::get_rgb;
 #[allow(unused_imports)]
 use crate::ppu::register::Register;
 use crate::ppu::register::PpuRegister;
+use crate::ppu::sprite::Sprites;
 #[allow(unused_imports)]
 use crate::ppu::palette::PaletteVram;
+use crate::ppu::palette::emphasize;
+use crate::ppu::colors::COLORS;
 use crate::ppu::tile::Tile;
-use crate::renderer::Renderer;
 use crate::rom::Cartbridge;
 
 use std::boxed::Box;
 use std::fmt;
+use std::hash::{Hash, Hasher};
 
 #[derive(PartialEq)]
 pub enum PpuStatus {
     PROCESSING,
     RENDERING,
-    INTERRUPTNMI,
 }
 
 const CYCLE_PER_LINE: i16 = 341;
@@ -35,43 +39,64 @@ pub struct Ppu {
     pub register: PpuRegister,
     pub mem: PpuMem,
     pub background: Background,
+    pub sprites: Sprites,
     pub dot: i16,
     pub line: i16,
     pub tileset: Box<Vec<Tile>>,
+    pub frame: Frame,
     updated: bool,
-    oam_addr: u8,
+    // $2002 was read right before vblank, the flag is not set for this frame
+    vbl_suppressed: bool,
+    // Level of /NMI seen by the CPU at its last poll
+    nmi_polled: bool,
+    odd_frame: bool,
 }
 
 impl Ppu {
     pub fn new() -> Ppu {
         Ppu {
             background: Background::new(),
+            sprites: Sprites::new(),
             register: PpuRegister::new(),
             mem: PpuMem::new(),
             tileset: Box::new(Vec::new()),
-            dot: 0,
+            frame: Frame::new(),
+            dot: -1,
             line: 0,
             updated: false,
-            oam_addr: 0,
+            vbl_suppressed: false,
+            nmi_polled: false,
+            odd_frame: false,
         }
     }
     pub fn peek(&mut self, i: u16) -> u8 {
+        // The flag is set at dot 1: a read one dot earlier sees it clear and cancels it
+        if i == 0x2002 && self.line == 241 && self.dot == 0 {
+            self.vbl_suppressed = true;
+        }
         self.register.peek(i, &mut self.mem)
     }
+    // /NMI output: active while vblank is set and enabled in PPUCTRL
+    pub fn nmi_line(&self) -> bool {
+        self.register.get_status() & 0x80 != 0 && self.register.get_nmi_enable() == 1
+    }
+    // The CPU only reacts to edges: vblank starting with NMI enabled, or NMI enabled during vblank
+    pub fn poll_nmi(&mut self) -> bool {
+        let line = self.nmi_line();
+        let edge = line && !self.nmi_polled;
+        self.nmi_polled = line;
+        edge
+    }
     pub fn write(&mut self, i: u16, v: u8) -> u8 {
         self.updated = true;
         self.register.write(i, v, &mut self.mem)
     }
-    pub fn write_dma(&mut self, v: u8, ram: &mut Ram) -> u8 {
-        self.updated = true;
-        self.register.write_oam_data(v, &mut self.mem, ram);
-        v
-    }
     pub fn init(&mut self, rom: &mut Cartbridge) {
         self.mem.set_cram(rom.get_character().to_vec());
         println!("PPU: CRAM OK");
+        // Both pattern tables, tiles of the second one start at 256
         let mut i = 0;
-        while i < 0x1000 {
+        while i < 0x2000 {
             let mut v = [0; 16];
             for j in 0..16 {
                 v[j] = self.mem.peek(i as usize);
@@ -83,10 +108,19 @@ impl Ppu {
         }
         println!("PPU: Tileset OK");
     }
-    pub fn reset(&mut self) {
-        self.mem = PpuMem::new();
+    // The PPU starts 0 to 2 dots ahead of the CPU depending on how the clock dividers come up
+    pub fn power_on(&mut self, phase: u8) {
         self.register = PpuRegister::new();
-        self.tileset = Box::new(Vec::new());
+        self.soft_reset();
+        self.dot += phase as i16;
+    }
+    // VRAM, OAM and the palettes are kept, rendering restarts from the top of the frame
+    pub fn soft_reset(&mut self) {
+        self.register.reset();
+        self.dot = -1;
+        self.line = 0;
+        self.frame.clear();
+        self.updated = true;
     }
     fn increment_y(&mut self) {
         let mut addr = self.register.get_addr();
@@ -125,14 +159,15 @@ impl Ppu {
         let temp_addr = self.register.get_temp_addr();
         self.register.set_addr_plain((addr & 0x841F) | temp_addr & 0x7BE0);
     }
-    pub fn run(&mut self, renderer: &mut Renderer) -> PpuStatus {
+    pub fn run(&mut self) -> PpuStatus {
         let mut current_status = PpuStatus::PROCESSING;
         if self.line == -1 && self.dot == -1{
             self.background.clear_data();
         }
         self.dot += 1;
-        if self.register.get_background_visibility() == 1 && self.register.get_sprite_visibility() == 1 {
-            if self.line >= 1 && (self.dot >= 1 && self.dot <= 256 || self.dot >= 321 && self.dot <= 336) {
+        let rendering = self.register.get_background_visibility() == 1 || self.register.get_sprite_visibility() == 1;
+        if rendering {
+            if self.line < 240 && (self.dot >= 1 && self.dot <= 256 || self.dot >= 321 && self.dot <= 336) {
                 match self.dot % 8 {
                     0 => self.background.store_tile_data(),
                     1 => self.background.fetch_nametable(&mut self.register),
@@ -142,10 +177,10 @@ impl Ppu {
                     _ => {}
                 }
             }
-            if self.line == 261 && self.dot >= 280 && self.dot <= 304 {
+            if self.line == -1 && self.dot >= 280 && self.dot <= 304 {
                 self.copy_y();
             }
-            if self.line == 261 || self.line < 240 && self.register.get_background_visibility() == 1 {
+            if self.line == -1 || self.line < 240 && self.register.get_background_visibility() == 1 {
                 if self.dot % 8 == 0 && (self.dot >= 1 && self.dot <= 256 || self.dot >= 321 && self.dot <= 336) {
                     self.increment_x();
                 }
@@ -156,68 +191,120 @@ impl Ppu {
                     self.copy_x();
                 }
             }
-            // sprite logic
-            if self.line >= 1 && self.line <= 239 && self.dot == 65 {
-                //self.mem.spr_mem.clear_secondary();
-                self.oam_addr = 0;
-            }
-            if self.line >= 1 && self.line <= 239 && self.dot >= 66 && self.dot <= 256 && self.oam_addr < 255 {
-                // Read Y coordinate
-                /*let oam_data = self.mem.spr_mem.get_oam()[self.oam_addr as usize];
-                self.mem.spr_mem.push_secondary(oam_data);
-                if y != 0 {
-                    println!("Not empty sprite");
-                }
-                if !self.mem.spr_mem.is_secondary_full() {
-                    if self.oam_addr >= 64 {
-                        self.oam_addr += 4;
-                    } else {
-                        self.oam_addr += 1;
-                    }
-                }*/
-            }
             if self.dot >= 1 && self.dot <= 256 && self.line < 240 {
                 self.background.shit_tile_data();
-                self.background.render(self.dot as u32, self.line as u32, renderer, &mut self.mem, &mut self.register);
-            }
-            if self.line >= 1 && self.line <= 239 && self.dot >= 257 && self.dot <= 320 {
-                let spr_row = (self.dot - 257) / 8;
-                let spr_line = self.line / 8;
-                let spr_number = spr_row * 4 + spr_line as i16 * 4;
-                let y = self.mem.spr_mem.get_oam()[spr_number as usize];
-                let index = self.mem.spr_mem.get_oam()[(spr_number) as usize + 1];
-                let attr = self.mem.spr_mem.get_oam()[(spr_number) as usize + 2];
-                let hflip = attr & 64;
-                let x = self.mem.spr_mem.get_oam()[(spr_number) as usize + 3];
-                let xoffset = ((self.dot + 8 - 257) % ((spr_row + 1) * 8)) as u32;
-                let yoffset = ((self.line + 8) % ((spr_line+1)*8)) as u32;
-                let patternlow = self.mem.peek(((index as u32*16) + yoffset) as usize) << xoffset;
-                let patternhi = self.mem.peek(((index as u32*16) + yoffset + 8) as usize) << xoffset;
-                let low = (patternlow & 0x80) >> 7;
-                let hi = (patternhi & 0x80) >> 6;
-                let pixel = low | hi;
-                let color = get_rgb(self.mem.palette.peek_color_sprite(attr & 3, pixel));
-                renderer.set_pixel_rgb(x as u32 + xoffset, y as u32 + yoffset, color);
             }
+            self.run_sprites();
+        }
+        if self.line >= 0 && self.line < 240 && self.dot >= 2 && self.dot <= 257 {
+            self.output_pixel((self.dot - 2) as u8);
+        }
+        // Pre-render line
+        if self.line == -1 && self.dot == 1 {
+            self.register.clear_vblank();
+            self.register.clear_spritehit();
+            self.register.clear_sprite_overflow();
         }
         if self.line == 241 && self.dot == 1 {
-            self.register.set_vblank();
-            if self.register.get_nmi_enable() == 0x1 {
-                return PpuStatus::INTERRUPTNMI;
+            if !self.vbl_suppressed {
+                self.register.set_vblank();
             }
+            self.vbl_suppressed = false;
         }
-        if self.dot >= CYCLE_PER_LINE {
+        // Dots 0 to 340 have been run, move to the next line. With rendering on, the pre-render
+        // line of odd frames is one dot shorter.
+        let skipped = self.line == -1 && self.odd_frame && rendering;
+        if self.dot >= CYCLE_PER_LINE - 1 || skipped && self.dot == CYCLE_PER_LINE - 2 {
             self.dot = -1;
             self.line += 1;
-            if self.line >= 262 {
+            // Lines 0 to 239 are visible, 240 to 260 are idle then vblank, -1 is the pre-render line
+            if self.line >= 261 {
                 self.line = -1;
-                self.register.clear_vblank();
-                self.register.clear_spritehit();
+                self.odd_frame = !self.odd_frame;
+                self.register.end_warm_up();
                 current_status = PpuStatus::RENDERING;
             }
         }
         current_status
     }
+    // Sprites drawn on a line are evaluated and fetched on the line before,
+    // the pre-render line fetches none
+    fn run_sprites(&mut self) {
+        let visible = self.line >= 0 && self.line < 240;
+        if visible && self.dot >= 2 && self.dot <= 257 {
+            let x = (self.dot - 2) as u8;
+            if self.is_sprite_zero_hit(x) {
+                self.register.set_spritehit();
+            }
+        }
+        if self.line == -1 && self.dot == 257 {
+            self.sprites.skip_evaluation();
+        }
+        match self.dot {
+            1..=64 if visible => self.sprites.clear_secondary(&mut self.mem.spr_mem, self.dot),
+            65 if visible => self.sprites.start_evaluation(),
+            65..=256 if visible && self.dot % 2 == 1 => {
+                let height = self.sprite_height();
+                let overflow = self.sprites.evaluate(&mut self.mem.spr_mem, self.line, height);
+                if overflow {
+                    self.register.set_sprite_overflow();
+                }
+            }
+            257..=320 if visible || self.line == -1 => {
+                // OAMADDR is cleared during the sprite fetches
+                self.register.set_oam_addr(0);
+                let (table, height) = (self.sprite_table(), self.sprite_height());
+                self.sprites.fetch(&self.mem, self.line, self.dot, table, height);
+            }
+            _ => {}
+        }
+    }
+    // Layers hidden by PPUMASK, or in the leftmost 8 pixels, are transparent
+    fn output_pixel(&mut self, x: u8) {
+        let register = &self.register;
+        let background = if register.get_background_visibility() == 1 && (x >= 8 || register.get_background_clipping() == 1) {
+            self.background.pixel()
+        } else {
+            0
+        };
+        let sprite = if register.get_sprite_visibility() == 1 && (x >= 8 || register.get_sprite_clipping() == 1) {
+            self.sprites.pixel(x)
+        } else {
+            None
+        };
+        // Palette RAM is read on every pixel, mid-frame writes show from the next one
+        let mut color = self.mem.palette.peek(multiplex(background, sprite) as usize);
+        if register.get_greyscale() == 1 {
+            color &= 0x30;
+        }
+        let rgb = emphasize(COLORS[color as usize], register.get_emphasis());
+        self.frame.set_pixel_rgb(x as u32, self.line as u32, get_rgb(rgb));
+    }
+    // Pattern table of 8x8 sprites
+    pub fn sprite_table(&self) -> u8 {
+        self.register.get_sprite_table()
+    }
+    // 8 or 16 lines, selected in PPUCTRL
+    pub fn sprite_height(&self) -> u8 {
+        8 << self.register.get_sprite_size()
+    }
+    // Opaque pixels of sprite 0 and of the background at x. Never at x=255, nor in the
+    // leftmost 8 pixels when one of the layers is hidden there.
+    fn is_sprite_zero_hit(&self, x: u8) -> bool {
+        let register = &self.register;
+        let clipped = x < 8 && (register.get_background_clipping() == 0 || register.get_sprite_clipping() == 0);
+        register.get_background_visibility() == 1
+            && register.get_sprite_visibility() == 1
+            && register.get_status() & 0x40 == 0
+            && x != 255
+            && !clipped
+            && self.background.pixel() & 3 != 0
+            && self.sprites.zero_pixel(x)
+    }
+    // Current scanline and number of dots already run on it
+    pub fn position(&self) -> (i16, i16) {
+        (self.line, self.dot + 1)
+    }
     pub fn has_been_updated(&mut self) -> bool {
         self.updated
     }
@@ -229,6 +316,47 @@ impl Ppu {
     }
 }
 
+// Palette index of a pixel from the background and the first opaque sprite at its position.
+// Colour 0 is transparent in both layers and shows the backdrop. The sprite priority bit is
+// only checked after the sprite was selected: a sprite behind the background still hides the
+// sprites after it in OAM.
+pub fn multiplex(background: u8, sprite: Option<(u8, bool)>) -> u8 {
+    let opaque = background & 3 != 0;
+    match sprite {
+        Some((index, behind)) if !opaque || !behind => index,
+        _ if opaque => background,
+        _ => 0,
+    }
+}
+
+// The 8 registers are mirrored every 8 bytes up to $3FFF
+impl Handler for Ppu {
+    fn read(&mut self, addr: u16, _open_bus: u8) -> u8 {
+        self.peek(0x2000 + (addr & 0x07))
+    }
+    fn write(&mut self, addr: u16, v: u8) {
+        Ppu::write(self, 0x2000 + (addr & 0x07), v);
+    }
+    fn inspect(&self, addr: u16, _open_bus: u8) -> u8 {
+        self.register.inspect(0x2000 + (addr & 0x07), &self.mem)
+    }
+}
+
+// The frame buffer and the tileset are derived from the state, they are left out
+impl Hash for Ppu {
+    fn hash<H: Hasher>(&self, state: &mut H) {
+        self.register.hash(state);
+        self.mem.hash(state);
+        self.background.hash(state);
+        self.dot.hash(state);
+        self.line.hash(state);
+        self.sprites.hash(state);
+        self.vbl_suppressed.hash(state);
+        self.nmi_polled.hash(state);
+        self.odd_frame.hash(state);
+    }
+}
+
 impl fmt::Display for Ppu {
     fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
         writeln!(f, "{}", self.mem)?;
@@ -238,3 +366,157 @@ impl fmt::Display for Ppu {
         Ok(())
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::{multiplex, Ppu, PpuStatus};
+    use crate::ppu::colors::COLORS;
+    use crate::ppu::register::Register;
+    use crate::renderer::get_rgb;
+
+    // Tile 1 is opaque, it fills the background and is used by sprite 0
+    fn ppu_with_sprite_zero(x: u8, y: u8, mask: u8) -> Ppu {
+        let mut ppu = Ppu::new();
+        for i in 0x0010..0x0020 {
+            ppu.mem.write(i, 0xFF);
+        }
+        for i in 0x2000..0x23C0 {
+            ppu.mem.write(i, 0x01);
+        }
+        for (i, v) in [y, 0x01, 0x00, x].iter().enumerate() {
+            ppu.mem.write_sprite_data(i, *v);
+        }
+        ppu.write(0x2001, mask);
+        ppu
+    }
+    // Line and x of the first pixel with the sprite 0 hit flag set
+    fn first_hit(ppu: &mut Ppu) -> Option<(i16, i16)> {
+        for _ in 0..262 * 341 {
+            ppu.run();
+            if ppu.register.get_status() & 0x40 != 0 {
+                return Some((ppu.line, ppu.dot - 2));
+            }
+        }
+        None
+    }
+
+    #[test]
+    fn sprite_zero_hit_should_be_set_on_the_first_overlapping_pixel() {
+        let mut ppu = ppu_with_sprite_zero(20, 10, 0x1E);
+        assert_eq!(first_hit(&mut ppu), Some((11, 20)));
+    }
+    #[test]
+    fn sprite_zero_hit_should_honour_left_clipping() {
+        assert_eq!(first_hit(&mut ppu_with_sprite_zero(4, 10, 0x18)), Some((11, 8)));
+        assert_eq!(first_hit(&mut ppu_with_sprite_zero(0, 10, 0x18)), None);
+        assert_eq!(first_hit(&mut ppu_with_sprite_zero(0, 10, 0x1C)), None);
+        assert_eq!(first_hit(&mut ppu_with_sprite_zero(0, 10, 0x1E)), Some((11, 0)));
+    }
+    #[test]
+    fn sprite_zero_hit_should_never_happen_at_x_255() {
+        assert_eq!(first_hit(&mut ppu_with_sprite_zero(255, 10, 0x1E)), None);
+    }
+    #[test]
+    fn sprite_zero_hit_should_be_cleared_on_the_pre_render_line() {
+        let mut ppu = ppu_with_sprite_zero(20, 10, 0x1E);
+        first_hit(&mut ppu);
+        while !(ppu.line == -1 && ppu.dot == 1) {
+            assert_eq!(ppu.register.get_status() & 0x40, 0x40);
+            ppu.run();
+        }
+        assert_eq!(ppu.register.get_status() & 0x40, 0x00);
+    }
+    #[test]
+    fn multiplex_should_follow_sprite_priority() {
+        // Transparent background: the sprite shows whatever its priority
+        assert_eq!(multiplex(0x04, Some((0x11, true))), 0x11);
+        assert_eq!(multiplex(0x04, Some((0x11, false))), 0x11);
+        // Opaque background: only sprites in front show
+        assert_eq!(multiplex(0x06, Some((0x11, false))), 0x11);
+        assert_eq!(multiplex(0x06, Some((0x11, true))), 0x06);
+        assert_eq!(multiplex(0x06, None), 0x06);
+        // Both transparent: backdrop
+        assert_eq!(multiplex(0x0C, None), 0x00);
+    }
+    #[test]
+    fn sprite_behind_the_background_should_hide_later_sprites() {
+        let mut ppu = ppu_with_sprite_zero(20, 10, 0x1E);
+        // Sprite 0 is behind the background, sprite 1 in front at the same place
+        ppu.mem.write_sprite_data(2, 0x20);
+        for (i, v) in [10, 0x01, 0x01, 20].iter().enumerate() {
+            ppu.mem.write_sprite_data(4 + i, *v);
+        }
+        while !(ppu.line == 11 && ppu.dot == 22) {
+            ppu.run();
+        }
+        assert_eq!(ppu.sprites.pixel(20), Some((0x13, true)));
+        assert_eq!(multiplex(ppu.background.pixel(), ppu.sprites.pixel(20)), ppu.background.pixel());
+    }
+    #[test]
+    fn palette_reads_should_bypass_the_buffer() {
+        let mut ppu = Ppu::new();
+        ppu.mem.write(0x2F00, 0x55);
+        ppu.write(0x2006, 0x3F);
+        ppu.write(0x2006, 0x10);
+        ppu.write(0x2007, 0x21);
+        ppu.write(0x2006, 0x3F);
+        ppu.write(0x2006, 0x00);
+        assert_eq!(ppu.peek(0x2007), 0x21);
+        // The buffer holds the nametable byte under the palette
+        ppu.write(0x2006, 0x00);
+        ppu.write(0x2006, 0x00);
+        assert_eq!(ppu.peek(0x2007), 0x55);
+    }
+    // Color output at x=100 on line, away from sprite 0
+    fn run_to_line(ppu: &mut Ppu, line: i16) -> (u8, u8, u8) {
+        while !(ppu.line == line && ppu.dot == 257) {
+            ppu.run();
+        }
+        let pixel = &ppu.frame.get_line(line as u32)[300..303];
+        (pixel[0], pixel[1], pixel[2])
+    }
+    #[test]
+    fn palette_changes_should_show_mid_frame() {
+        let mut ppu = ppu_with_sprite_zero(20, 10, 0x1E);
+        ppu.mem.write(0x3F03, 0x16);
+        assert_eq!(run_to_line(&mut ppu, 10), get_rgb(COLORS[0x16]));
+        ppu.mem.write(0x3F03, 0x2A);
+        assert_eq!(run_to_line(&mut ppu, 20), get_rgb(COLORS[0x2A]));
+        // Greyscale keeps the brightness of the color
+        ppu.write(0x2001, 0x1F);
+        assert_eq!(run_to_line(&mut ppu, 30), get_rgb(COLORS[0x20]));
+    }
+    // Dots run until the end of the frame
+    fn frame_length(ppu: &mut Ppu) -> usize {
+        let mut dots = 1;
+        while ppu.run() != PpuStatus::RENDERING {
+            dots += 1;
+        }
+        dots
+    }
+    #[test]
+    fn frame_should_last_262_lines() {
+        let mut ppu = Ppu::new();
+        frame_length(&mut ppu);
+        assert_eq!(frame_length(&mut ppu), 262 * 341);
+        assert_eq!(frame_length(&mut ppu), 262 * 341);
+        // Rendering skips the last dot of the pre-render line on odd frames
+        ppu.write(0x2001, 0x08);
+        let lengths = [frame_length(&mut ppu), frame_length(&mut ppu)];
+        assert!(lengths.contains(&(262 * 341)) && lengths.contains(&(262 * 341 - 1)));
+    }
+    #[test]
+    fn vblank_should_be_set_at_line_241_dot_1() {
+        let mut ppu = Ppu::new();
+        for _ in 0..2 {
+            while ppu.register.get_status() & 0x80 == 0 {
+                ppu.run();
+            }
+            assert_eq!((ppu.line, ppu.dot), (241, 1));
+            while ppu.register.get_status() & 0x80 != 0 {
+                ppu.run();
+            }
+            assert_eq!((ppu.line, ppu.dot), (-1, 1));
+        }
+    }
+}
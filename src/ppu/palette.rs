@@ -2,67 +2,108 @@ use crate::ppu::colors::COLORS;
 
 use std::fmt;
 
+// Palette RAM holds indices in the 64 colors of the NES, the colors are looked up when read
 pub trait PaletteVram {
-    fn write_background(&mut self, v: u8);
-    fn peek_color_background(&self, v: u8) -> u32;
+    fn peek(&self, i: usize) -> u8;
+    fn write(&mut self, i: usize, v: u8);
 
-    fn write_sprite(&mut self, i: usize, v: u8);
+    fn peek_color_background(&self, v: u8) -> u32;
     fn peek_color_sprite(&self, index: u8, v: u8) -> u32;
 }
 
+// 32 entries mirrored through $3F00-$3FFF: 4 background palettes then 4 sprite palettes
+#[derive(Hash)]
 pub struct Palette {
-    pub background: Vec<u32>,
-    pub sprite: [u32;16],
+    ram: [u8; 32],
 }
 
 impl Palette {
     pub fn new() -> Palette {
         Palette {
-            background: vec!(),
-            sprite: [0;16],
+            ram: [0; 32],
         }
     }
 }
 
-impl PaletteVram for Palette {
-    fn write_background(&mut self, v: u8) {
-        self.background.push(COLORS[v as usize]);
+// $3F10, $3F14, $3F18 and $3F1C are mirrors of $3F00, $3F04, $3F08 and $3F0C
+fn mirror(i: usize) -> usize {
+    let i = i & 0x1F;
+    if i & 0x13 == 0x10 {
+        i & 0x0F
+    } else {
+        i
     }
-    fn peek_color_background(&self, v: u8) -> u32 {
-        if self.background.len() > v as usize {
-            self.background[v as usize]
-        } else {
-            0
+}
+
+// Emphasis bits are red, green and blue from bit 0. Emphasizing a color darkens the two others.
+pub fn emphasize(rgb: u32, emphasis: u8) -> u32 {
+    if emphasis == 0 {
+        return rgb;
+    }
+    (0..3).fold(0, |acc, c| {
+        let shift = 16 - 8 * c;
+        let mut v = (rgb >> shift) & 0xFF;
+        if emphasis & !(1 << c) != 0 {
+            v = v * 3 / 4;
         }
+        acc | v << shift
+    })
+}
+
+impl PaletteVram for Palette {
+    fn peek(&self, i: usize) -> u8 {
+        self.ram[mirror(i)]
+    }
+    // Entries are 6 bits wide
+    fn write(&mut self, i: usize, v: u8) {
+        self.ram[mirror(i)] = v & 0x3F;
     }
-    fn write_sprite(&mut self, i: usize, v: u8) {
-        self.sprite[i - 0x3F10] = COLORS[v as usize];
+    fn peek_color_background(&self, v: u8) -> u32 {
+        COLORS[self.peek(v as usize) as usize]
     }
     fn peek_color_sprite(&self, index: u8, v: u8) -> u32 {
-        self.sprite[(index as usize * 4) + v as usize]
-        /*
-        if self.sprite.len() > v as usize {
-            self.sprite[v as usize]
-        } else {
-            0
-        }*/
+        COLORS[self.peek(0x10 | (index as usize) << 2 | v as usize) as usize]
     }
 }
 
 impl fmt::Display for Palette {
     fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
-        if self.background.len() > 0 {
-            writeln!(f, "Images")?;
-            for v in self.background.iter() {
-                write!(f, "#{:06x?}   ", v)?;
-            }
+        writeln!(f, "Images")?;
+        for i in 0x00..0x10 {
+            write!(f, "{:02x?} ", self.peek(i))?;
         }
-        if self.sprite.len() > 0 {
-            writeln!(f, "\nSprites")?;
-            for v in self.sprite.iter() {
-                write!(f, "#{:06x?}   ", v)?;
-            }
+        writeln!(f, "\nSprites")?;
+        for i in 0x10..0x20 {
+            write!(f, "{:02x?} ", self.peek(i))?;
         }
         Ok(())
     }
-}
\ No newline at end of file
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{emphasize, Palette, PaletteVram};
+
+    #[test]
+    fn sprite_backdrops_should_mirror_background_entries() {
+        let mut palette = Palette::new();
+        palette.write(0x3F10, 0x21);
+        palette.write(0x3F1C, 0x0F);
+        palette.write(0x3F15, 0x16);
+        assert_eq!(palette.peek(0x3F00), 0x21);
+        assert_eq!(palette.peek(0x3F0C), 0x0F);
+        assert_eq!(palette.peek(0x3F05), 0x00);
+        assert_eq!(palette.peek(0x3F15), 0x16);
+        // 32 bytes mirrored up to $3FFF, 6 bits each
+        palette.write(0x3FE1, 0xFF);
+        assert_eq!(palette.peek(0x3F01), 0x3F);
+    }
+    #[test]
+    fn emphasis_should_darken_the_other_colors() {
+        assert_eq!(emphasize(0x808080, 0), 0x808080);
+        assert_eq!(emphasize(0x808080, 1), 0x806060);
+        assert_eq!(emphasize(0x808080, 2), 0x608060);
+        assert_eq!(emphasize(0x808080, 6), 0x606060);
+        assert_eq!(emphasize(0x808080, 7), 0x606060);
+    }
+}
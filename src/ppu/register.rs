@@ -1,5 +1,3 @@
-use crate::cpu::memory::Ram;
-use crate::memory::Memory;
 use crate::ppu::mem::PpuMem;
 
 use std::fmt;
@@ -20,6 +18,8 @@ pub trait Register {
     fn get_sprite_visibility(&self) -> u8;
     fn get_sprite_clipping(&self) -> u8;
     fn get_background_clipping(&self) -> u8;
+    fn get_greyscale(&self) -> u8;
+    fn get_emphasis(&self) -> u8;
     fn get_ctrl_one(&self) -> u8;
 
     // $2002
@@ -34,13 +34,16 @@ pub trait Register {
     fn get_oam_dma(&self) -> u8;
     fn clear_vblank(&mut self) -> &mut Self;
     fn set_vblank(&mut self) -> &mut Self;
+    fn set_spritehit(&mut self) -> &mut Self;
     fn clear_spritehit(&mut self) -> &mut Self;
+    fn set_sprite_overflow(&mut self) -> &mut Self;
+    fn clear_sprite_overflow(&mut self) -> &mut Self;
 
     fn set_ctrl_zero(&mut self, v: u8) -> &mut Self;
     fn set_ctrl_one(&mut self, v: u8) -> &mut Self;
     fn set_status(&mut self, v: u8) -> &mut Self;
     fn set_oam_addr(&mut self, v: u8) -> &mut Self;
-    fn write_oam_data(&mut self, v: u8, mem: &mut PpuMem, ram: &mut Ram) -> &mut Self;
+    fn write_oam_data(&mut self, v: u8, mem: &mut PpuMem) -> &mut Self;
     fn set_scroll(&mut self, v: u8) -> &mut Self;
     fn set_addr(&mut self, v: u16) -> &mut Self;
     fn set_addr_plain(&mut self, v: u16) -> &mut Self;
@@ -50,11 +53,13 @@ pub trait Register {
     fn incr_addr(&mut self) -> &mut Self;
 
     fn peek(&mut self, i: u16, mem: &mut PpuMem) -> u8;
+    fn inspect(&self, i: u16, mem: &PpuMem) -> u8;
     fn write(&mut self, i: u16, v: u8, mem: &mut PpuMem) -> u8;
 
     fn get_r_fine_scroll_x(&self) -> u16;
 }
 
+#[derive(Hash)]
 pub struct PpuRegister {
     r_ctrl_zero: u8,
     r_ctrl_one: u8,
@@ -69,6 +74,11 @@ pub struct PpuRegister {
     r_writing_lower_addr: bool,
     r_fine_scroll_x: u16,
     r_data_buffer: u8,
+    // Last value seen on the data bus between the CPU and the PPU
+    r_io_latch: u8,
+    // After power on and reset, writes to $2000, $2001, $2005 and $2006 are ignored
+    // until the end of the first vblank
+    r_warm_up: bool,
 }
 
 impl PpuRegister {
@@ -87,8 +97,23 @@ impl PpuRegister {
             r_writing_lower_addr: false,
             r_fine_scroll_x: 0,
             r_data_buffer: 0,
+            r_io_latch: 0,
+            r_warm_up: false,
         }
     }
+    // Reset line: the status, OAM and VRAM addresses are kept
+    pub fn reset(&mut self) {
+        self.r_ctrl_zero = 0x00;
+        self.r_ctrl_one = 0x00;
+        self.r_writing_lower_addr = false;
+        self.r_t_addr = 0x00;
+        self.r_fine_scroll_x = 0;
+        self.r_data_buffer = 0;
+        self.r_warm_up = true;
+    }
+    pub fn end_warm_up(&mut self) {
+        self.r_warm_up = false;
+    }
 }
 
 impl Register for PpuRegister {
@@ -129,13 +154,21 @@ impl Register for PpuRegister {
         (self.r_ctrl_one >> 3) & 0x01
     }
     fn get_sprite_visibility(&self) -> u8 {
-        (self.r_ctrl_one >> 2) & 0x01
+        (self.r_ctrl_one >> 4) & 0x01
     }
+    // 1 when the layer is shown in the leftmost 8 pixels
     fn get_sprite_clipping(&self) -> u8 {
-        (self.r_ctrl_one >> 1) & 0x01
+        (self.r_ctrl_one >> 2) & 0x01
     }
     fn get_background_clipping(&self) -> u8 {
-        (self.r_ctrl_one >> 0) & 0x01
+        (self.r_ctrl_one >> 1) & 0x01
+    }
+    fn get_greyscale(&self) -> u8 {
+        self.r_ctrl_one & 0x01
+    }
+    // Red, green and blue from bit 0
+    fn get_emphasis(&self) -> u8 {
+        self.r_ctrl_one >> 5
     }
     fn get_ctrl_one(&self) -> u8 {
         self.r_ctrl_one
@@ -158,10 +191,22 @@ impl Register for PpuRegister {
         self
     }
  
+    fn set_spritehit(&mut self) -> &mut Self {
+        self.r_status |= 0x40;
+        self
+    }
     fn clear_spritehit(&mut self) -> &mut Self {
         self.r_status &= 0b1011_1111;
         self
     }
+    fn set_sprite_overflow(&mut self) -> &mut Self {
+        self.r_status |= 0x20;
+        self
+    }
+    fn clear_sprite_overflow(&mut self) -> &mut Self {
+        self.r_status &= 0b1101_1111;
+        self
+    }
     fn read_status(&mut self) -> u8 {
         let data = self.r_status;
         self.r_writing_lower_addr = false;
@@ -183,14 +228,17 @@ impl Register for PpuRegister {
     fn get_addr(&self) -> u16 {
         self.r_addr
     }
+    // Palette reads are not buffered, the buffer gets the nametable byte underneath instead.
+    // Palette entries are 6 bits, the 2 upper bits come from the latch.
     fn read_data(&mut self, mem: &mut PpuMem) -> u8 {
-        self.r_data = mem.peek(self.r_addr as usize);
+        let addr = self.r_addr as usize & 0x3FFF;
+        self.r_data = if addr >= 0x3F00 {
+            self.r_data_buffer = mem.peek(addr - 0x1000);
+            mem.peek(addr) | (self.r_io_latch & 0xC0)
+        } else {
+            std::mem::replace(&mut self.r_data_buffer, mem.peek(addr))
+        };
         self.incr_addr();
-        if self.r_addr < 0x3f00 {
-            let temp = self.r_data;
-            self.r_data = self.r_data_buffer;
-            self.r_data_buffer = temp;
-        }
         self.r_data
     }
     fn get_oam_dma(&self) -> u8 {
@@ -214,13 +262,9 @@ impl Register for PpuRegister {
         self.r_oam_addr = v;
         self
     }
-    fn write_oam_data(&mut self, v: u8, mem: &mut PpuMem, ram: &mut Ram) -> &mut Self {
-        let address = 0x0100*v as u16;
-        for _ in 0..255 {
-            let value = ram.peek(address + self.r_oam_addr as u16);
-            mem.write_sprite_data(self.r_oam_addr as usize, value);
-            self.r_oam_addr += 1;
-        }
+    fn write_oam_data(&mut self, v: u8, mem: &mut PpuMem) -> &mut Self {
+        mem.write_sprite_data(self.r_oam_addr as usize, v);
+        self.r_oam_addr = self.r_oam_addr.wrapping_add(1);
         self
     }
     fn set_scroll(&mut self, v: u8) -> &mut Self {
@@ -264,31 +308,44 @@ impl Register for PpuRegister {
     }
     fn set_oam_dma(&mut self, v: u8) -> &mut Self {
         self.r_oam_dma = v;
-        panic!("Not implemented");
+        self
     }
     fn peek(&mut self, i: u16, mem: &mut PpuMem) -> u8 {
-        match i {
-            0x2002 => self.read_status(),
+        let v = match i {
+            // Only the 3 upper bits of the status are driven
+            0x2002 => (self.read_status() & 0xE0) | (self.r_io_latch & 0x1F),
             0x2004 => self.read_oam(),
             0x2007 => self.read_data(mem),
-            _ => {
-                panic!("Invalid read PPU register at {:x?}", i);
-            }
+            // Write only registers
+            _ => self.r_io_latch,
+        };
+        self.r_io_latch = v;
+        v
+    }
+    // What peek would return, without clearing the flags nor moving the addresses
+    fn inspect(&self, i: u16, mem: &PpuMem) -> u8 {
+        match i {
+            0x2002 => (self.r_status & 0xE0) | (self.r_io_latch & 0x1F),
+            0x2004 => self.read_oam(),
+            0x2007 if self.r_addr & 0x3FFF < 0x3F00 => self.r_data_buffer,
+            0x2007 => mem.peek(self.r_addr as usize) | (self.r_io_latch & 0xC0),
+            _ => self.r_io_latch,
         }
     }
     fn write(&mut self, i: u16, v: u8, mem: &mut PpuMem) -> u8 {
+        self.r_io_latch = v;
+        if self.r_warm_up && matches!(i, 0x2000 | 0x2001 | 0x2005 | 0x2006) {
+            return v;
+        }
         match i {
             0x2000 => self.set_ctrl_zero(v),
             0x2001 => self.set_ctrl_one(v),
             0x2003 => self.set_oam_addr(v),
-            0x2004 => self,
+            0x2004 => self.write_oam_data(v, mem),
             0x2005 => self.set_scroll(v),
             0x2006 => self.set_addr(v as u16),
             0x2007 => self.write_data(v, mem),
-            0x4014 => self,
-            _ => {
-                panic!("Invalid write in PPU register at {:x?} : {:x?}", i, v);
-            }
+            _ => self,
         };
         v
     }
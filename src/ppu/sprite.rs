@@ -1,9 +1,11 @@
+use crate::ppu::mem::PpuMem;
+
 use std::fmt;
 
+#[derive(Hash)]
 pub struct SpriteMem {
     oam: [u8; 0x100],
     secondary_oam: [u8; 0x20],
-    // secondary_index: u8,
 }
 
 impl SpriteMem {
@@ -11,13 +13,9 @@ impl SpriteMem {
         SpriteMem {
             oam: [0; 0x100],
             secondary_oam: [0; 0x20],
-            // secondary_index: 0,
         }
     }
 
-    /*pub fn peek(&mut self, i: usize) -> u8 {
-        self.mem[i]
-    }*/
     pub fn write_data(&mut self, i: usize, value: u8) -> u8 {
         self.oam[i] = value;
         value
@@ -28,17 +26,188 @@ impl SpriteMem {
     pub fn get_secondary(&self) -> &[u8] {
         &self.secondary_oam
     }
-    /*pub fn push_secondary(&mut self, v: u8) {
-        self.secondary_oam[self.secondary_index as usize] = v;
-        self.secondary_index += 1;
+    pub fn write_secondary(&mut self, i: usize, value: u8) {
+        self.secondary_oam[i] = value;
     }
-    pub fn is_secondary_full(&self) -> bool {
-        self.secondary_index == 9
+}
+
+#[derive(Hash, PartialEq)]
+enum Evaluation {
+    // Looking for the sprites of the next line and copying them to the secondary OAM
+    Copying,
+    // 8 sprites found, looking for a 9th one
+    Overflow,
+    Done,
+}
+
+// Pattern and attributes of a sprite of the current line, loaded during dots 257-320
+#[derive(Hash, Copy, Clone)]
+struct SpriteSlot {
+    y: u8,
+    tile: u8,
+    attr: u8,
+    x: u8,
+    low: u8,
+    high: u8,
+}
+
+impl SpriteSlot {
+    // Colour of the pixel at x, 0 when transparent or outside of the sprite
+    fn color(&self, x: u8) -> u8 {
+        match x.checked_sub(self.x) {
+            Some(column) if column < 8 => (((self.high >> (7 - column)) & 1) << 1) | ((self.low >> (7 - column)) & 1),
+            _ => 0,
+        }
+    }
+}
+
+#[derive(Hash)]
+pub struct Sprites {
+    // Sprite n and byte m read from the OAM, number of sprites found for the next line
+    n: u8,
+    m: u8,
+    found: u8,
+    evaluation: Evaluation,
+    // Sprite 0 is in range of the next line, and is the first slot of the current one
+    zero_found: bool,
+    zero_in_line: bool,
+    slots: [SpriteSlot; 8],
+}
+
+impl Sprites {
+    pub fn new() -> Sprites {
+        Sprites {
+            n: 0,
+            m: 0,
+            found: 0,
+            evaluation: Evaluation::Done,
+            zero_found: false,
+            zero_in_line: false,
+            slots: [SpriteSlot { y: 0xFF, tile: 0xFF, attr: 0xFF, x: 0xFF, low: 0, high: 0 }; 8],
+        }
+    }
+    // Dots 1-64: the secondary OAM is filled with $FF, one byte every 2 dots
+    pub fn clear_secondary(&mut self, mem: &mut SpriteMem, dot: i16) {
+        if dot % 2 == 1 {
+            mem.write_secondary((dot / 2) as usize, 0xFF);
+        }
+    }
+    pub fn start_evaluation(&mut self) {
+        self.n = 0;
+        self.m = 0;
+        self.found = 0;
+        self.zero_found = false;
+        self.evaluation = Evaluation::Copying;
+    }
+    // No sprite is evaluated on the pre-render line, the first line has none
+    pub fn skip_evaluation(&mut self) {
+        self.found = 0;
+        self.zero_found = false;
+        self.evaluation = Evaluation::Done;
+    }
+    // Dots 65-256: OAM is read on odd dots and the secondary OAM written on even dots, a call
+    // does both. Sprites are 8 or 16 lines tall. Returns true when the sprite overflow flag is set.
+    pub fn evaluate(&mut self, mem: &mut SpriteMem, line: i16, height: u8) -> bool {
+        if self.evaluation == Evaluation::Done {
+            return false;
+        }
+        let v = mem.oam[4 * self.n as usize + self.m as usize];
+        let in_range = line - (v as i16) >= 0 && line - (v as i16) < height as i16;
+        match self.evaluation {
+            Evaluation::Copying => {
+                // The Y coordinate is copied before being checked
+                mem.write_secondary(4 * self.found as usize + self.m as usize, v);
+                if self.m == 0 && !in_range {
+                    self.next_sprite();
+                } else if self.m == 0 && self.n == 0 {
+                    self.zero_found = true;
+                    self.m += 1;
+                } else if self.m == 3 {
+                    self.m = 0;
+                    self.found += 1;
+                    self.next_sprite();
+                } else {
+                    self.m += 1;
+                }
+                false
+            }
+            // Hardware bug: m is incremented along with n, so the bytes checked after the 8th
+            // sprite are no longer Y coordinates and the flag has false positives and negatives
+            Evaluation::Overflow if in_range => {
+                self.evaluation = Evaluation::Done;
+                true
+            }
+            Evaluation::Overflow => {
+                self.m = (self.m + 1) & 3;
+                self.n += 1;
+                if self.n == 64 {
+                    self.evaluation = Evaluation::Done;
+                }
+                false
+            }
+            Evaluation::Done => unreachable!(),
+        }
+    }
+    fn next_sprite(&mut self) {
+        self.n += 1;
+        if self.n == 64 {
+            self.evaluation = Evaluation::Done;
+        } else if self.found == 8 {
+            self.evaluation = Evaluation::Overflow;
+        }
+    }
+    // Dots 257-320: 8 dots per sprite of the secondary OAM. Attributes and X are latched
+    // first, the pattern bytes are fetched on the 6th and 8th dots. Missing sprites are transparent.
+    // 8x16 sprites take their pattern table from bit 0 of the tile, the top half is the even tile.
+    pub fn fetch(&mut self, mem: &PpuMem, line: i16, dot: i16, table: u8, height: u8) {
+        let i = ((dot - 257) / 8) as usize;
+        let slot = &mut self.slots[i];
+        match (dot - 257) % 8 {
+            0 => {
+                if i == 0 {
+                    self.zero_in_line = self.zero_found;
+                }
+                let secondary = mem.spr_mem.get_secondary();
+                slot.y = secondary[4 * i];
+                slot.tile = secondary[4 * i + 1];
+                slot.attr = secondary[4 * i + 2];
+                slot.x = secondary[4 * i + 3];
+            }
+            5 | 7 => {
+                let plane = if (dot - 257) % 8 == 5 { 0 } else { 8 };
+                let mut row = (line - slot.y as i16) as u16 & (height as u16 - 1);
+                if slot.attr & 0x80 != 0 {
+                    row = height as u16 - 1 - row;
+                }
+                let (table, tile) = match height {
+                    16 => (slot.tile & 1, (slot.tile & 0xFE) as u16 | (row >> 3)),
+                    _ => (table, slot.tile as u16),
+                };
+                let addr = 0x1000 * table as u16 + 16 * tile + (row & 7) + plane;
+                let mut pattern = if i < self.found as usize { mem.peek(addr as usize) } else { 0 };
+                if slot.attr & 0x40 != 0 {
+                    pattern = pattern.reverse_bits();
+                }
+                if plane == 0 {
+                    slot.low = pattern;
+                } else {
+                    slot.high = pattern;
+                }
+            }
+            _ => {}
+        }
+    }
+    // Palette index of the first opaque sprite pixel at x, and whether that sprite is behind the background
+    pub fn pixel(&self, x: u8) -> Option<(u8, bool)> {
+        self.slots.iter().find_map(|slot| match slot.color(x) {
+            0 => None,
+            color => Some((0x10 | (slot.attr & 3) << 2 | color, slot.attr & 0x20 != 0)),
+        })
+    }
+    // Sprite 0 has an opaque pixel at x, even when hidden by another sprite
+    pub fn zero_pixel(&self, x: u8) -> bool {
+        self.zero_in_line && self.slots[0].color(x) != 0
     }
-    pub fn clear_secondary(&mut self) {
-        self.secondary_oam = [0;0x20];
-        self.secondary_index = 0;
-    }*/
 }
 
 impl fmt::Display for SpriteMem {
@@ -58,4 +227,92 @@ impl fmt::Display for SpriteMem {
         }
         Ok(())
     }
-}
\ No newline at end of file
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{SpriteMem, Sprites};
+    use crate::ppu::mem::PpuMem;
+
+    fn evaluate_line(oam: &[(u8, u8)], line: i16) -> (SpriteMem, bool) {
+        evaluate_sprites(oam, line, 8)
+    }
+    fn evaluate_sprites(oam: &[(u8, u8)], line: i16, height: u8) -> (SpriteMem, bool) {
+        let mut mem = SpriteMem::new();
+        for i in 0..64 {
+            mem.write_data(4 * i, 0xF0);
+        }
+        for (i, (y, tile)) in oam.iter().enumerate() {
+            mem.write_data(4 * i, *y);
+            mem.write_data(4 * i + 1, *tile);
+        }
+        let mut sprites = Sprites::new();
+        for dot in 1..=64 {
+            sprites.clear_secondary(&mut mem, dot);
+        }
+        sprites.start_evaluation();
+        let mut overflow = false;
+        for _ in (65..=256).step_by(2) {
+            overflow |= sprites.evaluate(&mut mem, line, height);
+        }
+        (mem, overflow)
+    }
+
+    #[test]
+    fn evaluation_should_copy_the_first_8_sprites_in_range() {
+        let oam: Vec<(u8, u8)> = (0..10).map(|i| (if i == 1 { 0x80 } else { 0x10 }, i)).collect();
+        let (mem, overflow) = evaluate_line(&oam, 0x12);
+        let tiles: Vec<u8> = mem.get_secondary().chunks(4).map(|sprite| sprite[1]).collect();
+        assert_eq!(tiles, vec![0, 2, 3, 4, 5, 6, 7, 8]);
+        assert!(overflow);
+    }
+    #[test]
+    fn unused_secondary_oam_should_stay_cleared() {
+        let (mem, overflow) = evaluate_line(&[(0x10, 1)], 0x10);
+        assert_eq!(&mem.get_secondary()[..4], &[0x10, 1, 0, 0]);
+        // Y of the last sprite checked is copied to the free slot, the rest is $FF
+        assert_eq!(mem.get_secondary()[4], 0xF0);
+        assert!(mem.get_secondary()[5..].iter().all(|v| *v == 0xFF));
+        assert!(!overflow);
+    }
+    #[test]
+    fn overflow_check_should_read_diagonally() {
+        // 8 sprites in range, then sprite 9 is out of range but the tile of sprite 10 looks in range
+        let mut oam: Vec<(u8, u8)> = (0..8).map(|_| (0x10, 0)).collect();
+        oam.push((0x80, 0));
+        oam.push((0x80, 0x10));
+        assert!(evaluate_line(&oam, 0x10).1);
+        // And a 9th sprite in range is missed when m is no longer 0
+        let mut oam: Vec<(u8, u8)> = (0..8).map(|_| (0x10, 0)).collect();
+        oam.push((0x80, 0));
+        oam.push((0x10, 0x80));
+        assert!(!evaluate_line(&oam, 0x10).1);
+    }
+    #[test]
+    fn tall_sprites_should_be_in_range_for_16_lines() {
+        let (mem, _) = evaluate_sprites(&[(0x10, 1), (0x20, 2)], 0x1F, 16);
+        assert_eq!(&mem.get_secondary()[..2], &[0x10, 1]);
+        assert_eq!(mem.get_secondary()[4], 0xF0);
+        let (mem, _) = evaluate_sprites(&[(0x10, 1)], 0x20, 16);
+        assert_eq!(mem.get_secondary()[1], 0xFF);
+    }
+    #[test]
+    fn tall_sprites_should_flip_the_pair_vertically() {
+        let mut mem = PpuMem::new();
+        // Tile $03 selects tiles 2 and 3 of the second pattern table
+        mem.write(0x1000 + 2 * 16 + 3, 0x80);
+        mem.write(0x1000 + 3 * 16 + 4, 0x40);
+        for (attr, color_at) in [(0x00, 0), (0x80, 1)].iter() {
+            for (i, v) in [0x10, 0x03, *attr, 0x00].iter().enumerate() {
+                mem.spr_mem.write_secondary(i, *v);
+            }
+            let mut sprites = Sprites::new();
+            sprites.start_evaluation();
+            sprites.found = 1;
+            for dot in 257..=264 {
+                sprites.fetch(&mem, 0x13, dot, 0, 16);
+            }
+            assert_eq!(sprites.pixel(*color_at), Some((0x11, false)));
+        }
+    }
+}
@@ -3,10 +3,13 @@ use sdl2::render::*;
 use sdl2::video::*;
 use sdl2::pixels::*;
 
+use crate::ppu::frame::Frame;
+
 use std::time::Instant;
 
 const SCREEN_HEIGHT: u32 = 224;
 const SCREEN_WIDTH: u32 = 256;
+const OVERSCAN: u32 = 8;
 
 pub struct Renderer {
     renderer: Canvas<Window>,
@@ -39,7 +42,11 @@ impl Renderer {
             last_frame_time: Instant::now(),
         }
     }
-    pub fn draw_window(&mut self) {
+    pub fn draw_window(&mut self, frame: &Frame) {
+        for y in 0..SCREEN_HEIGHT {
+            let start = (y * SCREEN_WIDTH * 3) as usize;
+            self.display[start..start + (SCREEN_WIDTH * 3) as usize].copy_from_slice(frame.get_line(y + OVERSCAN));
+        }
         self.renderer.clear();
         self.texture.update(None, &self.display, (SCREEN_WIDTH * 3) as usize).unwrap();
         self.renderer.copy(&self.texture, None, None).unwrap();
@@ -48,12 +55,7 @@ impl Renderer {
         println!("{:?} FPS", (1000/ms) as f64);
         self.last_frame_time = Instant::now();*/
     }
-    pub fn set_pixel_rgb(&mut self, x: u32, y: u32, color: (u8,u8,u8)) {
-        let coords = get_coords(x, y);
-        self.display[coords as usize] = color.0;
-        self.display[(coords + 1) as usize] = color.1;
-        self.display[(coords + 2) as usize] = color.2;
-    }
+    #[allow(dead_code)]
     pub fn reset(&mut self) {
         self.display = [0u8; (SCREEN_WIDTH * SCREEN_HEIGHT * 3) as usize];
         self.texture.update(None, &self.display, (SCREEN_WIDTH * 3) as usize).unwrap();
@@ -61,12 +63,6 @@ impl Renderer {
     }
 }
 
-fn get_coords(x: u32, y: u32) -> u32 {
-    let x = x % SCREEN_WIDTH;
-    let y = y % SCREEN_HEIGHT;
-    (x + SCREEN_WIDTH * y) * 3
-}
-
 pub fn get_rgb(color: u32) -> (u8, u8, u8) {
     let r = ((color & 0xFF0000) >> 16) as u8;
     let g = ((color & 0x00FF00) >> 8) as u8;
@@ -1,10 +1,14 @@
+use crate::error::{Component, EmuError};
+use crate::memory::map::Handler;
 use crate::memory::Memory;
 use std::path::Path;
-use std::str;
 
+#[derive(Hash)]
 pub struct Cartbridge {
     program: Vec<u8>,
     character: Vec<u8>,
+    // Battery backed or work RAM at $6000-$7FFF
+    prg_ram: Vec<u8>,
     mapper: u8,
     size: usize,
     pub offset: usize,
@@ -15,30 +19,25 @@ impl Cartbridge {
         Cartbridge {
             program: Vec::new(),
             character: Vec::new(),
+            prg_ram: vec![0; 0x2000],
             mapper: 0,
             size: 0,
             offset: 0,
         }
     }
-    pub fn read_file(&mut self, path: String) -> Vec<u8> {
+    pub fn read_file(&mut self, path: String) -> Result<Vec<u8>, EmuError> {
         println!("ROM: Loading : {}", path);
-        match std::fs::read(Path::new(&path)) {
-            Result::Ok(rom) => {
-                return rom;
-            },
-            Result::Err(err) => {
-                println!("ROM: Cannot open .nes file: {}", path);
-                panic!(err);
-            }
-        }
+        std::fs::read(Path::new(&path))
+            .map_err(|e| EmuError::new(Component::Cartridge, format!("Cannot open .nes file {}: {}", path, e)))
     }
     #[allow(dead_code)]
     pub fn load_from_vec(&mut self, program: &Vec<u8>) {
         for i in program.iter() {
             self.program.push(*i);
         }
-        self.program.resize(0x8000, 0u8);
-        self.size = 0x8000;
+        // A single 16K bank, mirrored at $C000
+        self.program.resize(0x4000.max(self.program.len()), 0u8);
+        self.size = self.program.len();
     }
     #[allow(dead_code)]
     pub fn get_program(&mut self) -> &mut Vec<u8> {
@@ -47,33 +46,53 @@ impl Cartbridge {
     pub fn get_character(&mut self) -> &mut Vec<u8> {
         &mut self.character
     }
-    pub fn load_program(&mut self, data: &Vec<u8>) -> &mut Self {
-        println!("ROM: Loading buffer (size : {}) into Rom memory", data.len());
-        let rom_name = str::from_utf8(&data[0..3]).unwrap();
-        if rom_name != "NES" {
-            panic!("ROM: Invalid ROM name header");
+    // Mappers receive their commands through writes to $8000-$FFFF, NROM has no register
+    pub fn write_register(&mut self, _addr: u16, v: u8) -> u8 {
+        v
+    }
+    // NROM: 16K of PRG-ROM are mirrored at $C000, 32K fill $8000-$FFFF
+    fn prg_rom(&self, addr: u16) -> Option<u8> {
+        match self.program.len() {
+            0 => None,
+            len => Some(self.program[(addr as usize - 0x8000) % len]),
         }
-        let next = str::from_utf8(&data[3..4]).unwrap();
-        if next != "\x1a" {
-            panic!("ROM: Invalid ROM header");
+    }
+    pub fn load_program(&mut self, data: &Vec<u8>) -> Result<&mut Self, EmuError> {
+        let error = |message: String| EmuError::new(Component::Cartridge, message);
+        println!("ROM: Loading buffer (size : {}) into Rom memory", data.len());
+        if data.len() < 0x0010 || &data[0..4] != b"NES\x1a" {
+            return Err(error(String::from("Invalid iNES header")));
         }
         let prg_pages = data[4] as usize;
         println!("ROM: PRG_PAGES: {}", prg_pages);
+        if prg_pages == 0 {
+            return Err(error(String::from("No PRG-ROM")));
+        }
         self.offset = 0x4000 / prg_pages;
         let chr_pages = data[5] as usize;
         let _rom_control_one = data[6] & 0x01;
-        let mut character_rom_start = 0x0010 + prg_pages * 0x4000;
+        let mapper = (data[7] & 0xF0) | (data[6] >> 4);
+        if mapper != 0 {
+            return Err(error(format!("Unsupported mapper {}", mapper)));
+        }
+        let character_rom_start = 0x0010 + prg_pages * 0x4000;
         let character_rom_end = character_rom_start + chr_pages * 0x2000;
-        if character_rom_start + 0x0010 > data.len() {
-            character_rom_start = data.len() - 0x0010;
+        if character_rom_end > data.len() {
+            return Err(error(format!(
+                "Truncated file: {} PRG and {} CHR pages need {} bytes, got {}",
+                prg_pages,
+                chr_pages,
+                character_rom_end,
+                data.len()
+            )));
         }
-        self.program = data[0x0010..0x0010 + character_rom_start].to_vec();
+        self.program = data[0x0010..character_rom_start].to_vec();
         println!("ROM: PRG-ROM: {}", self.program.len());
         self.character = data[character_rom_start..character_rom_end].to_vec();
         println!("ROM: CHR-ROM: {}", self.character.len());
         self.size = self.program.len();
-        self.mapper = 0;
-        self
+        self.mapper = mapper;
+        Ok(self)
     }
 }
 
@@ -91,4 +110,59 @@ impl Memory for Cartbridge {
     fn get_mem(&self) -> &[u8] {
         &self.program[0..self.size]
     }
-}
\ No newline at end of file
+}
+
+impl Handler for Cartbridge {
+    fn read(&mut self, addr: u16, open_bus: u8) -> u8 {
+        self.inspect(addr, open_bus)
+    }
+    fn write(&mut self, addr: u16, v: u8) {
+        match addr {
+            0x6000..=0x7FFF => self.prg_ram[addr as usize - 0x6000] = v,
+            0x8000..=0xFFFF => {
+                self.write_register(addr, v);
+            }
+            _ => {}
+        }
+    }
+    fn inspect(&self, addr: u16, open_bus: u8) -> u8 {
+        match addr {
+            0x6000..=0x7FFF => self.prg_ram[addr as usize - 0x6000],
+            0x8000..=0xFFFF => self.prg_rom(addr).unwrap_or(open_bus),
+            _ => open_bus,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::Cartbridge;
+
+    fn header(prg_pages: u8, chr_pages: u8, flags: u8) -> Vec<u8> {
+        let mut data = vec![b'N', b'E', b'S', 0x1a, prg_pages, chr_pages, flags];
+        data.resize(0x0010, 0);
+        data
+    }
+
+    #[test]
+    fn load_program_should_split_prg_and_chr() {
+        let mut data = header(1, 1, 0);
+        data.resize(0x0010 + 0x4000 + 0x2000, 0xEA);
+        let mut rom = Cartbridge::new();
+        assert!(rom.load_program(&data).is_ok());
+        assert_eq!(rom.get_program().len(), 0x4000);
+        assert_eq!(rom.get_character().len(), 0x2000);
+    }
+    #[test]
+    fn load_program_should_reject_invalid_files() {
+        let mut rom = Cartbridge::new();
+        let message = |data: &Vec<u8>, rom: &mut Cartbridge| rom.load_program(data).err().unwrap().message;
+        assert_eq!(message(&vec![0x4E, 0x45], &mut rom), "Invalid iNES header");
+        assert_eq!(message(&header(0, 1, 0), &mut rom), "No PRG-ROM");
+        assert_eq!(message(&header(2, 1, 0x10), &mut rom), "Unsupported mapper 1");
+        assert_eq!(
+            message(&header(2, 1, 0), &mut rom),
+            "Truncated file: 2 PRG and 1 CHR pages need 40976 bytes, got 16"
+        );
+    }
+}
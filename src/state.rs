@@ -0,0 +1,48 @@
+// Hash of the emulated state, used to check that two runs are identical frame by frame.
+// FNV-1a: unlike std's DefaultHasher its output does not change between Rust releases.
+use std::hash::Hasher;
+
+const FNV_OFFSET: u64 = 0xcbf2_9ce4_8422_2325;
+const FNV_PRIME: u64 = 0x0000_0100_0000_01b3;
+
+pub struct StateHasher {
+    hash: u64,
+}
+
+impl StateHasher {
+    pub fn new() -> StateHasher {
+        StateHasher { hash: FNV_OFFSET }
+    }
+}
+
+impl Hasher for StateHasher {
+    fn write(&mut self, bytes: &[u8]) {
+        for b in bytes {
+            self.hash ^= *b as u64;
+            self.hash = self.hash.wrapping_mul(FNV_PRIME);
+        }
+    }
+    fn finish(&self) -> u64 {
+        self.hash
+    }
+}
+
+// Seed of the machine RNG, printed so that a run started without one can be replayed
+pub fn machine_seed(seed: Option<u64>) -> u64 {
+    let seed = seed.unwrap_or_else(rand::random);
+    println!("EMU: Seed {}", seed);
+    seed
+}
+
+#[cfg(test)]
+mod tests {
+    use super::StateHasher;
+    use std::hash::Hasher;
+
+    #[test]
+    fn hasher_should_match_fnv1a() {
+        let mut hasher = StateHasher::new();
+        hasher.write(b"a");
+        assert_eq!(hasher.finish(), 0xaf63_dc4c_8601_ec8c);
+    }
+}